@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// Offered counts per user and asset in one `Check`; repeated entries for
+/// the same user and asset are summed.
+pub type Offers = BTreeMap<UserId, BTreeMap<AssetId, u32>>;
+
+fn offers(announcements: &Announcements) -> Offers {
+    let mut offers = Offers::new();
+    for offer in &announcements.0 {
+        let backets = offers.entry(offer.user_id.clone()).or_default();
+        for backet in &offer.backets {
+            let count = backets.entry(backet.asset_id.clone()).or_default();
+            *count = count.saturating_add(backet.count.get());
+        }
+    }
+    offers
+}
+
+/// A change of one user's offer for one asset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BacketChange {
+    Added {
+        user_id: UserId,
+        asset_id: AssetId,
+        count: u32,
+    },
+    Removed {
+        user_id: UserId,
+        asset_id: AssetId,
+        count: u32,
+    },
+    Changed {
+        user_id: UserId,
+        asset_id: AssetId,
+        from: u32,
+        to: u32,
+    },
+}
+
+impl fmt::Display for BacketChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BacketChange::Added {
+                user_id,
+                asset_id,
+                count,
+            } => write!(f, "+ {user_id} {asset_id} {count}"),
+            BacketChange::Removed {
+                user_id,
+                asset_id,
+                count,
+            } => write!(f, "- {user_id} {asset_id} {count}"),
+            BacketChange::Changed {
+                user_id,
+                asset_id,
+                from,
+                to,
+            } => write!(f, "~ {user_id} {asset_id} {from} -> {to}"),
+        }
+    }
+}
+
+/// Difference between two consecutive `Check` snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotDiff {
+    /// Request of the previous snapshot; `None` for the first one, which is
+    /// compared against an empty market.
+    pub from: Option<NonZeroU32>,
+    pub to: NonZeroU32,
+    /// Users with offers in the new snapshot only.
+    pub users_added: Vec<UserId>,
+    /// Users with offers in the old snapshot only.
+    pub users_removed: Vec<UserId>,
+    /// Per-asset changes, ordered by user and asset.
+    pub changes: Vec<BacketChange>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn to_json(&self) -> String {
+        let users = |users: &[UserId]| json::array(users.iter().map(|u| json::string(&u.0)));
+        let changes = self.changes.iter().map(|change| {
+            let (kind, user_id, asset_id, from, to) = match change {
+                BacketChange::Added {
+                    user_id,
+                    asset_id,
+                    count,
+                } => ("added", user_id, asset_id, 0, *count),
+                BacketChange::Removed {
+                    user_id,
+                    asset_id,
+                    count,
+                } => ("removed", user_id, asset_id, *count, 0),
+                BacketChange::Changed {
+                    user_id,
+                    asset_id,
+                    from,
+                    to,
+                } => ("changed", user_id, asset_id, *from, *to),
+            };
+            json::object([
+                ("change", json::string(kind)),
+                ("user_id", json::string(&user_id.0)),
+                ("asset_id", json::string(&asset_id.0)),
+                ("from", from.to_string()),
+                ("to", to.to_string()),
+            ])
+        });
+        json::object([
+            (
+                "from",
+                self.from.map_or("null".to_owned(), |id| id.to_string()),
+            ),
+            ("to", self.to.to_string()),
+            ("users_added", users(&self.users_added)),
+            ("users_removed", users(&self.users_removed)),
+            ("changes", json::array(changes)),
+        ])
+    }
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.from {
+            Some(from) => writeln!(f, "request {from} -> {}:", self.to)?,
+            None => writeln!(f, "request {}:", self.to)?,
+        }
+        for user_id in &self.users_added {
+            writeln!(f, "  + {user_id}")?;
+        }
+        for user_id in &self.users_removed {
+            writeln!(f, "  - {user_id}")?;
+        }
+        for change in &self.changes {
+            writeln!(f, "  {change}")?;
+        }
+        if self.is_empty() {
+            writeln!(f, "  (no changes)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes what changed from `old` to `new`.
+pub fn diff(from: Option<NonZeroU32>, old: &Offers, to: NonZeroU32, new: &Offers) -> SnapshotDiff {
+    let empty = BTreeMap::new();
+    let mut users: Vec<&UserId> = old.keys().chain(new.keys()).collect();
+    users.sort();
+    users.dedup();
+
+    let mut changes = Vec::new();
+    for user_id in users {
+        let old = old.get(user_id).unwrap_or(&empty);
+        let new = new.get(user_id).unwrap_or(&empty);
+        let mut assets: Vec<&AssetId> = old.keys().chain(new.keys()).collect();
+        assets.sort();
+        assets.dedup();
+        for asset_id in assets {
+            let (user_id, asset_id) = (user_id.clone(), asset_id.clone());
+            match (old.get(&asset_id), new.get(&asset_id)) {
+                (None, Some(&count)) => changes.push(BacketChange::Added {
+                    user_id,
+                    asset_id,
+                    count,
+                }),
+                (Some(&count), None) => changes.push(BacketChange::Removed {
+                    user_id,
+                    asset_id,
+                    count,
+                }),
+                (Some(&from), Some(&to)) if from != to => changes.push(BacketChange::Changed {
+                    user_id,
+                    asset_id,
+                    from,
+                    to,
+                }),
+                _ => {}
+            }
+        }
+    }
+    SnapshotDiff {
+        from,
+        to,
+        users_added: new
+            .keys()
+            .filter(|u| !old.contains_key(u))
+            .cloned()
+            .collect(),
+        users_removed: old
+            .keys()
+            .filter(|u| !new.contains_key(u))
+            .cloned()
+            .collect(),
+        changes,
+    }
+}
+
+/// `App::Trace Check` snapshots in log order.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Snapshots {
+    pub snapshots: Vec<(NonZeroU32, Offers)>,
+}
+
+impl Accumulator for Snapshots {
+    /// Records a `Check` line; other lines are ignored.
+    fn add(&mut self, log: &LogLine) {
+        if let LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) = &log.kind {
+            self.snapshots.push((log.request_id, offers(announcements)));
+        }
+    }
+}
+
+impl Snapshots {
+    /// Diffs of every snapshot against the one before it.
+    pub fn diffs(&self) -> Vec<SnapshotDiff> {
+        let empty = Offers::new();
+        let mut previous = (None, &empty);
+        self.snapshots
+            .iter()
+            .map(|(request_id, offers)| {
+                let diff = diff(previous.0, previous.1, *request_id, offers);
+                previous = (Some(*request_id), offers);
+                diff
+            })
+            .collect()
+    }
+
+    /// Renders the snapshots as a JSON array.
+    pub fn to_json(&self) -> String {
+        json::array(self.snapshots.iter().map(|(request_id, offers)| {
+            let offers = offers.iter().map(|(user_id, backets)| {
+                let backets = backets
+                    .iter()
+                    .map(|(asset_id, count)| (&asset_id.0, count.to_string()));
+                (&user_id.0, json::object(backets))
+            });
+            json::object([
+                ("request_id", request_id.to_string()),
+                ("offers", json::object(offers)),
+            ])
+        }))
+    }
+
+    /// Renders [Snapshots::diffs] as a JSON array.
+    pub fn diffs_to_json(&self) -> String {
+        json::array(self.diffs().iter().map(SnapshotDiff::to_json))
+    }
+}
+
+impl fmt::Display for Snapshots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (request_id, offers) in &self.snapshots {
+            writeln!(f, "request {request_id}:")?;
+            for (user_id, backets) in offers {
+                let backets: Vec<_> = backets
+                    .iter()
+                    .map(|(asset_id, count)| format!("{asset_id} {count}"))
+                    .collect();
+                writeln!(f, "  {user_id}: {}", backets.join(", "))?;
+            }
+            if offers.is_empty() {
+                writeln!(f, "  (empty)")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const SOURCE: &str = r#"
+App::Trace Check [] requestid=8
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},Backet{"asset_id":"eggs","count":2,},],},UserBackets{"user_id":"Carol","backets":[Backet{"asset_id":"milk","count":1,},],},] requestid=10
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":1,},Backet{"asset_id":"bread","count":4,},],},UserBackets{"user_id":"Dave","backets":[Backet{"asset_id":"milk","count":2,},],},] requestid=12
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":1,},],},UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"bread","count":4,},],},UserBackets{"user_id":"Dave","backets":[Backet{"asset_id":"milk","count":2,},],},] requestid=13
+"#;
+
+    fn snapshots() -> Snapshots {
+        accumulate(SOURCE)
+    }
+
+    fn change(kind: char, user: &str, asset: &str, from: u32, to: u32) -> BacketChange {
+        let (user_id, asset_id) = (UserId(user.into()), AssetId(asset.into()));
+        match kind {
+            '+' => BacketChange::Added {
+                user_id,
+                asset_id,
+                count: to,
+            },
+            '-' => BacketChange::Removed {
+                user_id,
+                asset_id,
+                count: from,
+            },
+            _ => BacketChange::Changed {
+                user_id,
+                asset_id,
+                from,
+                to,
+            },
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let diffs = snapshots().diffs();
+        assert_eq!(diffs.len(), 4);
+        assert_eq!((diffs[0].from, diffs[0].to), (None, nz(8)));
+        assert!(diffs[0].is_empty());
+        assert_eq!(diffs[1].changes.len(), 3);
+
+        let diff = &diffs[2];
+        assert_eq!((diff.from, diff.to), (Some(nz(10)), nz(12)));
+        assert_eq!(diff.users_added, [UserId("Dave".into())]);
+        assert_eq!(diff.users_removed, [UserId("Carol".into())]);
+        assert_eq!(
+            diff.changes,
+            [
+                change('+', "Bob", "bread", 0, 4),
+                change('-', "Bob", "eggs", 2, 0),
+                change('~', "Bob", "milk", 3, 1),
+                change('-', "Carol", "milk", 1, 0),
+                change('+', "Dave", "milk", 0, 2),
+            ]
+        );
+        // The same offers split over two `UserBackets` entries are no change.
+        assert!(diffs[3].is_empty());
+    }
+
+    #[test]
+    fn test_diff_display() {
+        let diffs = snapshots().diffs();
+        assert_eq!(
+            diffs[2].to_string(),
+            "request 10 -> 12:\n  + Dave\n  - Carol\n  + Bob bread 4\n  - Bob eggs 2\n  ~ Bob milk 3 -> 1\n  - Carol milk 1\n  + Dave milk 2\n"
+        );
+        assert_eq!(diffs[3].to_string(), "request 12 -> 13:\n  (no changes)\n");
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::parse::*;
+use crate::parse_line;
+
+/// What to replace `Connect` auth data with.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AuthPolicy {
+    /// All bytes zero.
+    Zero,
+    /// Keyed hash of the original, same length: equal tokens stay equal.
+    #[default]
+    Hash,
+}
+
+/// Counts reported by [Anonymizer::anonymize_log].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AnonymizeReport {
+    pub written: usize,
+    /// Non-blank lines that did not parse and were left out of the output.
+    pub dropped: usize,
+    /// Warnings about written lines with their 1-based line number, e.g.
+    /// unknown keys that were skipped and are missing from the output.
+    pub warnings: Vec<(usize, String)>,
+}
+
+/// Replaces identifying data in log lines with keyed pseudonyms.
+///
+/// Pseudonyms are `user_` / `asset_` and 16 hex digits, derived with
+/// HMAC-SHA256 from a seed, so the same seed maps an id to the same
+/// pseudonym in every line, file and run, while a vendor without the seed
+/// cannot reverse it. Ids are rewritten in structured fields and in
+/// `"user_id": ...` / `"asset_id": ...` pairs inside trace payloads.
+/// Every id [learned](Anonymizer::learn) from any line is also replaced
+/// wherever it appears as a whole word in free text: messages, payloads and
+/// string attributes, e.g. `can't buy milk` in an error message.
+#[derive(Clone)]
+pub struct Anonymizer {
+    mac: Hmac<Sha256>,
+    auth: AuthPolicy,
+    /// Ids seen so far, replaced in free text, keyed by their [leading
+    /// word](leading_word) and longest first.
+    known: HashMap<String, Vec<(String, IdKind)>>,
+}
+
+impl Anonymizer {
+    pub fn new(seed: &[u8], auth: AuthPolicy) -> Self {
+        Anonymizer {
+            mac: Hmac::new_from_slice(seed).expect("HMAC accepts keys of any length"),
+            auth,
+            known: HashMap::new(),
+        }
+    }
+
+    /// Remembers the ids of `log`, structured or keyed in a payload, so
+    /// that [Anonymizer::anonymize] replaces them in the free text of any line.
+    pub fn learn(&mut self, log: &LogLine) {
+        let mut kind = log.kind.clone();
+        kind.ids_mut(|id_kind, id| self.remember(id_kind, id));
+        kind.texts_mut(|text| {
+            map_payload_ids(text, |id_kind, id| {
+                self.remember(id_kind, id);
+                String::new()
+            });
+        });
+    }
+
+    fn remember(&mut self, kind: IdKind, id: &str) {
+        if id.is_empty() {
+            return;
+        }
+        let ids = self.known.entry(leading_word(id).to_owned()).or_default();
+        let longest_first = |(known, _): &(String, IdKind)| id.len().cmp(&known.len()).then(known.as_str().cmp(id));
+        if let Err(idx) = ids.binary_search_by(longest_first) {
+            ids.insert(idx, (id.to_owned(), kind));
+        }
+    }
+
+    /// Replaces every whole-word occurrence of a known id in `text`, the
+    /// longest one where several match. Only the ids sharing the leading
+    /// word at each word start are tried, so this is linear in `text` for
+    /// any number of known ids.
+    fn scrub(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut after_word = false;
+        while let Some(c) = rest.chars().next() {
+            let found = (!after_word)
+                .then(|| {
+                    self.known.get(leading_word(rest))?.iter().find(|(id, _)| {
+                        rest.starts_with(id.as_str()) && !rest[id.len()..].starts_with(is_word)
+                    })
+                })
+                .flatten();
+            match found {
+                Some((id, kind)) => {
+                    out.push_str(&self.id(*kind, id));
+                    rest = &rest[id.len()..];
+                    after_word = id.ends_with(is_word);
+                }
+                None => {
+                    out.push(c);
+                    rest = &rest[c.len_utf8()..];
+                    after_word = is_word(c);
+                }
+            }
+        }
+        out
+    }
+
+    fn digest(&self, domain: &str, data: &[u8]) -> [u8; 32] {
+        let mut mac = self.mac.clone();
+        mac.update(domain.as_bytes());
+        mac.update(&[0]);
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn pseudonym(&self, domain: &str, value: &str) -> String {
+        let digest = self.digest(domain, value.as_bytes());
+        // 64 bits: a collision, which would merge two ids, is expected
+        // only after about 5 billion distinct ones
+        let hex: String = digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+        format!("{domain}_{hex}")
+    }
+
+    pub fn user_id(&self, id: &UserId) -> UserId {
+        UserId(self.pseudonym("user", &id.0))
+    }
+
+    pub fn asset_id(&self, id: &AssetId) -> AssetId {
+        AssetId(self.pseudonym("asset", &id.0))
+    }
+
+    fn auth_data(&self, auth: &mut AuthData) {
+        match self.auth {
+            AuthPolicy::Zero => auth.0.fill(0),
+            AuthPolicy::Hash => {
+                let original = auth.0.clone();
+                for (block, chunk) in auth.0.chunks_mut(32).enumerate() {
+                    let mut data = (block as u32).to_le_bytes().to_vec();
+                    data.extend_from_slice(&original);
+                    chunk.copy_from_slice(&self.digest("auth", &data)[..chunk.len()]);
+                }
+            }
+        }
+    }
+
+    /// Replaces the values of `"user_id"` and `"asset_id"` keys in free
+    /// text, quoted (`"Bob"`) or bare (`10`).
+    pub fn payload(&self, text: &str) -> String {
+        map_payload_ids(text, |kind, value| self.id(kind, value))
+    }
+
+    fn id(&self, kind: IdKind, value: &str) -> String {
+        match kind {
+            IdKind::User => self.pseudonym("user", value),
+            IdKind::Asset => self.pseudonym("asset", value),
+        }
+    }
+
+    /// Anonymized copy of `log`; request ids, variants and amounts are kept.
+    /// Free text is scrubbed of the [learned](Anonymizer::learn) ids only.
+    pub fn anonymize(&self, log: &LogLine) -> LogLine {
+        let mut log = log.clone();
+        if let Some(payload) = log.kind.payload_mut() {
+            *payload = self.payload(payload);
+        }
+        log.kind.texts_mut(|text| *text = self.scrub(text));
+        log.attributes.texts_mut(|text| *text = self.scrub(text));
+        if let LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(auth))) = &mut log.kind {
+            self.auth_data(auth);
+        }
+        log.kind.ids_mut(|kind, id| *id = self.id(kind, id));
+        log
+    }
+
+    /// Parses every line of `input` and writes its anonymized form to
+    /// `output` in the log grammar. The whole log is read first, so an id
+    /// seen in any line is scrubbed from the free text of every line. Lines
+    /// that do not parse are dropped, since they cannot be scrubbed reliably.
+    pub fn anonymize_log(
+        &mut self,
+        input: impl Read,
+        mut output: impl Write,
+    ) -> io::Result<AnonymizeReport> {
+        let mut report = AnonymizeReport::default();
+        let mut reader = io::BufReader::new(input);
+        let mut buf = Vec::new();
+        let mut logs = Vec::new();
+        let mut line_number = 0;
+        while reader.read_until(b'\n', &mut buf)? > 0 {
+            line_number += 1;
+            let line = String::from_utf8_lossy(&buf);
+            match parse_line(&line) {
+                Some((log, warnings)) => {
+                    self.learn(&log);
+                    logs.push(log);
+                    let warnings = warnings.into_iter().map(|warning| (line_number, warning));
+                    report.warnings.extend(warnings);
+                }
+                None if line.trim().is_empty() => {}
+                None => report.dropped += 1,
+            }
+            buf.clear();
+        }
+        for log in &logs {
+            writeln!(output, "{}", self.anonymize(log).to_log_string())?;
+            report.written += 1;
+        }
+        output.flush()?;
+        Ok(report)
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The word characters `text` starts with, empty if it starts with another one.
+fn leading_word(text: &str) -> &str {
+    &text[..text.find(|c: char| !is_word(c)).unwrap_or(text.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadMode, read_log};
+
+    const SOURCE: &str = r#"
+System::Trace SendRequest "Jupiter->CreateUser{\"user_id\": \"Bob\", \"authrized_capital\": 1000,}" requestid=4
+System::Trace SendRequest "CreateUser{\"user_id\": 10, \"authrized_capital\": 1000,}" requestid=2
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=10
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+this line is not in the grammar
+"#;
+
+    fn anonymized(auth: AuthPolicy) -> (String, AnonymizeReport) {
+        let mut out = Vec::new();
+        let report = Anonymizer::new(b"seed", auth)
+            .anonymize_log(SOURCE.as_bytes(), &mut out)
+            .unwrap();
+        (String::from_utf8(out).unwrap(), report)
+    }
+
+    #[test]
+    fn test_consistent_pseudonyms() {
+        let (out, report) = anonymized(AuthPolicy::Hash);
+        assert_eq!(
+            report,
+            AnonymizeReport {
+                written: 5,
+                dropped: 1,
+                warnings: vec![],
+            }
+        );
+        assert!(!out.contains("Bob") && !out.contains("Alice") && !out.contains("milk"));
+
+        let anonymizer = Anonymizer::new(b"seed", AuthPolicy::Hash);
+        let bob = anonymizer.user_id(&UserId("Bob".into())).0;
+        let milk = anonymizer.asset_id(&AssetId("milk".into())).0;
+        assert!(bob.starts_with("user_") && bob.len() == 21);
+        assert_eq!(out.matches(&bob).count(), 3);
+        assert_eq!(out.matches(&milk).count(), 2);
+        assert!(out.contains(&format!(
+            r#"\"user_id\": {},"#,
+            anonymizer.pseudonym("user", "10")
+        )));
+
+        let other = Anonymizer::new(b"other seed", AuthPolicy::Hash);
+        assert_ne!(other.user_id(&UserId("Bob".into())).0, bob);
+    }
+
+    #[test]
+    fn test_output_reparses() {
+        let (out, _) = anonymized(AuthPolicy::Hash);
+        let logs = read_log(out.as_bytes(), ReadMode::All, vec![]).unwrap();
+        assert_eq!(logs.len(), 5);
+        assert_eq!(logs[4].request_id.get(), 10);
+    }
+
+    /// Whether `id` occurs in `text` as a whole word.
+    fn contains_word(text: &str, id: &str) -> bool {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        text.match_indices(id).any(|(pos, _)| {
+            !text[..pos].ends_with(is_word) && !text[pos + id.len()..].starts_with(is_word)
+        })
+    }
+
+    #[test]
+    fn test_free_text_is_scrubbed() {
+        const SOURCE: &str = r#"
+App::Journal RegisterAsset {"asset_id": "milk", "user_id": "Bob", "liquidity": 10000,} requestid=5
+App::Error LackOf "can't buy milk, no sellers" requestid=8
+App::Warn LowStock "Bob is out of milk" requestid=8 session=Bob note="milk for Bob" latency_ms=12
+App::Fatal Panic "bobby and milky are other words" requestid=9
+"#;
+        let mut out = Vec::new();
+        let mut anonymizer = Anonymizer::new(b"seed", AuthPolicy::Hash);
+        anonymizer.anonymize_log(SOURCE.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let bob = anonymizer.user_id(&UserId("Bob".into())).0;
+        let milk = anonymizer.asset_id(&AssetId("milk".into())).0;
+        assert!(!contains_word(&out, "Bob") && !contains_word(&out, "milk"), "{out}");
+        assert!(out.contains(&format!(r#""can't buy {milk}, no sellers""#)), "{out}");
+        assert!(out.contains(&format!(r#""{bob} is out of {milk}""#)), "{out}");
+        assert!(out.contains(&format!(r#"session={bob} note="{milk} for {bob}" latency_ms=12"#)));
+        assert!(out.contains("bobby and milky"));
+    }
+
+    #[test]
+    fn test_scrub_many_ids() {
+        let mut anonymizer = Anonymizer::new(b"seed", AuthPolicy::Hash);
+        for n in 0..10_000 {
+            anonymizer.remember(IdKind::User, &format!("user{n}"));
+        }
+        for id in ["milk", "milk-2l", "-x"] {
+            anonymizer.remember(IdKind::Asset, id);
+        }
+        let id = |kind, value| anonymizer.id(kind, value);
+        assert_eq!(
+            anonymizer.scrub("user42 buys milk-2l, milk and -x; user42x and xmilk stay"),
+            format!(
+                "{} buys {}, {} and {}; user42x and xmilk stay",
+                id(IdKind::User, "user42"),
+                id(IdKind::Asset, "milk-2l"),
+                id(IdKind::Asset, "milk"),
+                id(IdKind::Asset, "-x"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_example_log_ids_are_gone() {
+        let source = include_str!("../example.log");
+        let mut out = Vec::new();
+        let mut anonymizer = Anonymizer::new(b"seed", AuthPolicy::Hash);
+        anonymizer.anonymize_log(source.as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let ids: Vec<String> = anonymizer.known.keys().cloned().collect();
+        for id in ["Bob", "Alice", "alice", "milk", "butter", "10"] {
+            assert!(ids.iter().any(|known| known == id), "{id} not learned");
+        }
+        for id in &ids {
+            let lines: Vec<&str> = out.lines().filter(|line| contains_word(line, id)).collect();
+            // `requestid=10` is no user id
+            let leaks = lines.iter().filter(|line| !line.ends_with(&format!("requestid={id}")));
+            assert_eq!(leaks.count(), 0, "{id} leaks in {lines:?}");
+        }
+    }
+
+    #[test]
+    fn test_auth_data() {
+        let original = AuthData(vec![7; 1024]);
+        let mut zeroed = original.clone();
+        Anonymizer::new(b"seed", AuthPolicy::Zero).auth_data(&mut zeroed);
+        assert_eq!(zeroed, AuthData(vec![0; 1024]));
+
+        let anonymizer = Anonymizer::new(b"seed", AuthPolicy::Hash);
+        let (mut first, mut second) = (original.clone(), original.clone());
+        anonymizer.auth_data(&mut first);
+        anonymizer.auth_data(&mut second);
+        assert_eq!(first, second);
+        assert_ne!(first, original);
+        assert_ne!(first.0[..32], first.0[32..64]);
+    }
+}
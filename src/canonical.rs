@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::json;
+use crate::parse::*;
+
+/// How user and asset ids are normalized before they are compared.
+///
+/// Steps run in order: trim surrounding whitespace, Unicode NFC, lowercase,
+/// then the alias table of the id's kind. Alias keys are normalized the
+/// same way when added; alias targets are used verbatim.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub trim: bool,
+    pub nfc: bool,
+    pub fold_case: bool,
+    user_aliases: HashMap<String, String>,
+    asset_aliases: HashMap<String, String>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy {
+            trim: true,
+            nfc: true,
+            fold_case: true,
+            user_aliases: HashMap::new(),
+            asset_aliases: HashMap::new(),
+        }
+    }
+}
+
+/// A malformed line in an alias table.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("alias table line {line}: {reason}")]
+pub struct AliasError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl Policy {
+    fn normalize(&self, id: &str) -> String {
+        let id = if self.trim { id.trim() } else { id };
+        let id: String = if self.nfc {
+            id.nfc().collect()
+        } else {
+            id.to_owned()
+        };
+        if self.fold_case {
+            id.to_lowercase()
+        } else {
+            id
+        }
+    }
+
+    fn aliases(&self, kind: IdKind) -> &HashMap<String, String> {
+        match kind {
+            IdKind::User => &self.user_aliases,
+            IdKind::Asset => &self.asset_aliases,
+        }
+    }
+
+    /// Maps `alias` (after normalization) to `canonical`.
+    pub fn add_alias(&mut self, kind: IdKind, alias: &str, canonical: &str) {
+        let alias = self.normalize(alias);
+        let aliases = match kind {
+            IdKind::User => &mut self.user_aliases,
+            IdKind::Asset => &mut self.asset_aliases,
+        };
+        aliases.insert(alias, canonical.to_owned());
+    }
+
+    /// Adds aliases from a table with one `user|asset <alias> = <canonical>`
+    /// per line. Blank lines and lines starting with `#` are skipped.
+    pub fn load_aliases(&mut self, table: &str) -> Result<(), AliasError> {
+        for (index, line) in table.lines().enumerate() {
+            let error = |reason| AliasError {
+                line: index + 1,
+                reason,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, rest) = line
+                .split_once(char::is_whitespace)
+                .ok_or(error("expected `user` or `asset`"))?;
+            let kind = match kind {
+                "user" => IdKind::User,
+                "asset" => IdKind::Asset,
+                _ => return Err(error("expected `user` or `asset`")),
+            };
+            let (alias, canonical) = rest.split_once('=').ok_or(error("expected `=`"))?;
+            let (alias, canonical) = (alias.trim(), canonical.trim());
+            if alias.is_empty() || canonical.is_empty() {
+                return Err(error("empty identifier"));
+            }
+            self.add_alias(kind, alias, canonical);
+        }
+        Ok(())
+    }
+
+    /// Canonical form of an id of the given kind.
+    pub fn canonical(&self, kind: IdKind, id: &str) -> String {
+        let id = self.normalize(id);
+        self.aliases(kind).get(&id).cloned().unwrap_or(id)
+    }
+}
+
+/// Distinct spellings that share a canonical form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub kind: IdKind,
+    pub canonical: String,
+    pub spellings: BTreeSet<String>,
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            IdKind::User => "user",
+            IdKind::Asset => "asset",
+        };
+        let spellings: Vec<_> = self.spellings.iter().map(|s| format!("{s:?}")).collect();
+        write!(f, "{kind} {:?}: {}", self.canonical, spellings.join(", "))
+    }
+}
+
+/// Rewrites the user and asset ids of log lines to their canonical form,
+/// remembering the original spellings of each.
+///
+/// Ids are rewritten in structured fields and in `"user_id"` /
+/// `"asset_id"` pairs inside trace payloads, so the System layer's
+/// `"alice"` and the journal's `"Alice"` end up as the same id.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Canonicalizer {
+    policy: Policy,
+    spellings: BTreeMap<(IdKind, String), BTreeSet<String>>,
+}
+
+impl Canonicalizer {
+    pub fn new(policy: Policy) -> Self {
+        Canonicalizer {
+            policy,
+            spellings: BTreeMap::new(),
+        }
+    }
+
+    fn canonical(&mut self, kind: IdKind, id: &str) -> String {
+        let canonical = self.policy.canonical(kind, id);
+        self.spellings
+            .entry((kind, canonical.clone()))
+            .or_default()
+            .insert(id.to_owned());
+        canonical
+    }
+
+    /// Copy of `log` with canonical ids.
+    pub fn canonicalize(&mut self, log: &LogLine) -> LogLine {
+        let mut log = log.clone();
+        if let Some(payload) = log.kind.payload_mut() {
+            *payload = map_payload_ids(payload, |kind, id| self.canonical(kind, id));
+        }
+        log.kind.ids_mut(|kind, id| *id = self.canonical(kind, id));
+        log
+    }
+
+    /// Canonical ids seen with more than one spelling, by kind and id.
+    pub fn collisions(&self) -> Vec<Collision> {
+        self.spellings
+            .iter()
+            .filter(|(_, spellings)| spellings.len() > 1)
+            .map(|((kind, canonical), spellings)| Collision {
+                kind: *kind,
+                canonical: canonical.clone(),
+                spellings: spellings.clone(),
+            })
+            .collect()
+    }
+
+    /// Renders [Canonicalizer::collisions] as a JSON array.
+    pub fn collisions_to_json(&self) -> String {
+        json::array(self.collisions().iter().map(|collision| {
+            let kind = match collision.kind {
+                IdKind::User => "user",
+                IdKind::Asset => "asset",
+            };
+            json::object([
+                ("kind", json::string(kind)),
+                ("canonical", json::string(&collision.canonical)),
+                (
+                    "spellings",
+                    json::array(collision.spellings.iter().map(|s| json::string(s))),
+                ),
+            ])
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::Ledger;
+    use crate::{ReadMode, read_log};
+
+    #[test]
+    fn test_policy() {
+        let mut policy = Policy::default();
+        assert_eq!(policy.canonical(IdKind::User, "  Alice "), "alice");
+        // "e" followed by a combining acute accent composes to "é".
+        assert_eq!(policy.canonical(IdKind::Asset, "Cafe\u{301}"), "caf\u{e9}");
+
+        policy
+            .load_aliases("# renamed accounts\n\nuser Alicia = alice\nasset  MILK2 =  milk\n")
+            .unwrap();
+        assert_eq!(policy.canonical(IdKind::User, "alicia"), "alice");
+        assert_eq!(policy.canonical(IdKind::Asset, "milk2"), "milk");
+        assert_eq!(policy.canonical(IdKind::User, "milk2"), "milk2");
+
+        let err = policy.load_aliases("user a = b\nbuyer a = b").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "alias table line 2: expected `user` or `asset`"
+        );
+        assert_eq!(
+            policy.load_aliases("user a b").unwrap_err().reason,
+            "expected `=`"
+        );
+
+        let exact = Policy {
+            fold_case: false,
+            ..Policy::default()
+        };
+        assert_eq!(exact.canonical(IdKind::User, "Alice"), "Alice");
+    }
+
+    #[test]
+    fn test_canonicalize_collisions() {
+        const SOURCE: &str = r#"
+System::Trace SendRequest "Jupiter->buyasset{\"user_id\":\"alice\",\"backet\":backet{\"asset_id\":\"milk\",\"count\":5,},}" requestid=10
+App::Journal CreateUser {"user_id": "Alice", "authorized_capital": 5000,} requestid=3
+App::Journal DepositCash UserCash{"user_id":" alice","count":500,} requestid=6
+App::Journal BuyAsset UserBacket{"user_id":"ALICE","backet":Backet{"asset_id":"Milk","count":5,},} requestid=10
+"#;
+        let mut canonicalizer = Canonicalizer::default();
+        let logs: Vec<_> = read_log(SOURCE.as_bytes(), ReadMode::All, vec![])
+            .unwrap()
+            .iter()
+            .map(|log| canonicalizer.canonicalize(log))
+            .collect();
+        assert!(logs[0].to_log_string().contains(r#"\"user_id\":\"alice\""#));
+
+        let mut ledger = Ledger::default();
+        ledger.extend(&logs);
+        assert_eq!(ledger.accounts.len(), 1);
+        let alice = &ledger.accounts[&UserId("alice".into())];
+        assert_eq!(alice.cash[&Currency::UNSPECIFIED].amount(), "5500");
+        assert_eq!(alice.holdings[&AssetId("milk".into())], 5);
+
+        let collisions = canonicalizer.collisions();
+        assert_eq!(collisions.len(), 2);
+        assert_eq!(
+            collisions[0].to_string(),
+            r#"user "alice": " alice", "ALICE", "Alice", "alice""#
+        );
+        assert_eq!(
+            collisions[1],
+            Collision {
+                kind: IdKind::Asset,
+                canonical: "milk".into(),
+                spellings: BTreeSet::from(["Milk".into(), "milk".into()]),
+            }
+        );
+    }
+}
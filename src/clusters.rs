@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// How many example lines each cluster keeps.
+const MAX_EXAMPLES: usize = 3;
+
+/// Error lines that share the same normalized message template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorCluster {
+    /// Error kind and normalized message, e.g. `System::NetworkError <str>`.
+    pub template: String,
+    pub count: usize,
+    pub first_request_id: NonZeroU32,
+    pub last_request_id: NonZeroU32,
+    /// The first few original lines of the cluster, in the order they were seen.
+    pub examples: Vec<String>,
+}
+
+impl ErrorCluster {
+    fn to_json(&self) -> String {
+        json::object([
+            ("template", json::string(&self.template)),
+            ("count", self.count.to_string()),
+            ("first_request_id", self.first_request_id.to_string()),
+            ("last_request_id", self.last_request_id.to_string()),
+            (
+                "examples",
+                json::array(self.examples.iter().map(|line| json::string(line))),
+            ),
+        ])
+    }
+}
+
+impl fmt::Display for ErrorCluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>6}  {}  (requests {}..{})",
+            self.count, self.template, self.first_request_id, self.last_request_id
+        )?;
+        for example in &self.examples {
+            writeln!(f, "        {example}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Groups error lines by [normalized](normalize_message) message template.
+#[derive(Debug, Default, Clone)]
+pub struct ErrorClusters {
+    clusters: Vec<ErrorCluster>,
+    by_template: HashMap<String, usize>,
+}
+
+impl Accumulator for ErrorClusters {
+    /// Accounts a log line; non-error lines are ignored.
+    fn add(&mut self, log: &LogLine) {
+        let Some(message) = error_text(&log.kind) else {
+            return;
+        };
+        let template = format!(
+            "{}::{} {}",
+            log.kind.source(),
+            log.kind.variant(),
+            normalize_message(&message)
+        );
+        match self.by_template.get(&template) {
+            Some(&idx) => {
+                let cluster = &mut self.clusters[idx];
+                cluster.count += 1;
+                cluster.last_request_id = log.request_id;
+                if cluster.examples.len() < MAX_EXAMPLES {
+                    cluster.examples.push(log.to_string());
+                }
+            }
+            None => {
+                self.by_template.insert(template.clone(), self.clusters.len());
+                self.clusters.push(ErrorCluster {
+                    template,
+                    count: 1,
+                    first_request_id: log.request_id,
+                    last_request_id: log.request_id,
+                    examples: vec![log.to_string()],
+                });
+            }
+        }
+    }
+}
+
+/// Text to cluster a line of severity `Error` or worse by: its message, or
+/// the payload of an unknown or custom line.
+fn error_text(kind: &LogKind) -> Option<String> {
+    if kind.severity() < Severity::Error {
+        return None;
+    }
+    let text = match kind {
+        LogKind::Unknown { raw_payload, .. } => raw_payload.clone(),
+        LogKind::Custom { payload, .. } => payload.to_log_string(),
+        _ => kind.error_message().or(kind.message()).unwrap_or_default().to_owned(),
+    };
+    Some(text)
+}
+
+impl ErrorClusters {
+    /// The `n` largest clusters, by count and then by first appearance.
+    pub fn top(&self, n: usize) -> Vec<&ErrorCluster> {
+        let mut clusters: Vec<&ErrorCluster> = self.clusters.iter().collect();
+        // stable sort keeps first-appearance order among equal counts
+        clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.count));
+        clusters.truncate(n);
+        clusters
+    }
+
+    /// Renders the `n` largest clusters as a JSON array.
+    pub fn top_to_json(&self, n: usize) -> String {
+        json::array(self.top(n).into_iter().map(ErrorCluster::to_json))
+    }
+}
+
+/// Replaces the variable parts of an error message with placeholders:
+/// quoted values become `<str>`, numbers `<num>`, and words mixing letters
+/// with digits (hex ids, tokens) `<id>`.
+///
+/// `can't buy "milk", 3 left (id a1b2)` -> `can't buy <str>, <num> left (id <id>)`
+pub fn normalize_message(message: &str) -> String {
+    let mut result = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '"' {
+            let mut escaped_now = false;
+            for c in chars.by_ref() {
+                match (c, escaped_now) {
+                    ('\\', false) => escaped_now = true,
+                    ('"', false) => break,
+                    _ => escaped_now = false,
+                }
+            }
+            result.push_str("<str>");
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut word = String::from(c);
+            while let Some(&c) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            if word.chars().all(|c| c.is_ascii_digit()) {
+                result.push_str("<num>");
+            } else if word.chars().any(|c| c.is_ascii_digit()) {
+                result.push_str("<id>");
+            } else {
+                result.push_str(&word);
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_normalize_message() {
+        assert_eq!(normalize_message("network interface is down"), "network interface is down");
+        assert_eq!(normalize_message("NS_BINDING_ABORTED"), "NS_BINDING_ABORTED");
+        assert_eq!(
+            normalize_message(r#"can't buy "milk", 3 left (id a1b2)"#),
+            "can't buy <str>, <num> left (id <id>)"
+        );
+        assert_eq!(normalize_message(r#"user "a\"b" 0x1f"#), "user <str> <id>");
+    }
+
+    #[test]
+    fn test_clusters_top() {
+        const SOURCE: &str = r#"
+System::Error NetworkError "timeout after 30 s" requestid=1
+App::Error SystemError "network" requestid=1
+System::Error NetworkError "timeout after 45 s" requestid=4
+App::Error SystemError "network" requestid=7
+System::Error NetworkError "timeout after 10 s" requestid=9
+App::Error LackOf "can't buy milk, no sellers" requestid=8
+System::Trace GetResponse "HTTP 200" requestid=8
+App::Fatal Panic index 7 out of range requestid=10
+Gateway::Error Timeout "upstream slow" requestid=11
+"#;
+        let clusters: ErrorClusters = accumulate(SOURCE);
+
+        let top = clusters.top(2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].template, "System::NetworkError timeout after <num> s");
+        assert_eq!(top[0].count, 3);
+        assert_eq!(top[0].first_request_id.get(), 1);
+        assert_eq!(top[0].last_request_id.get(), 9);
+        assert_eq!(top[0].examples.len(), 3);
+        assert_eq!(top[1].template, "App::SystemError network");
+        assert_eq!(top[1].count, 2);
+        let templates: Vec<_> = clusters.top(10).iter().map(|c| c.template.clone()).collect();
+        assert_eq!(templates.len(), 5);
+        assert!(templates.contains(&"App::Panic index <num> out of range".to_owned()));
+        assert!(templates.contains(&"Gateway::Timeout <str>".to_owned()));
+        assert!(
+            clusters
+                .top_to_json(1)
+                .starts_with(r#"[{"template":"System::NetworkError timeout after <num> s","count":3,"#)
+        );
+    }
+}
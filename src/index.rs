@@ -0,0 +1,404 @@
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::parse::{LogLine, Parser, Trailer};
+use crate::{ReadMode, read_log};
+
+/// Sidecar file magic, bumped whenever the binary layout changes.
+const MAGIC: &[u8; 8] = b"RQIDX003";
+/// Size of each block of the log that [fingerprint] hashes.
+const SAMPLE_BLOCK: u64 = 4096;
+/// Number of gaps between the blocks that [fingerprint] hashes.
+const SAMPLE_GAPS: u64 = 16;
+
+/// Location of one physical log line carrying a `requestid=`.
+///
+/// Entries are single lines: the index knows nothing of
+/// [Continuation](crate::Continuation), so a multi-line entry can't be read
+/// back through it. Such logs are read with `LogIterator::with_continuation`
+/// and a request id filter instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub request_id: NonZeroU32,
+    /// Byte offset of the line start.
+    pub offset: u64,
+    /// Line length in bytes, without the line terminator.
+    pub len: u32,
+}
+
+/// How an index relates to the current contents of its log file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// The log is exactly what was indexed.
+    Fresh,
+    /// The indexed prefix is unchanged and new bytes were appended;
+    /// [RequestIndex::update] brings the index up to date.
+    Grown,
+    /// The log was truncated or rewritten; the index must be rebuilt.
+    Changed,
+}
+
+/// Byte offsets of log lines grouped by request id, persisted as a compact
+/// sidecar file next to the log so a single request can be read with a few seeks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestIndex {
+    /// Length of the indexed prefix; always ends right after a `\n`.
+    log_len: u64,
+    /// [fingerprint] of the indexed prefix.
+    content_hash: [u8; 32],
+    /// Sorted by request id, then by offset.
+    entries: Vec<IndexEntry>,
+}
+
+impl RequestIndex {
+    /// Indexes every complete line of `log`.
+    pub fn build<R: Read + Seek>(log: &mut R) -> io::Result<Self> {
+        let mut index = RequestIndex {
+            log_len: 0,
+            content_hash: [0; 32],
+            entries: Vec::new(),
+        };
+        index.update(log)?;
+        Ok(index)
+    }
+
+    /// Indexes lines appended to `log` since the index was built.
+    /// Call only when [RequestIndex::freshness] reports [Freshness::Grown].
+    pub fn update<R: Read + Seek>(&mut self, log: &mut R) -> io::Result<()> {
+        log.seek(SeekFrom::Start(self.log_len))?;
+        let mut reader = io::BufReader::new(&mut *log);
+        let mut offset = self.log_len;
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            // a trailing line without `\n` may still be being written
+            if read == 0 || buf.last() != Some(&b'\n') {
+                break;
+            }
+            let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if let Some(request_id) = find_request_id(line) {
+                let len = u32::try_from(line.len()).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "log line longer than 4 GiB")
+                })?;
+                self.entries.push(IndexEntry {
+                    request_id,
+                    offset,
+                    len,
+                });
+            }
+            offset += read as u64;
+        }
+        drop(reader);
+        self.entries.sort_by_key(|entry| (entry.request_id, entry.offset));
+        self.log_len = offset;
+        self.content_hash = fingerprint(log, self.log_len)?;
+        Ok(())
+    }
+
+    /// Compares the index against the current contents of `log`.
+    pub fn freshness<R: Read + Seek>(&self, log: &mut R) -> io::Result<Freshness> {
+        let current_len = log.seek(SeekFrom::End(0))?;
+        if current_len < self.log_len {
+            return Ok(Freshness::Changed);
+        }
+        if fingerprint(log, self.log_len)? != self.content_hash {
+            return Ok(Freshness::Changed);
+        }
+        Ok(if current_len == self.log_len {
+            Freshness::Fresh
+        } else {
+            Freshness::Grown
+        })
+    }
+
+    /// Entries of a single request, in file order.
+    pub fn entries(&self, request_id: NonZeroU32) -> &[IndexEntry] {
+        let start = self.entries.partition_point(|entry| entry.request_id < request_id);
+        let end = self.entries.partition_point(|entry| entry.request_id <= request_id);
+        &self.entries[start..end]
+    }
+
+    /// All indexed request ids, ascending and without duplicates.
+    pub fn request_ids(&self) -> Vec<NonZeroU32> {
+        let mut ids: Vec<NonZeroU32> = self.entries.iter().map(|entry| entry.request_id).collect();
+        ids.dedup();
+        ids
+    }
+
+    /// Seeks to the lines of `request_id` and parses them.
+    /// Lines that do not parse are skipped, as in [read_log].
+    pub fn read_request<R: Read + Seek>(
+        &self,
+        log: &mut R,
+        request_id: NonZeroU32,
+    ) -> io::Result<Vec<LogLine>> {
+        let mut lines = Vec::new();
+        for entry in self.entries(request_id) {
+            log.seek(SeekFrom::Start(entry.offset))?;
+            let start = lines.len();
+            lines.resize(start + entry.len as usize, 0);
+            log.read_exact(&mut lines[start..])?;
+            lines.push(b'\n');
+        }
+        read_log(lines.as_slice(), ReadMode::All, vec![request_id])
+    }
+
+    /// Serializes the index in the sidecar format.
+    pub fn write_to(&self, mut out: impl Write) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&self.log_len.to_le_bytes())?;
+        out.write_all(&self.content_hash)?;
+        out.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for entry in &self.entries {
+            out.write_all(&entry.request_id.get().to_le_bytes())?;
+            out.write_all(&entry.offset.to_le_bytes())?;
+            out.write_all(&entry.len.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    /// Deserializes an index written by [RequestIndex::write_to].
+    pub fn read_from(mut input: impl Read) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a request index file"));
+        }
+        let log_len = read_u64(&mut input)?;
+        let mut content_hash = [0; 32];
+        input.read_exact(&mut content_hash)?;
+        let count = read_u64(&mut input)?;
+        let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+        for _ in 0..count {
+            let request_id = NonZeroU32::new(read_u32(&mut input)?)
+                .ok_or_else(|| invalid("zero request id in index"))?;
+            let offset = read_u64(&mut input)?;
+            let len = read_u32(&mut input)?;
+            entries.push(IndexEntry {
+                request_id,
+                offset,
+                len,
+            });
+        }
+        Ok(RequestIndex {
+            log_len,
+            content_hash,
+            entries,
+        })
+    }
+}
+
+/// Loads the sidecar index of `log_path`, updating it if the log has grown
+/// and rebuilding it if the sidecar is missing, unreadable or stale.
+/// The sidecar is rewritten whenever the index changes, through a temporary
+/// file renamed over it, so readers never see a partial index.
+pub fn load_or_build(log_path: &Path) -> io::Result<RequestIndex> {
+    let mut log = std::fs::File::open(log_path)?;
+    let sidecar = sidecar_path(log_path);
+    let loaded = std::fs::File::open(&sidecar)
+        .and_then(|file| RequestIndex::read_from(io::BufReader::new(file)));
+    let index = match loaded {
+        Ok(mut index) => match index.freshness(&mut log)? {
+            Freshness::Fresh => return Ok(index),
+            Freshness::Grown => {
+                index.update(&mut log)?;
+                index
+            }
+            Freshness::Changed => RequestIndex::build(&mut log)?,
+        },
+        Err(_) => RequestIndex::build(&mut log)?,
+    };
+    let mut tmp = sidecar.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let written = std::fs::File::create(&tmp)
+        .and_then(|file| index.write_to(io::BufWriter::new(file)))
+        .and_then(|()| std::fs::rename(&tmp, &sidecar));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    written?;
+    Ok(index)
+}
+
+/// Sidecar path for a log file: `app.log` -> `app.log.idx`.
+pub fn sidecar_path(log: &Path) -> PathBuf {
+    let mut path = log.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Request id in the [Trailer] of the line, where the reader finds it.
+fn find_request_id(line: &[u8]) -> Option<NonZeroU32> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    let (_, (request_id, _)) = Trailer.parse(&line[Trailer::start(line)..]).ok()?;
+    Some(request_id)
+}
+
+/// SHA-256 of `len` and of [SAMPLE_GAPS] + 1 blocks of `log[..len]`,
+/// evenly spaced from its first to its last byte. Reads at most about
+/// 68 KiB whatever the log size, and all of a shorter prefix; an edit that
+/// keeps the length and misses every sampled block goes unnoticed.
+fn fingerprint<R: Read + Seek>(log: &mut R, len: u64) -> io::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(len.to_le_bytes());
+    let mut block = vec![0; SAMPLE_BLOCK.min(len) as usize];
+    let span = len - block.len() as u64;
+    let gaps = if span == 0 { 1 } else { SAMPLE_GAPS };
+    for gap in 0..=gaps {
+        log.seek(SeekFrom::Start(span * gap / gaps))?;
+        log.read_exact(&mut block)?;
+        hasher.update(&block);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::nz;
+    use std::io::Cursor;
+
+    const SOURCE: &str = r#"System::Error NetworkError "network interface is down" requestid=1
+App::Error SystemError "network" requestid=1
+System::Trace SendRequest "login me" requestid=3
+
+System::Trace SendRequest "login me" requestid=2
+System::Trace GetResponse "HTTP 200" requestid=3
+"#;
+
+    #[test]
+    fn test_build_and_read_request() {
+        let mut log = Cursor::new(SOURCE.as_bytes().to_vec());
+        let index = RequestIndex::build(&mut log).unwrap();
+        assert_eq!(index.request_ids(), vec![nz(1), nz(2), nz(3)]);
+        assert_eq!(index.entries(nz(3)).len(), 2);
+        assert!(index.entries(nz(4)).is_empty());
+
+        let lines = index.read_request(&mut log, nz(3)).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1].to_string(),
+            r#"[req=3] System::Trace GetResponse "HTTP 200""#
+        );
+    }
+
+    #[test]
+    fn test_sidecar_roundtrip() {
+        let index = RequestIndex::build(&mut Cursor::new(SOURCE.as_bytes())).unwrap();
+        let mut sidecar = Vec::new();
+        index.write_to(&mut sidecar).unwrap();
+        assert_eq!(sidecar.len(), 8 + 8 + 32 + 8 + 5 * 16);
+        assert_eq!(RequestIndex::read_from(sidecar.as_slice()).unwrap(), index);
+        assert!(RequestIndex::read_from(&b"garbage!"[..]).is_err());
+    }
+
+    #[test]
+    fn test_freshness_and_update() {
+        let mut log = Cursor::new(SOURCE.as_bytes().to_vec());
+        let mut index = RequestIndex::build(&mut log).unwrap();
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Fresh);
+
+        log.get_mut()
+            .extend_from_slice(b"App::Error LackOf \"no sellers\" requestid=2\n");
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Grown);
+        index.update(&mut log).unwrap();
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Fresh);
+        assert_eq!(index.read_request(&mut log, nz(2)).unwrap().len(), 2);
+
+        log.get_mut()[0] = b'X';
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Changed);
+
+        log.get_mut().truncate(10);
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Changed);
+    }
+
+    #[test]
+    fn test_sampled_edit_is_detected() {
+        let mut log = Cursor::new(SOURCE.repeat(5000).into_bytes());
+        let index = RequestIndex::build(&mut log).unwrap();
+        // same length, far from both ends of the file but inside the
+        // middle sampled block
+        let middle = log.get_ref().len() / 2;
+        log.get_mut()[middle] ^= 1;
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Changed);
+        log.get_mut()[middle] ^= 1;
+
+        // only the sampled blocks are read
+        let mut counting = CountingReader { inner: &mut log, read: 0 };
+        assert_eq!(index.freshness(&mut counting).unwrap(), Freshness::Fresh);
+        assert!(counting.read <= (SAMPLE_GAPS + 1) * SAMPLE_BLOCK, "{}", counting.read);
+    }
+
+    struct CountingReader<'a> {
+        inner: &'a mut Cursor<Vec<u8>>,
+        read: u64,
+    }
+
+    impl Read for CountingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let read = self.inner.read(buf)?;
+            self.read += read as u64;
+            Ok(read)
+        }
+    }
+
+    impl Seek for CountingReader<'_> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_load_or_build_replaces_sidecar() {
+        let path = std::env::temp_dir().join(format!("analysis-index-{}.log", std::process::id()));
+        let sidecar = sidecar_path(&path);
+        std::fs::write(&path, SOURCE).unwrap();
+        // a truncated sidecar left by an interrupted run is rebuilt
+        std::fs::write(&sidecar, &MAGIC[..]).unwrap();
+        let index = load_or_build(&path).unwrap();
+        assert_eq!(index.request_ids(), vec![nz(1), nz(2), nz(3)]);
+        let saved = RequestIndex::read_from(std::fs::File::open(&sidecar).unwrap()).unwrap();
+        assert_eq!(saved, index);
+        let dir = std::fs::read_dir(std::env::temp_dir()).unwrap();
+        let prefix = format!("analysis-index-{}.log.idx.", std::process::id());
+        assert!(!dir.flatten().any(|entry| entry.file_name().to_string_lossy().starts_with(&prefix)));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&sidecar).unwrap();
+    }
+
+    #[test]
+    fn test_request_id_from_trailer() {
+        let id = |line: &str| find_request_id(line.as_bytes());
+        assert_eq!(id(r#"App::Warn Slow "x" requestid=4 note="see requestid=9""#), Some(nz(4)));
+        assert_eq!(id(r#"Db::Trace Dump {"text":"requestid=7"} session=s requestid=5"#), Some(nz(5)));
+        assert_eq!(id("App::Debug Cache key=42 requestid=6 "), Some(nz(6)));
+        assert_eq!(id("App::Debug Cache requestid=6 trailing junk"), None);
+    }
+
+    #[test]
+    fn test_partial_last_line_is_not_indexed() {
+        let mut log = Cursor::new(b"App::Error LackOf \"x\" requestid=1\nApp::Error LackOf \"y\" requ".to_vec());
+        let index = RequestIndex::build(&mut log).unwrap();
+        assert_eq!(index.request_ids(), vec![nz(1)]);
+        assert_eq!(index.freshness(&mut log).unwrap(), Freshness::Grown);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// Balances of a single user, as implied by the journal.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Account {
+    /// Authorized capital plus deposits minus withdrawals, per currency.
+    pub cash: BTreeMap<Currency, Money>,
+    /// Units bought minus units sold, per asset.
+    pub holdings: BTreeMap<AssetId, i64>,
+    /// Set once a `DeleteUser` entry was seen.
+    pub deleted: bool,
+}
+
+/// A registered asset and its owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Listing {
+    pub owner: UserId,
+    pub liquidity: Money,
+    /// Set once an `UnregisterAsset` entry was seen.
+    pub unregistered: bool,
+}
+
+/// User accounts and asset registrations replayed from `App::Journal` entries.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Ledger {
+    pub accounts: BTreeMap<UserId, Account>,
+    pub assets: BTreeMap<AssetId, Listing>,
+    /// Journal entries whose amounts could not be applied, by request id.
+    pub rejected: Vec<(NonZeroU32, MoneyError)>,
+}
+
+impl Accumulator for Ledger {
+    /// Applies a log line; everything except journal entries is ignored.
+    fn add(&mut self, log: &LogLine) {
+        let LogKind::App(AppLogKind::Journal(journal)) = &log.kind else {
+            return;
+        };
+        match journal {
+            AppLogJournalKind::CreateUser {
+                user_id,
+                authorized_capital,
+            } => self.credit(log.request_id, user_id, *authorized_capital),
+            AppLogJournalKind::DeleteUser { user_id } => self.account(user_id).deleted = true,
+            AppLogJournalKind::RegisterAsset {
+                asset_id,
+                user_id,
+                liquidity,
+            } => {
+                self.assets.insert(
+                    asset_id.clone(),
+                    Listing {
+                        owner: user_id.clone(),
+                        liquidity: *liquidity,
+                        unregistered: false,
+                    },
+                );
+            }
+            AppLogJournalKind::UnregisterAsset { asset_id, .. } => {
+                if let Some(listing) = self.assets.get_mut(asset_id) {
+                    listing.unregistered = true;
+                }
+            }
+            AppLogJournalKind::DepositCash(cash) => {
+                self.credit(log.request_id, &cash.user_id, cash.count);
+            }
+            AppLogJournalKind::WithdrawCash(cash) => {
+                self.debit(log.request_id, &cash.user_id, cash.count);
+            }
+            AppLogJournalKind::BuyAsset(trade) => {
+                *self.holding(&trade.user_id, &trade.backet.asset_id) +=
+                    i64::from(trade.backet.count.get());
+            }
+            AppLogJournalKind::SellAsset(trade) => {
+                *self.holding(&trade.user_id, &trade.backet.asset_id) -=
+                    i64::from(trade.backet.count.get());
+            }
+        }
+    }
+}
+
+impl Ledger {
+    fn account(&mut self, user_id: &UserId) -> &mut Account {
+        self.accounts.entry(user_id.clone()).or_default()
+    }
+
+    fn credit(&mut self, request_id: NonZeroU32, user_id: &UserId, amount: Money) {
+        let cash = self.cash(user_id, amount.currency());
+        match cash.checked_add(amount) {
+            Ok(sum) => *cash = sum,
+            Err(err) => self.rejected.push((request_id, err)),
+        }
+    }
+
+    fn debit(&mut self, request_id: NonZeroU32, user_id: &UserId, amount: Money) {
+        let cash = self.cash(user_id, amount.currency());
+        match cash.checked_sub(amount) {
+            Ok(difference) => *cash = difference,
+            Err(err) => self.rejected.push((request_id, err)),
+        }
+    }
+
+    fn cash(&mut self, user_id: &UserId, currency: Currency) -> &mut Money {
+        self.account(user_id)
+            .cash
+            .entry(currency)
+            .or_insert(Money::zero(currency))
+    }
+
+    fn holding(&mut self, user_id: &UserId, asset_id: &AssetId) -> &mut i64 {
+        self.account(user_id)
+            .holdings
+            .entry(asset_id.clone())
+            .or_default()
+    }
+
+    /// Renders accounts and assets as a JSON object.
+    pub fn to_json(&self) -> String {
+        let accounts = self.accounts.iter().map(|(user_id, account)| {
+            let holdings = account
+                .holdings
+                .iter()
+                .map(|(asset_id, count)| (&asset_id.0, count.to_string()));
+            let cash = account
+                .cash
+                .iter()
+                .map(|(currency, amount)| (currency.as_str(), amount.amount()));
+            (
+                &user_id.0,
+                json::object([
+                    ("cash", json::object(cash)),
+                    ("holdings", json::object(holdings)),
+                    ("deleted", account.deleted.to_string()),
+                ]),
+            )
+        });
+        let assets = self.assets.iter().map(|(asset_id, listing)| {
+            (
+                &asset_id.0,
+                json::object([
+                    ("owner", json::string(&listing.owner.0)),
+                    ("liquidity", listing.liquidity.amount()),
+                    (
+                        "currency",
+                        json::string(listing.liquidity.currency().as_str()),
+                    ),
+                    ("unregistered", listing.unregistered.to_string()),
+                ]),
+            )
+        });
+        json::object([
+            ("accounts", json::object(accounts)),
+            ("assets", json::object(assets)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    #[test]
+    fn test_ledger_replay() {
+        const SOURCE: &str = r#"
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Journal RegisterAsset {"asset_id": "milk", "user_id": "Bob", "liquidity": 10000,} requestid=5
+App::Journal DepositCash UserCash{"user_id":"Bob","count":500 usd,} requestid=6
+App::Journal WithdrawCash UserCash{"user_id":"Bob","count":200,} requestid=7
+App::Journal DepositCash UserCash{"user_id":"Bob","count":12.5 EUR,} requestid=8
+App::Journal SellAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":3,},} requestid=9
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+App::Journal UnregisterAsset {"asset_id":"milk","user_id":"Bob",} requestid=11
+"#;
+        let ledger: Ledger = accumulate(SOURCE);
+
+        let milk = AssetId("milk".into());
+        let bob = &ledger.accounts[&UserId("Bob".into())];
+        assert_eq!(bob.cash[&Currency::UNSPECIFIED].amount(), "800");
+        assert_eq!(bob.cash[&Currency::USD].amount(), "500");
+        assert_eq!(bob.cash[&Currency::new("eur").unwrap()].minor(), 1250);
+        assert_eq!(bob.holdings[&milk], -3);
+        assert_eq!(ledger.accounts[&UserId("Alice".into())].holdings[&milk], 5);
+        assert!(ledger.assets[&milk].unregistered);
+        assert_eq!(
+            ledger.to_json(),
+            r#"{"accounts":{"Alice":{"cash":{},"holdings":{"milk":5},"deleted":false},"Bob":{"cash":{"unspecified":800,"eur":12.50,"usd":500},"holdings":{"milk":-3},"deleted":false}},"assets":{"milk":{"owner":"Bob","liquidity":10000,"currency":"unspecified","unregistered":true}}}"#
+        );
+    }
+
+    #[test]
+    fn test_ledger_rejects_overflow() {
+        const SOURCE: &str = r#"
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 92233720368547758.07,} requestid=4
+App::Journal DepositCash UserCash{"user_id":"Bob","count":0.01,} requestid=5
+"#;
+        let ledger: Ledger = accumulate(SOURCE);
+        assert_eq!(
+            ledger.rejected,
+            [(NonZeroU32::new(5).unwrap(), MoneyError::Overflow)]
+        );
+        assert_eq!(
+            ledger.accounts[&UserId("Bob".into())].cash[&Currency::UNSPECIFIED].minor(),
+            i64::MAX
+        );
+    }
+}
@@ -1,14 +1,34 @@
+pub mod announcements;
+pub mod anonymize;
+pub mod canonical;
+pub mod clusters;
+pub mod index;
+mod json;
+pub mod ledger;
+pub mod metrics;
+pub mod orderbook;
 pub mod parse;
+pub mod server;
+pub mod sessions;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "tui")]
+pub mod tui;
 use parse::*;
 use std::io::Read;
 use std::num::NonZeroU32;
+use std::ops::RangeInclusive;
 
 /// Read mode for filtering log entries.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ReadMode {
     /// Return all log entries.
     All,
-    /// Return only error entries (System::Error and App::Error).
+    /// Return only entries of severity `Error` or worse, whatever their
+    /// source: `App::Fatal`, and unknown or schema-defined `Error` lines too.
     Errors,
     /// Return only exchange/journal operation entries.
     Exchanges,
@@ -27,11 +47,7 @@ impl LogFilter for ReadMode {
     fn accepts(&self, log: &LogLine) -> bool {
         match self {
             ReadMode::All => true,
-            ReadMode::Errors => matches!(
-                &log.kind,
-                LogKind::System(SystemLogKind::Error(_))
-                    | LogKind::App(AppLogKind::Error(_))
-            ),
+            ReadMode::Errors => log.kind.severity() >= Severity::Error,
             ReadMode::Exchanges => matches!(
                 &log.kind,
                 LogKind::App(AppLogKind::Journal(
@@ -47,65 +63,384 @@ impl LogFilter for ReadMode {
     }
 }
 
+/// Both filters must accept, e.g. `(ReadMode::Errors, attribute_filters)`.
+impl<A: LogFilter, B: LogFilter> LogFilter for (A, B) {
+    fn accepts(&self, log: &LogLine) -> bool {
+        self.0.accepts(log) && self.1.accepts(log)
+    }
+}
+
+/// Every filter must accept; an empty list accepts everything.
+impl<F: LogFilter> LogFilter for Vec<F> {
+    fn accepts(&self, log: &LogLine) -> bool {
+        self.iter().all(|filter| filter.accepts(log))
+    }
+}
+
+/// `None` accepts everything, e.g. an optional [MinSeverity].
+impl<F: LogFilter> LogFilter for Option<F> {
+    fn accepts(&self, log: &LogLine) -> bool {
+        self.as_ref().is_none_or(|filter| filter.accepts(log))
+    }
+}
+
+/// A report built up one log line at a time, in log order, e.g.
+/// [stats::Stats] or [ledger::Ledger].
+///
+/// Every accumulator also implements `Extend<&LogLine>`, so a whole log is
+/// added with `report.extend(&logs)`.
+pub trait Accumulator {
+    /// Accounts a single log line.
+    fn add(&mut self, log: &LogLine);
+}
+
+/// `Extend` can't be implemented for every [Accumulator] at once outside
+/// of `std`, so the impls are generated here for the crate's own ones.
+macro_rules! extend_accumulators {
+    ($($accumulator:ty),* $(,)?) => {$(
+        impl<'a> Extend<&'a LogLine> for $accumulator {
+            fn extend<I: IntoIterator<Item = &'a LogLine>>(&mut self, logs: I) {
+                logs.into_iter().for_each(|log| self.add(log));
+            }
+        }
+    )*};
+}
+
+extend_accumulators!(
+    announcements::Snapshots,
+    clusters::ErrorClusters,
+    ledger::Ledger,
+    metrics::Metrics,
+    orderbook::OrderBook,
+    sessions::Sessions,
+    stats::Stats,
+);
+
+/// Accepts entries at least as severe as the given [Severity], see [LogKind::severity].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinSeverity(pub Severity);
+
+impl LogFilter for MinSeverity {
+    fn accepts(&self, log: &LogLine) -> bool {
+        log.kind.severity() >= self.0
+    }
+}
+
+/// Accepts entries having attribute `key`, with the given value if any.
+///
+/// Parsed from `key` or `key=value`; the value is compared with
+/// [Attribute::text], so `session=a` matches both `session=a` and `session="a"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl LogFilter for AttributeFilter {
+    fn accepts(&self, log: &LogLine) -> bool {
+        log.attributes.get(&self.key).is_some_and(|attribute| {
+            self.value
+                .as_ref()
+                .is_none_or(|value| attribute.text() == value)
+        })
+    }
+}
+
+impl std::str::FromStr for AttributeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value.to_owned())),
+            None => (s, None),
+        };
+        if key.is_empty() {
+            return Err(format!("missing attribute key in '{s}'"));
+        }
+        Ok(AttributeFilter {
+            key: key.to_owned(),
+            value,
+        })
+    }
+}
+
+/// Policy for physical lines that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Utf8Policy {
+    /// Replace invalid sequences with `U+FFFD` and parse the line as usual.
+    #[default]
+    Lossy,
+    /// Skip the line and record its number in [ReadReport::invalid_utf8_lines].
+    Skip,
+    /// Abort reading with an [std::io::ErrorKind::InvalidData] error.
+    Fail,
+}
+
+/// Policy for joining physical lines into one logical entry before parsing,
+/// for stack traces and pretty-printed payloads spanning several lines.
+///
+/// Joined lines are separated by `\n`, so a quoted payload keeps its layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Continuation {
+    /// Every physical line is an entry of its own.
+    #[default]
+    None,
+    /// A non-blank line starting with a space or a tab continues the previous entry.
+    Indented,
+    /// A line ending with `\` continues on the next line; the `\` is dropped.
+    Backslash,
+    /// Every line up to the next one starting with a `Source::Category Variant`
+    /// skeleton, such as `App::Error SystemError` or `Db::Warn Slow`, continues
+    /// the entry.
+    UntilPrefix,
+}
+
+impl Continuation {
+    /// Whether `line` continues the entry before it; for [Continuation::Backslash]
+    /// this is decided by the previous line instead.
+    fn continues(self, line: &str) -> bool {
+        match self {
+            Continuation::None | Continuation::Backslash => false,
+            Continuation::Indented => line.starts_with([' ', '\t']) && !line.trim().is_empty(),
+            Continuation::UntilPrefix => UnknownKind::skeleton(line).is_err(),
+        }
+    }
+}
+
 /// Итератор, на выходе которого - строки распарсенной структуры данных
-struct LogIterator<R: Read> {
-    #[allow(clippy::type_complexity)]
-    lines: std::iter::Filter<
-        std::io::Lines<std::io::BufReader<R>>,
-        fn(&Result<String, std::io::Error>) -> bool,
-    >,
-    parser: <LogLine as Parsable>::Parser,
+///
+/// Entries that do not match the grammar are skipped and their line ranges
+/// recorded; I/O errors and [Utf8Policy::Fail] violations are yielded as `Err`.
+pub struct LogIterator<R: Read> {
+    reader: std::io::BufReader<R>,
+    buf: Vec<u8>,
+    line_number: usize,
+    policy: Utf8Policy,
+    invalid_utf8_lines: Vec<usize>,
+    grammar: LogGrammar,
+    continuation: Continuation,
+    /// Line read ahead of the current entry, which starts the next one.
+    pending: Option<String>,
+    entry_lines: RangeInclusive<usize>,
+    unparsed_entries: Vec<RangeInclusive<usize>>,
+    warnings: Vec<(usize, String)>,
 }
 impl<R: Read> LogIterator<R> {
-    fn new(reader: R) -> Self {
-        use std::io::BufRead;
+    pub fn new(reader: R, policy: Utf8Policy) -> Self {
+        Self::with_grammar(reader, policy, LogGrammar::default())
+    }
+
+    /// Same as [LogIterator::new], also accepting the variants registered
+    /// in `grammar`.
+    pub fn with_grammar(reader: R, policy: Utf8Policy, grammar: LogGrammar) -> Self {
         Self {
-            lines: std::io::BufReader::with_capacity(4096, reader)
-                .lines()
-                .filter(|line_res| {
-                    !line_res
-                        .as_ref()
-                        .ok()
-                        .map(|line| line.trim().is_empty())
-                        .unwrap_or(false)
-                }),
-            parser: LogLine::parser(),
+            reader: std::io::BufReader::with_capacity(4096, reader),
+            buf: Vec::new(),
+            line_number: 0,
+            policy,
+            invalid_utf8_lines: Vec::new(),
+            grammar,
+            continuation: Continuation::None,
+            pending: None,
+            entry_lines: 0..=0,
+            unparsed_entries: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Joins physical lines into entries according to `continuation`.
+    pub fn with_continuation(mut self, continuation: Continuation) -> Self {
+        self.continuation = continuation;
+        self
+    }
+
+    /// 1-based numbers of the lines skipped so far under [Utf8Policy::Skip].
+    pub fn invalid_utf8_lines(&self) -> &[usize] {
+        &self.invalid_utf8_lines
+    }
+
+    /// 1-based range of physical lines of the entry read last.
+    pub fn entry_lines(&self) -> RangeInclusive<usize> {
+        self.entry_lines.clone()
+    }
+
+    /// Line ranges of the non-blank entries skipped so far because they did
+    /// not match the grammar.
+    pub fn unparsed_entries(&self) -> &[RangeInclusive<usize>] {
+        &self.unparsed_entries
+    }
+
+    /// Warnings about the entries parsed so far, such as unknown keys that
+    /// were skipped, with the first line of their entry.
+    pub fn warnings(&self) -> &[(usize, String)] {
+        &self.warnings
+    }
+
+    /// Reads the next physical line, decoded according to the UTF-8 policy.
+    /// Returns `Ok(None)` at end of input.
+    fn next_line(&mut self) -> Result<Option<String>, std::io::Error> {
+        use std::io::BufRead;
+        loop {
+            self.buf.clear();
+            if self.reader.read_until(b'\n', &mut self.buf)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            let bytes = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+            let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+            match (std::str::from_utf8(bytes), self.policy) {
+                (Ok(line), _) => return Ok(Some(line.to_owned())),
+                (Err(_), Utf8Policy::Lossy) => {
+                    return Ok(Some(String::from_utf8_lossy(bytes).into_owned()));
+                }
+                (Err(_), Utf8Policy::Skip) => self.invalid_utf8_lines.push(self.line_number),
+                (Err(e), Utf8Policy::Fail) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("line {}: {e}", self.line_number),
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Reads the next logical entry, joining continuation lines.
+    /// Returns `Ok(None)` at end of input.
+    fn next_entry(&mut self) -> Result<Option<String>, std::io::Error> {
+        let mut entry = match self.pending.take() {
+            Some(line) => line,
+            None => match self.next_line()? {
+                Some(line) => line,
+                None => return Ok(None),
+            },
+        };
+        // The pending line, if any, is always the last one read.
+        let first = self.line_number;
+        let mut last = first;
+        loop {
+            if self.continuation == Continuation::Backslash {
+                let Some(joined) = entry.strip_suffix('\\') else {
+                    break;
+                };
+                entry.truncate(joined.len());
+            }
+            let Some(line) = self.next_line()? else {
+                break;
+            };
+            if self.continuation != Continuation::Backslash && !self.continuation.continues(&line) {
+                self.pending = Some(line);
+                break;
+            }
+            entry.push('\n');
+            entry.push_str(&line);
+            last = self.line_number;
         }
+        self.entry_lines = first..=last;
+        Ok(Some(entry))
     }
 }
 impl<R: Read> Iterator for LogIterator<R> {
     type Item = Result<parse::LogLine, std::io::Error>;
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let line_result = self.lines.next()?;
-            let line = match line_result {
-                Ok(line) => line,
+            let entry = match self.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => return None,
                 Err(e) => return Some(Err(e)),
             };
-            let Ok((remaining, result)) = self.parser.parse(line.trim()) else {
-                continue;
-            };
-            if remaining.trim().is_empty() {
-                return Some(Ok(result));
+            if let Some((log, warnings)) = self.grammar.parse_line(&entry) {
+                let line = *self.entry_lines.start();
+                self.warnings
+                    .extend(warnings.into_iter().map(|warning| (line, warning)));
+                return Some(Ok(log));
+            }
+            if !entry.trim().is_empty() {
+                self.unparsed_entries.push(self.entry_lines());
             }
         }
     }
 }
 
+/// Parses a single log line along with its warnings, e.g. unknown keys that
+/// were skipped; `None` if it is blank or does not match the grammar.
+pub fn parse_line(line: &str) -> Option<(LogLine, Vec<String>)> {
+    let (parsed, warnings) = parse::collect_warnings(|| LogLine::parser().parse(line.trim()));
+    let (remaining, log) = parsed.ok()?;
+    remaining.trim().is_empty().then_some((log, warnings))
+}
+
+/// Result of [read_log_with_policy]: the accepted logs plus lines skipped
+/// under [Utf8Policy::Skip] or because they did not parse.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReadReport {
+    pub logs: Vec<LogLine>,
+    /// 1-based numbers of physical lines dropped because they were not valid UTF-8.
+    pub invalid_utf8_lines: Vec<usize>,
+    /// 1-based line ranges of non-blank entries that did not match the grammar.
+    pub unparsed_entries: Vec<RangeInclusive<usize>>,
+    /// Warnings about accepted entries, with the first line of the entry.
+    pub warnings: Vec<(usize, String)>,
+}
+
 /// Принимает поток байт, отдаёт отфильтрованные и распарсенные логи
+///
+/// Lines with invalid UTF-8 are decoded lossily; use [read_log_with_policy]
+/// to skip or reject them instead.
 pub fn read_log(
     input: impl Read,
     filter: impl LogFilter,
     request_ids: Vec<NonZeroU32>,
 ) -> Result<Vec<LogLine>, std::io::Error> {
-    let collected = LogIterator::new(input).collect::<Result<Vec<_>, _>>()?;
-    Ok(collected
-        .into_iter()
-        .filter(|log| {
-            (request_ids.is_empty() || request_ids.contains(&log.request_id))
-                && filter.accepts(log)
+    read_log_with_policy(input, filter, request_ids, Utf8Policy::Lossy).map(|report| report.logs)
+}
+
+/// Same as [read_log], with an explicit policy for lines that are not valid UTF-8.
+pub fn read_log_with_policy(
+    input: impl Read,
+    filter: impl LogFilter,
+    request_ids: Vec<NonZeroU32>,
+    policy: Utf8Policy,
+) -> Result<ReadReport, std::io::Error> {
+    read_log_with_grammar(input, filter, request_ids, policy, LogGrammar::default())
+}
+
+/// Same as [read_log_with_policy], also accepting the variants registered
+/// in `grammar`.
+pub fn read_log_with_grammar(
+    input: impl Read,
+    filter: impl LogFilter,
+    request_ids: Vec<NonZeroU32>,
+    policy: Utf8Policy,
+    grammar: LogGrammar,
+) -> Result<ReadReport, std::io::Error> {
+    read_log_from(
+        LogIterator::with_grammar(input, policy, grammar),
+        filter,
+        request_ids,
+    )
+}
+
+/// Same as [read_log_with_policy], reading from an already configured iterator.
+pub fn read_log_from<R: Read>(
+    mut iter: LogIterator<R>,
+    filter: impl LogFilter,
+    request_ids: Vec<NonZeroU32>,
+) -> Result<ReadReport, std::io::Error> {
+    let logs = iter
+        .by_ref()
+        .filter(|log_res| {
+            log_res.as_ref().map_or(true, |log| {
+                (request_ids.is_empty() || request_ids.contains(&log.request_id))
+                    && filter.accepts(log)
+            })
         })
-        .collect())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ReadReport {
+        logs,
+        invalid_utf8_lines: iter.invalid_utf8_lines,
+        unparsed_entries: iter.unparsed_entries,
+        warnings: iter.warnings,
+    })
 }
 
 #[cfg(test)]
@@ -258,4 +593,223 @@ App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"m
             );
         }
     }
+
+    const INVALID_UTF8: &[u8] = b"System::Error NetworkError \"url unknown\" requestid=1\n\
+App::Error SystemError \"bad \xff byte\" requestid=2\r\n\
+App::Error SystemError \"network\" requestid=3\n";
+
+    #[test]
+    fn test_invalid_utf8_lossy() {
+        let logs = read_log(INVALID_UTF8, ReadMode::All, vec![]).unwrap();
+        assert_eq!(logs.len(), 3);
+        assert_eq!(
+            logs[1].kind,
+            LogKind::App(AppLogKind::Error(AppLogErrorKind::SystemError(
+                "bad \u{FFFD} byte".into()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8_skip() {
+        let report =
+            read_log_with_policy(INVALID_UTF8, ReadMode::All, vec![], Utf8Policy::Skip).unwrap();
+        let ids: Vec<u32> = report.logs.iter().map(|log| log.request_id.get()).collect();
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(report.invalid_utf8_lines, vec![2]);
+    }
+
+    #[test]
+    fn test_invalid_utf8_fail() {
+        let err = read_log_with_policy(INVALID_UTF8, ReadMode::All, vec![], Utf8Policy::Fail)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("line 2:"), "{err}");
+    }
+
+    fn read_entries(
+        source: &str,
+        continuation: Continuation,
+    ) -> (Vec<(LogLine, RangeInclusive<usize>)>, ReadReport) {
+        let mut iter =
+            LogIterator::new(source.as_bytes(), Utf8Policy::Lossy).with_continuation(continuation);
+        let mut entries = Vec::new();
+        while let Some(log) = iter.next() {
+            entries.push((log.unwrap(), iter.entry_lines()));
+        }
+        let report = read_log_from(
+            LogIterator::new(source.as_bytes(), Utf8Policy::Lossy).with_continuation(continuation),
+            ReadMode::All,
+            vec![],
+        )
+        .unwrap();
+        (entries, report)
+    }
+
+    fn system_error(message: &str) -> LogKind {
+        LogKind::App(AppLogKind::Error(AppLogErrorKind::SystemError(
+            message.into(),
+        )))
+    }
+
+    #[test]
+    fn test_continuation_indented() {
+        let source = "App::Error SystemError \"panicked at src/main.rs:10\n\
+\x20 0: std::panicking::begin_panic\n\
+\t1: main\" requestid=1\n\
+\n\
+App::Error SystemError \"network\" requestid=2\n\
+\x20 stray\n";
+        let (entries, report) = read_entries(source, Continuation::Indented);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].0.kind,
+            system_error("panicked at src/main.rs:10\n  0: std::panicking::begin_panic\n\t1: main")
+        );
+        assert_eq!(entries[0].1, 1..=3);
+        // The second entry swallows the indented line after it and no longer parses.
+        assert_eq!(report.logs.len(), 1);
+        assert_eq!(report.unparsed_entries, vec![5..=6]);
+
+        // Without a policy every physical line stands alone.
+        let (entries, report) = read_entries(source, Continuation::None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].1, 5..=5);
+        assert_eq!(report.unparsed_entries, vec![1..=1, 2..=2, 3..=3, 6..=6]);
+    }
+
+    #[test]
+    fn test_continuation_backslash() {
+        let source = "App::Error SystemError \"first\\\nsecond\" \\\nrequestid=1\n\
+App::Error SystemError \"network\" requestid=2\\\n";
+        let (entries, report) = read_entries(source, Continuation::Backslash);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.kind, system_error("first\nsecond"));
+        assert_eq!(entries[0].1, 1..=3);
+        // A backslash on the last line joins nothing.
+        assert_eq!(entries[1].0.request_id.get(), 2);
+        assert_eq!(entries[1].1, 4..=4);
+        assert!(report.unparsed_entries.is_empty());
+    }
+
+    #[test]
+    fn test_continuation_until_prefix() {
+        let source = "garbage\n\
+App::Error SystemError \"{\n\
+user_id: 1,\n\
+}\" requestid=1\n\
+\n\
+System::Error NetworkError \"url unknown\" requestid=2\n\
+Db::Warn Slow query took 5s\n\
+Caused by: lock::wait timeout requestid=3\n\
+App::Trace Heartbeat requestid=4\n";
+        let (entries, report) = read_entries(source, Continuation::UntilPrefix);
+        let lines: Vec<_> = entries.iter().map(|(_, lines)| lines.clone()).collect();
+        assert_eq!(lines, vec![2..=5, 6..=6, 7..=8, 9..=9]);
+        // Sources other than `System` and `App` start entries too.
+        assert!(matches!(entries[2].0.kind, LogKind::Unknown { .. }));
+        assert_eq!(entries[2].0.request_id.get(), 3);
+        assert_eq!(entries[0].0.kind, system_error("{\nuser_id: 1,\n}"));
+        assert_eq!(report.unparsed_entries, vec![1..=1]);
+
+        // The whole test log reads the same as line by line.
+        let (_, joined) = read_entries(SOURCE, Continuation::UntilPrefix);
+        assert_eq!(
+            joined.logs,
+            read_log(SOURCE.as_bytes(), ReadMode::All, vec![]).unwrap()
+        );
+        assert_eq!(joined.unparsed_entries, vec![]);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let source = "App::Journal CreateUser {\"user_id\": \"Alice\", \"region\": \"eu\", \"authorized_capital\": 5000,} requestid=3\n\
+App::Journal CreateUser {\"user_id\": \"Bob\",} requestid=4\n\
+App::Journal DeleteUser {\"note\": [1, 2], \"user_id\": \"Bob\",} requestid=5\n";
+        let report =
+            read_log_with_policy(source.as_bytes(), ReadMode::All, vec![], Utf8Policy::Lossy).unwrap();
+        let ids: Vec<_> = report.logs.iter().map(|log| log.request_id.get()).collect();
+        assert_eq!(ids, vec![3, 5]);
+        // A missing key rejects the entry.
+        assert_eq!(report.unparsed_entries, vec![2..=2]);
+        assert_eq!(
+            report.warnings,
+            vec![
+                (1, "unknown key \"region\" skipped".to_string()),
+                (3, "unknown key \"note\" skipped".to_string()),
+            ]
+        );
+        let (log, warnings) = parse_line(source.lines().next().unwrap()).unwrap();
+        assert_eq!(log.request_id.get(), 3);
+        assert_eq!(warnings, ["unknown key \"region\" skipped"]);
+    }
+
+    #[test]
+    fn test_attribute_filters() {
+        let source = r#"System::Error NetworkError "down" requestid=1 exchange=Jupiter
+App::Error SystemError "network" session="s 1" requestid=1 exchange=Jupiter
+App::Journal DepositCash UserCash{"user_id":"Bob","count":10,} requestid=2 exchange=Mars session=s2
+App::Error SystemError "network" requestid=3
+"#;
+        let filter = |filter| {
+            read_log(source.as_bytes(), filter, vec![])
+                .unwrap()
+                .iter()
+                .map(|log| log.request_id.get())
+                .collect::<Vec<_>>()
+        };
+        let attr = |s: &str| s.parse::<AttributeFilter>().unwrap();
+        assert_eq!(filter(vec![attr("exchange")]), [1, 1, 2]);
+        assert_eq!(filter(vec![attr("exchange=Jupiter")]), [1, 1]);
+        assert_eq!(filter(vec![attr("session=s 1")]), [1]);
+        assert_eq!(filter(vec![attr("exchange"), attr("session")]), [1, 2]);
+        assert_eq!(filter(vec![]), [1, 1, 2, 3]);
+        assert_eq!(
+            read_log(
+                source.as_bytes(),
+                (ReadMode::Errors, vec![attr("session")]),
+                vec![]
+            )
+            .unwrap()
+            .len(),
+            1
+        );
+        assert!("=x".parse::<AttributeFilter>().is_err());
+    }
+
+    #[test]
+    fn test_min_severity() {
+        let source = r#"System::Trace GetResponse "HTTP 200" requestid=1
+App::Debug CacheMiss key=42 requestid=2
+App::Journal DeleteUser {"user_id":"Bob",} requestid=3
+System::Warn DiskFull "90% used" requestid=4
+App::Error LackOf "milk" requestid=5
+App::Fatal Panic "boom" requestid=6
+"#;
+        let ids = |filter: Option<MinSeverity>| {
+            read_log(source.as_bytes(), filter, vec![])
+                .unwrap()
+                .iter()
+                .map(|log| log.request_id.get())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(None), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(ids(Some(MinSeverity(Severity::Info))), [3, 4, 5, 6]);
+        assert_eq!(ids(Some(MinSeverity(Severity::Warn))), [4, 5, 6]);
+        assert_eq!(ids(Some(MinSeverity(Severity::Fatal))), [6]);
+    }
+
+    #[test]
+    fn test_errors_mode_by_severity() {
+        let source = r#"System::Warn DiskFull "90% used" requestid=1
+Gateway::Error Timeout "upstream slow" requestid=2
+Gateway::Trace Timeout "retrying" requestid=3
+App::Fatal Panic "boom" requestid=4
+App::Error LackOf "milk" requestid=5
+"#;
+        let logs = read_log(source.as_bytes(), ReadMode::Errors, vec![]).unwrap();
+        let ids: Vec<_> = logs.iter().map(|log| log.request_id.get()).collect();
+        assert_eq!(ids, [2, 4, 5]);
+        assert!(matches!(logs[0].kind, LogKind::Unknown { .. }));
+    }
 }
@@ -57,7 +57,7 @@ use clap::Parser;
 enum Mode {
     /// Return all log entries.
     All,
-    /// Return only error entries.
+    /// Return only entries of severity Error or Fatal.
     Errors,
     /// Return only exchange/journal operation entries.
     Exchanges,
@@ -73,6 +73,75 @@ impl From<Mode> for analysis::ReadMode {
     }
 }
 
+/// CLI policy for lines that are not valid UTF-8, mirrors `analysis::Utf8Policy`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum InvalidUtf8 {
+    /// Replace invalid bytes with U+FFFD and parse the line as usual.
+    Lossy,
+    /// Skip the line and report its number on stderr.
+    Skip,
+    /// Abort with an error.
+    Fail,
+}
+
+impl From<InvalidUtf8> for analysis::Utf8Policy {
+    fn from(policy: InvalidUtf8) -> Self {
+        match policy {
+            InvalidUtf8::Lossy => Self::Lossy,
+            InvalidUtf8::Skip => Self::Skip,
+            InvalidUtf8::Fail => Self::Fail,
+        }
+    }
+}
+
+/// CLI policy for multi-line entries, mirrors `analysis::Continuation`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Continuation {
+    /// Every line is an entry.
+    None,
+    /// Lines starting with a space or a tab continue the previous entry.
+    Indented,
+    /// A line ending with `\` continues on the next line.
+    Backslash,
+    /// Lines continue the entry until the next one starting with `Source::Category Variant`.
+    Prefix,
+}
+
+impl From<Continuation> for analysis::Continuation {
+    fn from(continuation: Continuation) -> Self {
+        match continuation {
+            Continuation::None => Self::None,
+            Continuation::Indented => Self::Indented,
+            Continuation::Backslash => Self::Backslash,
+            Continuation::Prefix => Self::UntilPrefix,
+        }
+    }
+}
+
+/// CLI severity level, mirrors `analysis::parse::Severity`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Severity {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+
+impl From<Severity> for analysis::parse::Severity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Trace => Self::Trace,
+            Severity::Debug => Self::Debug,
+            Severity::Info => Self::Info,
+            Severity::Warn => Self::Warn,
+            Severity::Error => Self::Error,
+            Severity::Fatal => Self::Fatal,
+        }
+    }
+}
+
 /// Wrapper for comma-separated request IDs, used for clap parsing.
 #[derive(Clone, Debug)]
 struct RequestIds(Vec<NonZeroU32>);
@@ -95,10 +164,36 @@ impl std::str::FromStr for RequestIds {
     }
 }
 
-/// Log analysis tool for a trading/exchange application.
-#[derive(Parser)]
-#[command(name = "cli", version, about)]
-struct Cli {
+/// CLI policy for `Connect` auth data, mirrors `analysis::anonymize::AuthPolicy`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Auth {
+    /// Replace with zero bytes.
+    Zero,
+    /// Replace with a keyed hash, so equal tokens stay equal.
+    Hash,
+}
+
+impl From<Auth> for analysis::anonymize::AuthPolicy {
+    fn from(auth: Auth) -> Self {
+        match auth {
+            Auth::Zero => Self::Zero,
+            Auth::Hash => Self::Hash,
+        }
+    }
+}
+
+/// Output format for report subcommands.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Format {
+    /// Human-readable text.
+    Text,
+    /// A single JSON document.
+    Json,
+}
+
+/// Input file and filters shared by all subcommands.
+#[derive(clap::Args)]
+struct ReadArgs {
     /// Log file to analyze.
     filename: String,
 
@@ -109,23 +204,653 @@ struct Cli {
     /// Comma-separated request IDs to filter by (e.g. 1,2,3).
     #[arg(long)]
     request_id: Option<RequestIds>,
+
+    /// Keep only entries at least this severe; journal operations count as info.
+    #[arg(long, value_enum)]
+    min_severity: Option<Severity>,
+
+    /// Keep only entries with this trailing attribute, as `key` or `key=value`; repeatable.
+    #[arg(long = "attr", value_name = "KEY[=VALUE]")]
+    attributes: Vec<analysis::AttributeFilter>,
+
+    /// How to handle lines that are not valid UTF-8: lossy, skip, or fail.
+    #[arg(long, value_enum, default_value_t = InvalidUtf8::Lossy)]
+    invalid_utf8: InvalidUtf8,
+
+    /// How to join multi-line entries: none, indented, backslash, or prefix.
+    #[arg(long, value_enum, default_value_t = Continuation::None)]
+    continuation: Continuation,
+
+    /// TOML schema of extra journal operations to parse (`[[operation]]` tables).
+    #[arg(long)]
+    schema: Option<std::path::PathBuf>,
+
+    /// Canonicalize user and asset ids (trim, NFC, lowercase, aliases).
+    #[arg(long)]
+    canonicalize: bool,
+
+    // Same options as `CanonicalArgs`, inlined: clap cannot tell whether an
+    // optional flattened struct was given when it flattens another one.
+    /// Keep letter case when canonicalizing ids; implies --canonicalize.
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Alias table with one `user|asset <alias> = <canonical>` per line; implies --canonicalize.
+    #[arg(long)]
+    aliases: Option<std::path::PathBuf>,
+}
+
+/// How lines are decoded, joined and parsed; the part of `ReadArgs` that
+/// `export` takes too.
+#[derive(clap::Args)]
+struct ParseArgs {
+    /// How to handle lines that are not valid UTF-8: lossy, skip, or fail.
+    #[arg(long, value_enum, default_value_t = InvalidUtf8::Lossy)]
+    invalid_utf8: InvalidUtf8,
+
+    /// How to join multi-line entries: none, indented, backslash, or prefix.
+    #[arg(long, value_enum, default_value_t = Continuation::None)]
+    continuation: Continuation,
+
+    /// TOML schema of extra journal operations to parse (`[[operation]]` tables).
+    #[arg(long)]
+    schema: Option<std::path::PathBuf>,
+}
+
+impl ParseArgs {
+    /// The built-in grammar plus the operations of `--schema`, if given.
+    fn grammar(&self) -> anyhow::Result<analysis::parse::LogGrammar> {
+        let mut grammar = analysis::parse::LogGrammar::default();
+        if let Some(path) = &self.schema {
+            let schema = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", path.display(), e))?;
+            analysis::parse::Schema::from_toml(&schema)
+                .and_then(|schema| schema.register(&mut grammar))
+                .map_err(|e| anyhow::anyhow!("'{}': {}", path.display(), e))?;
+        }
+        Ok(grammar)
+    }
+
+    /// Opens `filename` and reads the entries accepted by `filter`,
+    /// reporting skipped lines and warnings on stderr.
+    fn read(
+        &self,
+        filename: &str,
+        filter: impl analysis::LogFilter,
+        request_ids: Vec<NonZeroU32>,
+    ) -> anyhow::Result<Vec<analysis::parse::LogLine>> {
+        let file = std::fs::File::open(filename)
+            .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", filename, e))?;
+        let logs = analysis::LogIterator::with_grammar(file, self.invalid_utf8.into(), self.grammar()?)
+            .with_continuation(self.continuation.into());
+        let report = analysis::read_log_from(logs, filter, request_ids)?;
+        for line in &report.invalid_utf8_lines {
+            eprintln!("skipped line {line}: invalid UTF-8");
+        }
+        for lines in &report.unparsed_entries {
+            match (lines.start(), lines.end()) {
+                (first, last) if first == last => {
+                    eprintln!("skipped line {first}: not a log entry")
+                }
+                (first, last) => eprintln!("skipped lines {first}-{last}: not a log entry"),
+            }
+        }
+        for (line, warning) in &report.warnings {
+            eprintln!("warning: line {line}: {warning}");
+        }
+        Ok(report.logs)
+    }
+}
+
+/// Options of identifier canonicalization, mirrors `analysis::canonical::Policy`.
+#[derive(clap::Args)]
+struct CanonicalArgs {
+    /// Keep letter case when canonicalizing ids.
+    #[arg(long)]
+    case_sensitive: bool,
+
+    /// Alias table with one `user|asset <alias> = <canonical>` per line.
+    #[arg(long)]
+    aliases: Option<std::path::PathBuf>,
+}
+
+impl CanonicalArgs {
+    fn policy(&self) -> anyhow::Result<analysis::canonical::Policy> {
+        let mut policy = analysis::canonical::Policy::default();
+        policy.fold_case = !self.case_sensitive;
+        if let Some(path) = &self.aliases {
+            let table = std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", path.display(), e))?;
+            policy.load_aliases(&table)?;
+        }
+        Ok(policy)
+    }
+
+    /// The policy if `--canonicalize` was given or implied by another option.
+    fn requested_policy(
+        &self,
+        canonicalize: bool,
+    ) -> anyhow::Result<Option<analysis::canonical::Policy>> {
+        if canonicalize || self.case_sensitive || self.aliases.is_some() {
+            self.policy().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ReadArgs {
+    /// `--invalid-utf8`, `--continuation` and `--schema`.
+    fn parse_args(&self) -> ParseArgs {
+        ParseArgs {
+            invalid_utf8: self.invalid_utf8,
+            continuation: self.continuation,
+            schema: self.schema.clone(),
+        }
+    }
+
+    /// The built-in grammar plus the operations of `--schema`, if given.
+    fn grammar(&self) -> anyhow::Result<analysis::parse::LogGrammar> {
+        self.parse_args().grammar()
+    }
+
+    /// `--mode`, `--min-severity` and `--attr` combined.
+    fn filter(&self) -> impl analysis::LogFilter + use<> {
+        let read_mode: analysis::ReadMode = self.mode.into();
+        let min_severity = self
+            .min_severity
+            .map(|severity| analysis::MinSeverity(severity.into()));
+        (read_mode, (min_severity, self.attributes.clone()))
+    }
+
+    fn request_ids(&self) -> Vec<NonZeroU32> {
+        self.request_id.as_ref().map(|ids| ids.0.clone()).unwrap_or_default()
+    }
+
+    /// A canonicalizer if `--canonicalize`, `--case-sensitive` or `--aliases` was given.
+    fn canonicalizer(&self) -> anyhow::Result<Option<analysis::canonical::Canonicalizer>> {
+        let canonical = CanonicalArgs {
+            case_sensitive: self.case_sensitive,
+            aliases: self.aliases.clone(),
+        };
+        let policy = canonical.requested_policy(self.canonicalize)?;
+        Ok(policy.map(analysis::canonical::Canonicalizer::new))
+    }
+
+    /// Opens the file and returns the filtered logs, reporting skipped lines on stderr.
+    fn read(self) -> anyhow::Result<Vec<analysis::parse::LogLine>> {
+        let logs = self
+            .parse_args()
+            .read(&self.filename, self.filter(), self.request_ids())?;
+        let Some(mut canonicalizer) = self.canonicalizer()? else {
+            return Ok(logs);
+        };
+        Ok(logs
+            .iter()
+            .map(|log| canonicalizer.canonicalize(log))
+            .collect())
+    }
+}
+
+/// Export destinations for `cli export`.
+#[cfg(feature = "sqlite")]
+#[derive(clap::Subcommand)]
+enum ExportTarget {
+    /// Append parsed logs to a SQLite database; entries imported before are
+    /// skipped. Export a file with the same parse options every time.
+    Sqlite {
+        /// Database file; created if missing.
+        database: std::path::PathBuf,
+
+        /// Log files to import.
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+
+        #[command(flatten)]
+        parse: ParseArgs,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Summarize line counts, errors, ids and traded volume.
+    Stats {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Cluster error messages by normalized template and show the largest clusters.
+    Errors {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Number of clusters to show.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Build or refresh the request-id sidecar index (`<file>.idx`).
+    Index {
+        /// Log file to index.
+        filename: std::path::PathBuf,
+    },
+    /// Print the lines of the given requests, seeking via the sidecar index.
+    ///
+    /// The index holds single lines; for multi-line entries use
+    /// `cli <file> --continuation ... --request-id <ids>`.
+    Request {
+        /// Log file to read.
+        filename: std::path::PathBuf,
+
+        /// Comma-separated request IDs to print (e.g. 1,2,3).
+        request_id: RequestIds,
+    },
+    /// Report user and asset ids whose spellings collide after canonicalization.
+    Identifiers {
+        /// Log file to analyze.
+        filename: std::path::PathBuf,
+
+        #[command(flatten)]
+        canonical: CanonicalArgs,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Group requests into sessions by `Connect` auth token and list journal
+    /// operations without an authenticated session.
+    Sessions {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// List the offers of every `Check` snapshot, or what changed between them.
+    Announcements {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Show added, removed and changed offers between consecutive snapshots.
+        #[arg(long)]
+        diff: bool,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Replay offers from `Check` snapshots and trades, and report buys that
+    /// exceeded what was on offer.
+    Orderbook {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Show the book as of this request id instead of the end of the log.
+        #[arg(long)]
+        at: Option<std::num::NonZeroU32>,
+
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+    },
+    /// Serve JSON query endpoints (/lines, /requests/{id}, /stats, /ledger) over HTTP.
+    Serve {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Log files to serve; re-read on every request.
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+
+        /// Canonicalize user and asset ids (trim, NFC, lowercase, aliases);
+        /// implied by --case-sensitive and --aliases.
+        #[arg(long)]
+        canonicalize: bool,
+
+        #[command(flatten)]
+        canonical: CanonicalArgs,
+    },
+    /// Expose counters in the Prometheus text format (stdout, HTTP or textfile).
+    #[command(group(clap::ArgGroup::new("sink").multiple(true)))]
+    Metrics {
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// Keep reading lines appended to the file, like `tail -f`; entries
+        /// are single lines, so `--continuation` must be `none`.
+        #[arg(long, requires = "sink")]
+        follow: bool,
+
+        /// Serve `GET /metrics` on this address.
+        #[arg(long, group = "sink")]
+        listen: Option<String>,
+
+        /// Write metrics to this path for the node exporter textfile collector.
+        #[arg(long, group = "sink")]
+        textfile: Option<std::path::PathBuf>,
+
+        /// Polling and textfile refresh interval in follow mode, in milliseconds.
+        #[arg(long, default_value_t = 1000)]
+        interval_ms: u64,
+    },
+    /// Rewrite a log with pseudonymous user/asset ids and scrubbed auth data.
+    Anonymize {
+        /// Log file to anonymize.
+        filename: std::path::PathBuf,
+
+        /// Secret seed for the pseudonyms; the same seed gives the same pseudonyms.
+        #[arg(long)]
+        seed: String,
+
+        /// What to replace `Connect` auth data with.
+        #[arg(long, value_enum, default_value_t = Auth::Hash)]
+        auth: Auth,
+
+        /// Output file; stdout if omitted.
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Browse a log interactively in the terminal.
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Log file to browse.
+        filename: std::path::PathBuf,
+
+        /// Initial filtering mode; press `m` to cycle.
+        #[arg(long, value_enum, default_value_t = Mode::All)]
+        mode: Mode,
+
+        /// How to handle lines that are not valid UTF-8: lossy, skip, or fail.
+        #[arg(long, value_enum, default_value_t = InvalidUtf8::Lossy)]
+        invalid_utf8: InvalidUtf8,
+    },
+    /// Export parsed logs into another store.
+    #[cfg(feature = "sqlite")]
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+}
+
+/// Log analysis tool for a trading/exchange application.
+#[derive(Parser)]
+#[command(
+    name = "cli",
+    version,
+    about,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    read: Option<ReadArgs>,
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
-    let read_mode: analysis::ReadMode = cli.mode.into();
-    let request_ids = cli.request_id.map(|ids| ids.0).unwrap_or_default();
-
-    println!(
-        "Trying opening file '{}' from directory '{}'",
-        cli.filename,
-        std::env::current_dir()?.to_string_lossy()
-    );
-    let file = std::fs::File::open(&cli.filename)
-        .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", cli.filename, e))?;
-    let logs = analysis::read_log(file, read_mode, request_ids)?;
-    println!("got logs:");
-    logs.iter().for_each(|parsed| println!("  {}", parsed));
+    match cli.command {
+        Some(Command::Stats { read, format }) => {
+            let mut stats = analysis::stats::Stats::default();
+            stats.extend(&read.read()?);
+            match format {
+                Format::Text => print!("{stats}"),
+                Format::Json => println!("{}", stats.to_json()),
+            }
+        }
+        Some(Command::Errors { read, top, format }) => {
+            let mut clusters = analysis::clusters::ErrorClusters::default();
+            clusters.extend(&read.read()?);
+            match format {
+                Format::Text => clusters.top(top).iter().for_each(|cluster| print!("{cluster}")),
+                Format::Json => println!("{}", clusters.top_to_json(top)),
+            }
+        }
+        Some(Command::Index { filename }) => {
+            let index = analysis::index::load_or_build(&filename)?;
+            println!(
+                "indexed {} requests into '{}'",
+                index.request_ids().len(),
+                analysis::index::sidecar_path(&filename).display()
+            );
+        }
+        Some(Command::Request {
+            filename,
+            request_id,
+        }) => {
+            let index = analysis::index::load_or_build(&filename)?;
+            let mut log = std::fs::File::open(&filename)?;
+            for id in request_id.0 {
+                for line in index.read_request(&mut log, id)? {
+                    println!("{line}");
+                }
+            }
+        }
+        Some(Command::Identifiers {
+            filename,
+            canonical,
+            format,
+        }) => {
+            let file = std::fs::File::open(&filename)
+                .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", filename.display(), e))?;
+            let mut canonicalizer = analysis::canonical::Canonicalizer::new(canonical.policy()?);
+            for log in analysis::read_log(file, analysis::ReadMode::All, vec![])? {
+                canonicalizer.canonicalize(&log);
+            }
+            match format {
+                Format::Text => {
+                    let collisions = canonicalizer.collisions();
+                    println!("collisions: {}", collisions.len());
+                    for collision in collisions {
+                        println!("  {collision}");
+                    }
+                }
+                Format::Json => println!("{}", canonicalizer.collisions_to_json()),
+            }
+        }
+        Some(Command::Sessions { read, format }) => {
+            let mut sessions = analysis::sessions::Sessions::default();
+            sessions.extend(&read.read()?);
+            match format {
+                Format::Text => print!("{sessions}"),
+                Format::Json => println!("{}", sessions.to_json()),
+            }
+        }
+        Some(Command::Announcements { read, diff, format }) => {
+            let mut snapshots = analysis::announcements::Snapshots::default();
+            snapshots.extend(&read.read()?);
+            match (format, diff) {
+                (Format::Text, false) => print!("{snapshots}"),
+                (Format::Text, true) => snapshots.diffs().iter().for_each(|diff| print!("{diff}")),
+                (Format::Json, false) => println!("{}", snapshots.to_json()),
+                (Format::Json, true) => println!("{}", snapshots.diffs_to_json()),
+            }
+        }
+        Some(Command::Orderbook { read, at, format }) => {
+            let mut order_book = analysis::orderbook::OrderBook::default();
+            order_book.extend(&read.read()?);
+            if let Some(id) = at
+                && order_book.as_of(id).is_none()
+            {
+                anyhow::bail!("request {id} is not in the log");
+            }
+            match format {
+                Format::Text => {
+                    let (book, violations) = match at {
+                        Some(id) => {
+                            println!("order book as of request {id}:");
+                            let book = order_book.as_of(id).unwrap_or_default();
+                            (book, order_book.violations_as_of(id).unwrap_or_default())
+                        }
+                        None => {
+                            println!("order book:");
+                            (order_book.current().clone(), order_book.violations())
+                        }
+                    };
+                    print!("{book}");
+                    println!("violations: {}", violations.len());
+                    for violation in violations {
+                        println!("  {violation}");
+                    }
+                }
+                Format::Json => println!("{}", order_book.to_json(at).unwrap_or_default()),
+            }
+        }
+        Some(Command::Serve {
+            listen,
+            files,
+            canonicalize,
+            canonical,
+        }) => {
+            let mut server = analysis::server::Server::bind(&listen, files)?;
+            if let Some(policy) = canonical.requested_policy(canonicalize)? {
+                server = server.with_policy(policy);
+            }
+            println!("listening on http://{}", server.local_addr()?);
+            server.serve()?;
+        }
+        Some(Command::Metrics {
+            read,
+            follow,
+            listen,
+            textfile,
+            interval_ms,
+        }) => {
+            use analysis::{Accumulator, LogFilter, metrics};
+            use std::sync::{Arc, Mutex};
+
+            let shared = Arc::new(Mutex::new(metrics::Metrics::default()));
+            let interval = std::time::Duration::from_millis(interval_ms);
+            if follow && !matches!(read.continuation, Continuation::None) {
+                anyhow::bail!("--follow reads one entry per line; --continuation is not supported");
+            }
+            let filename = std::path::PathBuf::from(&read.filename);
+            let (grammar, filter, request_ids) = (read.grammar()?, read.filter(), read.request_ids());
+            let mut canonicalizer = read.canonicalizer()?;
+            if !follow {
+                shared.lock().unwrap().extend(&read.read()?);
+            }
+            let server = match listen {
+                Some(addr) => {
+                    let listener = std::net::TcpListener::bind(&addr)?;
+                    println!("serving http://{}/metrics", listener.local_addr()?);
+                    let shared = Arc::clone(&shared);
+                    Some(std::thread::spawn(move || metrics::serve(listener, shared)))
+                }
+                None => None,
+            };
+            if let Some(path) = textfile.clone() {
+                if follow {
+                    let shared = Arc::clone(&shared);
+                    std::thread::spawn(move || {
+                        loop {
+                            let snapshot = shared.lock().unwrap().clone();
+                            if let Err(e) = metrics::write_textfile(&path, &snapshot) {
+                                eprintln!("failed to write '{}': {e}", path.display());
+                            }
+                            std::thread::sleep(interval);
+                        }
+                    });
+                } else {
+                    metrics::write_textfile(&path, &shared.lock().unwrap())?;
+                }
+            }
+            if follow {
+                metrics::follow(&filename, interval, &grammar, |log, warnings| {
+                    for warning in warnings {
+                        eprintln!("warning: request {}: {warning}", log.request_id);
+                    }
+                    if (request_ids.is_empty() || request_ids.contains(&log.request_id))
+                        && filter.accepts(&log)
+                    {
+                        let log = match &mut canonicalizer {
+                            Some(canonicalizer) => canonicalizer.canonicalize(&log),
+                            None => log,
+                        };
+                        shared.lock().unwrap().add(&log);
+                    }
+                })?;
+            } else if let Some(server) = server {
+                server.join().expect("metrics server panicked")?;
+            } else if textfile.is_none() {
+                print!("{}", shared.lock().unwrap().render());
+            }
+        }
+        Some(Command::Anonymize {
+            filename,
+            seed,
+            auth,
+            output,
+        }) => {
+            let file = std::fs::File::open(&filename)
+                .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", filename.display(), e))?;
+            let mut anonymizer = analysis::anonymize::Anonymizer::new(seed.as_bytes(), auth.into());
+            let report = match output {
+                Some(path) => anonymizer.anonymize_log(file, std::fs::File::create(path)?)?,
+                None => anonymizer.anonymize_log(file, std::io::stdout().lock())?,
+            };
+            if report.dropped > 0 {
+                eprintln!("dropped {} unparseable lines", report.dropped);
+            }
+            for (line, warning) in &report.warnings {
+                eprintln!("warning: line {line}: {warning}");
+            }
+        }
+        #[cfg(feature = "tui")]
+        Some(Command::Tui {
+            filename,
+            mode,
+            invalid_utf8,
+        }) => {
+            let file = std::fs::File::open(&filename)
+                .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", filename.display(), e))?;
+            let logs = analysis::LogIterator::new(file, invalid_utf8.into());
+            analysis::tui::run(logs, mode.into())?;
+        }
+        #[cfg(feature = "sqlite")]
+        Some(Command::Export {
+            target:
+                ExportTarget::Sqlite {
+                    database,
+                    files,
+                    parse,
+                },
+        }) => {
+            let mut export = analysis::sqlite::SqliteExport::open(&database)?;
+            for path in files {
+                let source = path.to_string_lossy();
+                let logs = parse.read(&source, analysis::ReadMode::All, vec![])?;
+                match export.append(&source, &logs)? {
+                    Some(added) => println!("imported {added} new entries from '{source}'"),
+                    None => eprintln!(
+                        "skipping '{source}': it no longer starts with the {} entries imported before",
+                        export.imported_lines(&source)?.unwrap_or_default()
+                    ),
+                }
+            }
+        }
+        None => {
+            let read = cli.read.expect("clap requires a filename without a subcommand");
+            println!(
+                "Trying opening file '{}' from directory '{}'",
+                read.filename,
+                std::env::current_dir()?.to_string_lossy()
+            );
+            let logs = read.read()?;
+            println!("got logs:");
+            logs.iter().for_each(|parsed| println!("  {}", parsed));
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::Accumulator;
+use crate::parse::*;
+use crate::server::{READ_TIMEOUT, Response, accept_loop};
+
+/// Counters and gauges derived from a stream of log lines, rendered in the
+/// Prometheus text exposition format.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Metrics {
+    /// `log_lines_total{source, category, variant}`
+    lines: BTreeMap<(String, String, String), u64>,
+    /// `journal_volume_total{op, asset}`
+    journal_volume: BTreeMap<(String, String), f64>,
+    /// `errors_total{kind}`
+    errors: BTreeMap<String, u64>,
+    /// `log_last_request_id`
+    last_request_id: u32,
+}
+
+impl Accumulator for Metrics {
+    /// Accounts a single log line.
+    fn add(&mut self, log: &LogLine) {
+        let kind = &log.kind;
+        *self
+            .lines
+            .entry((
+                kind.source().to_owned(),
+                kind.category().to_owned(),
+                kind.variant().to_owned(),
+            ))
+            .or_default() += 1;
+        if kind.severity() >= Severity::Error {
+            *self
+                .errors
+                .entry(format!("{}::{}", kind.source(), kind.variant()))
+                .or_default() += 1;
+        }
+        if let LogKind::App(AppLogKind::Journal(journal)) = kind
+            && let Some((asset, amount)) = journal_volume(journal)
+        {
+            *self
+                .journal_volume
+                .entry((kind.variant().to_owned(), asset))
+                .or_default() += amount;
+        }
+        self.last_request_id = log.request_id.get();
+    }
+}
+
+impl Metrics {
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP log_lines_total Parsed log lines.\n");
+        out.push_str("# TYPE log_lines_total counter\n");
+        for ((source, category, variant), n) in &self.lines {
+            let _ = writeln!(
+                out,
+                "log_lines_total{{source=\"{}\",category=\"{}\",variant=\"{}\"}} {n}",
+                escape_label(source),
+                escape_label(category),
+                escape_label(variant)
+            );
+        }
+        out.push_str("# HELP journal_volume_total Units moved by journal operations.\n");
+        out.push_str("# TYPE journal_volume_total counter\n");
+        for ((op, asset), n) in &self.journal_volume {
+            let _ = writeln!(
+                out,
+                "journal_volume_total{{op=\"{}\",asset=\"{}\"}} {n}",
+                escape_label(op),
+                escape_label(asset)
+            );
+        }
+        out.push_str("# HELP errors_total Error and fatal lines per kind.\n");
+        out.push_str("# TYPE errors_total counter\n");
+        for (kind, n) in &self.errors {
+            let _ = writeln!(out, "errors_total{{kind=\"{}\"}} {n}", escape_label(kind));
+        }
+        out.push_str("# HELP log_last_request_id Request id of the most recent line.\n");
+        out.push_str("# TYPE log_last_request_id gauge\n");
+        let _ = writeln!(out, "log_last_request_id {}", self.last_request_id);
+        out
+    }
+}
+
+/// `(asset, amount)` moved by a journal operation: cash operations count
+/// against their currency code, registrations against the registered asset.
+fn journal_volume(journal: &AppLogJournalKind) -> Option<(String, f64)> {
+    match journal {
+        AppLogJournalKind::CreateUser {
+            authorized_capital, ..
+        } => Some((
+            authorized_capital.currency().to_string(),
+            authorized_capital.to_f64(),
+        )),
+        AppLogJournalKind::RegisterAsset {
+            asset_id,
+            liquidity,
+            ..
+        } => Some((asset_id.0.clone(), liquidity.to_f64())),
+        AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+            Some((cash.count.currency().to_string(), cash.count.to_f64()))
+        }
+        AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => Some((
+            trade.backet.asset_id.0.clone(),
+            f64::from(trade.backet.count.get()),
+        )),
+        AppLogJournalKind::DeleteUser { .. } | AppLogJournalKind::UnregisterAsset { .. } => None,
+    }
+}
+
+/// Escapes `\`, `"` and newlines in a label value.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Tails `path` like `tail -f`, calling `on_line` for every complete line
+/// that parses with `grammar`, along with its parse warnings. Polls every `interval` at end of file. When the file at
+/// `path` is shorter than what was read (truncated) or is another file
+/// (rotated and replaced), it is reopened and read from the start. Never
+/// returns unless an I/O error occurs.
+pub fn follow(
+    path: &Path,
+    interval: Duration,
+    grammar: &LogGrammar,
+    mut on_line: impl FnMut(LogLine, Vec<String>),
+) -> io::Result<()> {
+    let mut reader = io::BufReader::new(std::fs::File::open(path)?);
+    let mut pos = 0;
+    let mut buf = Vec::new();
+    loop {
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if buf.last() == Some(&b'\n') {
+            pos += buf.len() as u64;
+            if let Some((log, warnings)) = grammar.parse_line(&String::from_utf8_lossy(&buf)) {
+                on_line(log, warnings);
+            }
+            buf.clear();
+            continue;
+        }
+        if read == 0 {
+            match std::fs::metadata(path) {
+                Ok(current)
+                    if current.len() < pos + buf.len() as u64
+                        || replaced(reader.get_ref(), &current)? =>
+                {
+                    reader = io::BufReader::new(std::fs::File::open(path)?);
+                    pos = 0;
+                    buf.clear();
+                }
+                Ok(_) => {}
+                // rotated away, the new file is not there yet
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+/// Whether `current`, the metadata of the followed path, is of another file
+/// than the open `file`. Without inode numbers only truncation is noticed.
+#[cfg(unix)]
+fn replaced(file: &std::fs::File, current: &std::fs::Metadata) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let open = file.metadata()?;
+    Ok((open.dev(), open.ino()) != (current.dev(), current.ino()))
+}
+
+#[cfg(not(unix))]
+fn replaced(_file: &std::fs::File, _current: &std::fs::Metadata) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// Atomically replaces `path` with the rendered metrics (for the node
+/// exporter textfile collector, which may read the file at any moment).
+pub fn write_textfile(path: &Path, metrics: &Metrics) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, metrics.render())?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Serves `GET /metrics` from the shared metrics, forever.
+pub fn serve(listener: TcpListener, metrics: Arc<Mutex<Metrics>>) -> io::Result<()> {
+    accept_loop(&listener, READ_TIMEOUT, move |request| match request.path.as_str() {
+        "/metrics" => Response {
+            status: 200,
+            content_type: "text/plain; version=0.0.4",
+            body: metrics.lock().map(|m| m.render()).unwrap_or_default(),
+        },
+        _ => Response::error(404, "unknown endpoint"),
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const SOURCE: &str = r#"
+System::Error NetworkError "network interface is down" requestid=1
+App::Error SystemError "network" requestid=1
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Journal SellAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":3,},} requestid=9
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+App::Journal BuyAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":2,},} requestid=11
+"#;
+
+    #[test]
+    fn test_render_exposition_format() {
+        let metrics: Metrics = accumulate(SOURCE);
+        let text = metrics.render();
+        assert!(text.contains(
+            "log_lines_total{source=\"App\",category=\"Journal\",variant=\"BuyAsset\"} 2\n"
+        ));
+        assert!(text.contains("journal_volume_total{op=\"BuyAsset\",asset=\"milk\"} 7\n"));
+        assert!(text.contains("journal_volume_total{op=\"CreateUser\",asset=\"unspecified\"} 1000\n"));
+        assert!(text.contains("errors_total{kind=\"System::NetworkError\"} 1\n"));
+        let fatal: Metrics = accumulate("App::Fatal Panic \"boom\" requestid=1\n");
+        assert!(fatal.render().contains("errors_total{kind=\"App::Panic\"} 1\n"));
+        assert!(text.contains("# TYPE errors_total counter\n"));
+        assert!(text.ends_with("log_last_request_id 11\n"));
+    }
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+
+    #[test]
+    fn test_follow_appends_truncation_and_rotation() {
+        use std::io::Write;
+        use std::sync::mpsc;
+
+        let path =
+            std::env::temp_dir().join(format!("analysis-follow-{}.log", std::process::id()));
+        let rotated = path.with_extension("log.1");
+        let line = |id: u32| format!("App::Error SystemError \"network\" requestid={id}\n");
+        std::fs::write(&path, line(1) + &line(2)).unwrap();
+
+        let shared = Arc::new(Mutex::new(Metrics::default()));
+        let (tx, rx) = mpsc::channel();
+        {
+            let (path, shared) = (path.clone(), Arc::clone(&shared));
+            std::thread::spawn(move || {
+                follow(&path, Duration::from_millis(5), &LogGrammar::default(), |log, _| {
+                    shared.lock().unwrap().add(&log);
+                    let _ = tx.send(log.request_id.get());
+                })
+            });
+        }
+        let next = || rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((next(), next()), (1, 2));
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        // a partial line waits for its end
+        write!(file, "App::Error SystemError \"network\" requ").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        writeln!(file, "estid=3").unwrap();
+        assert_eq!(next(), 3);
+        let text = shared.lock().unwrap().render();
+        assert!(text.contains("errors_total{kind=\"App::SystemError\"} 3\n"), "{text}");
+
+        // truncated in place
+        std::fs::write(&path, line(4)).unwrap();
+        assert_eq!(next(), 4);
+
+        // rotated: renamed away and replaced by a new file of the same length
+        std::fs::rename(&path, &rotated).unwrap();
+        std::fs::write(&path, line(5)).unwrap();
+        if cfg!(unix) {
+            assert_eq!(next(), 5);
+            assert!(shared.lock().unwrap().render().ends_with("log_last_request_id 5\n"));
+        }
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(rotated).unwrap();
+    }
+
+    #[test]
+    fn test_textfile_is_replaced() {
+        let path =
+            std::env::temp_dir().join(format!("analysis-metrics-{}.prom", std::process::id()));
+        write_textfile(&path, &Metrics::default()).unwrap();
+        let metrics: Metrics = accumulate(SOURCE);
+        write_textfile(&path, &metrics).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), metrics.render());
+        std::fs::remove_file(path).unwrap();
+    }
+}
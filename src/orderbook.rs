@@ -0,0 +1,360 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// Quantities on offer, per asset and offering user.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Book {
+    pub offers: BTreeMap<AssetId, BTreeMap<UserId, u32>>,
+}
+
+impl Book {
+    fn from_announcements(announcements: &Announcements) -> Self {
+        let mut book = Book::default();
+        for offer in &announcements.0 {
+            for backet in &offer.backets {
+                book.offer(&offer.user_id, &backet.asset_id, backet.count.get());
+            }
+        }
+        book
+    }
+
+    fn offer(&mut self, user_id: &UserId, asset_id: &AssetId, count: u32) {
+        let offered = self
+            .offers
+            .entry(asset_id.clone())
+            .or_default()
+            .entry(user_id.clone())
+            .or_default();
+        *offered = offered.saturating_add(count);
+    }
+
+    /// Sets the quantity a user offers; zero withdraws the offer.
+    fn set(&mut self, user_id: &UserId, asset_id: &AssetId, count: u32) {
+        if count > 0 {
+            self.offers
+                .entry(asset_id.clone())
+                .or_default()
+                .insert(user_id.clone(), count);
+        } else if let Some(sellers) = self.offers.get_mut(asset_id) {
+            sellers.remove(user_id);
+            if sellers.is_empty() {
+                self.offers.remove(asset_id);
+            }
+        }
+    }
+
+    /// Takes up to `count` units of `asset_id` from the sellers in user id
+    /// order; the journal does not say who the counterparty was. Returns the
+    /// sellers' remaining quantities.
+    fn take(&mut self, asset_id: &AssetId, mut count: u32) -> Vec<(UserId, u32)> {
+        let Some(sellers) = self.offers.get_mut(asset_id) else {
+            return Vec::new();
+        };
+        let mut remaining = Vec::new();
+        for (user_id, offered) in sellers.iter_mut() {
+            if count == 0 {
+                break;
+            }
+            let taken = count.min(*offered);
+            *offered -= taken;
+            count -= taken;
+            remaining.push((user_id.clone(), *offered));
+        }
+        sellers.retain(|_, offered| *offered > 0);
+        if sellers.is_empty() {
+            self.offers.remove(asset_id);
+        }
+        remaining
+    }
+
+    /// Total quantity of an asset on offer.
+    pub fn offered(&self, asset_id: &AssetId) -> u32 {
+        self.offers
+            .get(asset_id)
+            .map_or(0, |sellers| sellers.values().sum())
+    }
+
+    /// Renders the offers as a JSON object keyed by asset, then user.
+    pub fn to_json(&self) -> String {
+        json::object(self.offers.iter().map(|(asset_id, sellers)| {
+            let sellers = sellers
+                .iter()
+                .map(|(user_id, count)| (&user_id.0, count.to_string()));
+            (&asset_id.0, json::object(sellers))
+        }))
+    }
+}
+
+impl fmt::Display for Book {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (asset_id, sellers) in &self.offers {
+            writeln!(f, "  {}: {} offered", asset_id, self.offered(asset_id))?;
+            for (user_id, count) in sellers {
+                writeln!(f, "    {user_id}: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `BuyAsset` for more units than were on offer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub request_id: NonZeroU32,
+    pub user_id: UserId,
+    pub asset_id: AssetId,
+    pub requested: u32,
+    pub offered: u32,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request {}: {} bought {} {}, {} offered",
+            self.request_id, self.user_id, self.requested, self.asset_id, self.offered
+        )
+    }
+}
+
+/// A change to the book made by one line.
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    /// A `Check` snapshot.
+    Replace(Book),
+    /// The new quantity of one user's offer.
+    Set {
+        user_id: UserId,
+        asset_id: AssetId,
+        count: u32,
+    },
+}
+
+/// Offers replayed from `App::Trace Check` snapshots and journal trades.
+///
+/// A `Check` replaces the book with the announced offers, a `SellAsset`
+/// adds to the seller's offer and a `BuyAsset` takes from the offers of its
+/// asset. Buys for more than is on offer are recorded as [Violation]s.
+///
+/// Changes are kept as deltas in log order, with the position of the last
+/// line of every request, so the book can be rebuilt [as of](OrderBook::as_of)
+/// any request id even when ids do not grow through the log.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct OrderBook {
+    book: Book,
+    /// Lines seen so far.
+    seq: usize,
+    /// Changes keyed by the sequence number of their line.
+    history: Vec<(usize, Change)>,
+    /// Sequence number of the last line of every request.
+    last_seq: HashMap<NonZeroU32, usize>,
+    violations: Vec<Violation>,
+    /// Sequence number of the line of every violation.
+    violation_seqs: Vec<usize>,
+}
+
+impl Accumulator for OrderBook {
+    /// Applies a log line; lines other than `Check` and trades are ignored.
+    fn add(&mut self, log: &LogLine) {
+        let seq = self.seq;
+        self.seq += 1;
+        self.last_seq.insert(log.request_id, seq);
+        match &log.kind {
+            LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) => {
+                self.book = Book::from_announcements(announcements);
+                self.history.push((seq, Change::Replace(self.book.clone())));
+            }
+            LogKind::App(AppLogKind::Journal(AppLogJournalKind::SellAsset(trade))) => {
+                let asset_id = &trade.backet.asset_id;
+                self.book
+                    .offer(&trade.user_id, asset_id, trade.backet.count.get());
+                let count = self.book.offers[asset_id][&trade.user_id];
+                self.history.push((
+                    seq,
+                    Change::Set {
+                        user_id: trade.user_id.clone(),
+                        asset_id: asset_id.clone(),
+                        count,
+                    },
+                ));
+            }
+            LogKind::App(AppLogKind::Journal(AppLogJournalKind::BuyAsset(trade))) => {
+                let asset_id = &trade.backet.asset_id;
+                let requested = trade.backet.count.get();
+                let offered = self.book.offered(asset_id);
+                if requested > offered {
+                    self.violations.push(Violation {
+                        request_id: log.request_id,
+                        user_id: trade.user_id.clone(),
+                        asset_id: asset_id.clone(),
+                        requested,
+                        offered,
+                    });
+                    self.violation_seqs.push(seq);
+                }
+                for (user_id, count) in self.book.take(asset_id, requested) {
+                    let asset_id = asset_id.clone();
+                    let change = Change::Set {
+                        user_id,
+                        asset_id,
+                        count,
+                    };
+                    self.history.push((seq, change));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl OrderBook {
+    /// The book after the last line seen.
+    pub fn current(&self) -> &Book {
+        &self.book
+    }
+
+    /// The book after the last line of `request_id`, rebuilt from the last
+    /// `Check` before it; `None` if the request is not in the log.
+    pub fn as_of(&self, request_id: NonZeroU32) -> Option<Book> {
+        let seq = *self.last_seq.get(&request_id)?;
+        let changes = &self.history[..self.history.partition_point(|(at, _)| *at <= seq)];
+        let start = changes
+            .iter()
+            .rposition(|(_, change)| matches!(change, Change::Replace(_)))
+            .unwrap_or(0);
+        let mut book = Book::default();
+        for (_, change) in &changes[start..] {
+            match change {
+                Change::Replace(snapshot) => book = snapshot.clone(),
+                Change::Set {
+                    user_id,
+                    asset_id,
+                    count,
+                } => book.set(user_id, asset_id, *count),
+            }
+        }
+        Some(book)
+    }
+
+    /// Buys that exceeded the offers, in log order.
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Violations up to the last line of `request_id`; `None` if the request
+    /// is not in the log.
+    pub fn violations_as_of(&self, request_id: NonZeroU32) -> Option<&[Violation]> {
+        let seq = *self.last_seq.get(&request_id)?;
+        let count = self.violation_seqs.partition_point(|at| *at <= seq);
+        Some(&self.violations[..count])
+    }
+
+    /// Renders the book as of `request_id` (or the current one) and the
+    /// violations up to it as a JSON object; `None` if the request is not in
+    /// the log.
+    pub fn to_json(&self, request_id: Option<NonZeroU32>) -> Option<String> {
+        let (book, violations) = match request_id {
+            Some(id) => (self.as_of(id)?, self.violations_as_of(id)?),
+            None => (self.book.clone(), self.violations()),
+        };
+        let violations = violations.iter().map(|v| {
+            json::object([
+                ("request_id", v.request_id.to_string()),
+                ("user_id", json::string(&v.user_id.0)),
+                ("asset_id", json::string(&v.asset_id.0)),
+                ("requested", v.requested.to_string()),
+                ("offered", v.offered.to_string()),
+            ])
+        });
+        Some(json::object([
+            (
+                "as_of",
+                request_id.map_or("null".to_owned(), |id| id.to_string()),
+            ),
+            ("book", book.to_json()),
+            ("violations", json::array(violations)),
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const SOURCE: &str = r#"
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=2
+App::Journal SellAsset UserBacket{"user_id":"Carol","backet":Backet{"asset_id":"milk","count":4,},} requestid=3
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=5
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"eggs","count":2,},],},] requestid=7
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"eggs","count":3,},} requestid=8
+"#;
+
+    fn order_book() -> OrderBook {
+        accumulate(SOURCE)
+    }
+
+    #[test]
+    fn test_book_as_of() {
+        let order_book = order_book();
+        let milk = AssetId("milk".into());
+        assert_eq!(order_book.as_of(nz(1)), None);
+        assert_eq!(order_book.as_of(nz(4)), None);
+        assert_eq!(order_book.as_of(nz(3)).unwrap().offered(&milk), 7);
+        // Bob's offer is taken first, then two of Carol's four.
+        assert_eq!(
+            order_book.as_of(nz(5)).unwrap().offers[&milk],
+            BTreeMap::from([(UserId("Carol".into()), 2)])
+        );
+        assert_eq!(order_book.as_of(nz(7)).unwrap().offered(&milk), 0);
+        assert_eq!(order_book.as_of(nz(8)).as_ref(), Some(order_book.current()));
+        assert_eq!(order_book.current(), &Book::default());
+    }
+
+    #[test]
+    fn test_as_of_out_of_order_ids() {
+        // Request 2 runs after request 9 and ends after request 4.
+        let order_book: OrderBook = accumulate(
+            r#"
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=9
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":1,},} requestid=2
+App::Journal SellAsset UserBacket{"user_id":"Carol","backet":Backet{"asset_id":"milk","count":4,},} requestid=4
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":9,},} requestid=1
+System::Trace SendRequest "done" requestid=2
+"#,
+        );
+        let milk = AssetId("milk".into());
+        assert_eq!(order_book.as_of(nz(9)).unwrap().offered(&milk), 3);
+        assert_eq!(order_book.as_of(nz(4)).unwrap().offered(&milk), 6);
+        assert_eq!(order_book.as_of(nz(1)), Some(Book::default()));
+        assert_eq!(order_book.as_of(nz(2)), Some(Book::default()));
+        assert_eq!(order_book.violations_as_of(nz(4)).unwrap(), []);
+        assert_eq!(order_book.violations_as_of(nz(2)).unwrap().len(), 1);
+        assert_eq!(order_book.violations_as_of(nz(3)), None);
+    }
+
+    #[test]
+    fn test_violations() {
+        let order_book = order_book();
+        assert_eq!(
+            order_book.violations(),
+            [Violation {
+                request_id: nz(8),
+                user_id: UserId("Alice".into()),
+                asset_id: AssetId("eggs".into()),
+                requested: 3,
+                offered: 2,
+            }]
+        );
+        assert_eq!(
+            order_book.to_json(Some(nz(5))).unwrap(),
+            r#"{"as_of":5,"book":{"milk":{"Carol":2}},"violations":[]}"#
+        );
+        assert_eq!(order_book.to_json(Some(nz(6))), None);
+    }
+}
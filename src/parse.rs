@@ -1,7 +1,15 @@
+mod attributes;
 mod combinators;
 mod domain;
+mod grammar;
 mod log;
+mod schema;
+mod value;
 
+pub use attributes::*;
 pub use combinators::*;
 pub use domain::*;
+pub use grammar::*;
 pub use log::*;
+pub use schema::*;
+pub use value::*;
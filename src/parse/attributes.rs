@@ -0,0 +1,286 @@
+use std::fmt;
+use std::num::NonZeroU32;
+
+use super::combinators::*;
+
+/// Typed value of a trailing [Attribute], inferred from its text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Bool(bool),
+    /// A quoted value, or a bare one that is no number or boolean.
+    Str(String),
+}
+
+impl AttributeValue {
+    /// Infers the type of a bare value: a boolean, a number as read by
+    /// [primitives] or, failing that, a string.
+    fn infer(bare: &str) -> Self {
+        fn whole<P: Parser>(parser: P, input: &str) -> Option<P::Dest> {
+            let (_, (value, ())) = tuple2(parser, eof()).parse(input).ok()?;
+            Some(value)
+        }
+        match bare {
+            "true" => AttributeValue::Bool(true),
+            "false" => AttributeValue::Bool(false),
+            _ => whole(primitives::U64, bare)
+                .map(AttributeValue::U64)
+                .or_else(|| whole(primitives::I64, bare).map(AttributeValue::I64))
+                .or_else(|| whole(primitives::F64, bare).map(AttributeValue::F64))
+                .unwrap_or_else(|| AttributeValue::Str(bare.to_owned())),
+        }
+    }
+}
+
+/// A `key=value` attribute after the log payload, e.g. `latency_ms=12`
+/// or `session="a b"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub key: String,
+    /// The value as written, quotes included.
+    pub raw: String,
+    pub value: AttributeValue,
+}
+
+impl Attribute {
+    /// The value without quotes: the string of a quoted value, else [Attribute::raw].
+    pub fn text(&self) -> &str {
+        match (&self.value, self.raw.starts_with('"')) {
+            (AttributeValue::Str(text), true) => text,
+            _ => &self.raw,
+        }
+    }
+}
+
+impl fmt::Display for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.raw)
+    }
+}
+
+/// Trailing attributes of a [LogLine](super::LogLine) in the order they
+/// were written; keys are unique.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Attributes(Vec<Attribute>);
+
+impl Attributes {
+    pub fn get(&self, key: &str) -> Option<&Attribute> {
+        self.0.iter().find(|attribute| attribute.key == key)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Attribute> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Calls `f` on the [text](Attribute::text) of every string-valued
+    /// attribute; a changed text is written back, quoted if it was before.
+    pub fn texts_mut(&mut self, mut f: impl FnMut(&mut String)) {
+        for attribute in &mut self.0 {
+            let AttributeValue::Str(text) = &mut attribute.value else {
+                continue;
+            };
+            let original = text.clone();
+            f(text);
+            if *text == original {
+                continue;
+            }
+            let bare = !text.is_empty() && !text.contains(|c: char| c.is_whitespace() || c == '"');
+            if attribute.raw.starts_with('"') || !bare {
+                attribute.raw = quote(text);
+            } else {
+                attribute.raw = text.clone();
+                attribute.value = AttributeValue::infer(text);
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = &'a Attribute;
+    type IntoIter = std::slice::Iter<'a, Attribute>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Emit for Attributes {
+    fn emit(&self, out: &mut String) {
+        for attribute in &self.0 {
+            out.push(' ');
+            out.push_str(&attribute.key);
+            out.push('=');
+            out.push_str(&attribute.raw);
+        }
+    }
+}
+
+/// Parses one `key=value` attribute; the value is quoted or runs up to the
+/// next whitespace.
+fn attribute(input: &str) -> Result<(&str, Attribute), ParseError> {
+    let (rest, key) =
+        take_while(|c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+            .parse(input)?;
+    if key.is_empty() || key == "requestid" {
+        return Err(ParseError::UnexpectedInput("expected attribute key"));
+    }
+    let rest = rest.strip_prefix('=').ok_or(ParseError::UnexpectedInput(
+        "expected '=' after attribute key",
+    ))?;
+    let (remaining, raw, value) = if rest.starts_with('"') {
+        let (remaining, text) = unquote().parse(rest)?;
+        let raw = &rest[..rest.len() - remaining.len()];
+        (remaining, raw, AttributeValue::Str(text))
+    } else {
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '"')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(ParseError::UnexpectedInput("expected attribute value"));
+        }
+        (
+            &rest[end..],
+            &rest[..end],
+            AttributeValue::infer(&rest[..end]),
+        )
+    };
+    let attribute = Attribute {
+        key,
+        raw: raw.to_owned(),
+        value,
+    };
+    Ok((remaining, attribute))
+}
+
+/// Parser of a log line's trailer: `requestid=N` and [attributes](Attributes)
+/// in any order, separated by whitespace
+#[derive(Debug, Clone)]
+pub struct Trailer;
+impl Trailer {
+    /// Byte offset in `input` of the first whitespace-separated token from
+    /// which [Trailer] reads the rest of the line, skipping quoted strings;
+    /// the length of `input` if there is none. Free text before it is the
+    /// payload of the line.
+    pub(crate) fn start(input: &str) -> usize {
+        let mut idx = 0;
+        while let Some(c) = input[idx..].chars().next() {
+            let rest = &input[idx..];
+            let token_start = idx == 0 || input[..idx].ends_with(char::is_whitespace);
+            if token_start && !c.is_whitespace() && matches!(Trailer.parse(rest), Ok(("", _))) {
+                return idx;
+            }
+            idx += c.len_utf8();
+            if c == '"'
+                && let Ok((after, _)) = unquote().parse(rest)
+            {
+                idx = input.len() - after.len();
+            }
+        }
+        input.len()
+    }
+}
+impl Parser for Trailer {
+    type Dest = (NonZeroU32, Attributes);
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let mut remaining = input;
+        let mut request_id = None;
+        let mut attributes = Vec::<Attribute>::new();
+        loop {
+            let token = remaining.trim_start();
+            let separated = |rest: &str| rest.is_empty() || rest.starts_with(char::is_whitespace);
+            if let Ok((rest, id)) = primitives::U32.preceded_by(tag("requestid=")).parse(token)
+                && separated(rest)
+            {
+                if request_id.replace(id).is_some() {
+                    return Err(ParseError::UnexpectedInput("duplicate requestid"));
+                }
+                remaining = rest;
+            } else if let Ok((rest, attribute)) = attribute(token)
+                && separated(rest)
+            {
+                if attributes.iter().any(|seen| seen.key == attribute.key) {
+                    return Err(ParseError::UnexpectedInput("duplicate attribute"));
+                }
+                attributes.push(attribute);
+                remaining = rest;
+            } else {
+                break;
+            }
+        }
+        let request_id = request_id.ok_or(ParseError::IncompleteInput("expected requestid="))?;
+        Ok((remaining.trim_start(), (request_id, Attributes(attributes))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nz(n: u32) -> NonZeroU32 {
+        NonZeroU32::new(n).unwrap()
+    }
+
+    #[test]
+    fn test_trailer() {
+        assert_eq!(
+            Trailer.parse(" requestid=7"),
+            Ok(("", (nz(7), Attributes::default())))
+        );
+        let (remaining, (request_id, attributes)) = Trailer
+            .parse(r#"session="a b" requestid=7 latency_ms=1_200 exchange=Jupiter delta=-3 ratio=0.5 cached=true"#)
+            .unwrap();
+        assert_eq!((remaining, request_id), ("", nz(7)));
+        let values: Vec<_> = attributes
+            .iter()
+            .map(|attribute| (attribute.key.as_str(), attribute.value.clone()))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("session", AttributeValue::Str("a b".into())),
+                ("latency_ms", AttributeValue::U64(1200)),
+                ("exchange", AttributeValue::Str("Jupiter".into())),
+                ("delta", AttributeValue::I64(-3)),
+                ("ratio", AttributeValue::F64(0.5)),
+                ("cached", AttributeValue::Bool(true)),
+            ]
+        );
+        let session = attributes.get("session").unwrap();
+        assert_eq!((session.raw.as_str(), session.text()), (r#""a b""#, "a b"));
+        assert_eq!(attributes.get("latency_ms").unwrap().text(), "1_200");
+        assert_eq!(attributes.get("user"), None);
+        assert_eq!(
+            attributes.to_log_string(),
+            r#" session="a b" latency_ms=1_200 exchange=Jupiter delta=-3 ratio=0.5 cached=true"#
+        );
+
+        // Whatever is not an attribute is left to the caller.
+        assert_eq!(
+            Trailer.parse("requestid=7 trailing junk"),
+            Ok(("trailing junk", (nz(7), Attributes::default())))
+        );
+        assert_eq!(
+            Trailer
+                .parse("requestid=7x")
+                .map(|(remaining, _)| remaining),
+            Err(ParseError::IncompleteInput("expected requestid="))
+        );
+        assert_eq!(
+            Trailer.parse("session=1"),
+            Err(ParseError::IncompleteInput("expected requestid="))
+        );
+        assert_eq!(
+            Trailer.parse("requestid=1 requestid=2"),
+            Err(ParseError::UnexpectedInput("duplicate requestid"))
+        );
+        assert_eq!(
+            Trailer.parse("a=1 requestid=2 a=2"),
+            Err(ParseError::UnexpectedInput("duplicate attribute"))
+        );
+    }
+}
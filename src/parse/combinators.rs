@@ -1,3 +1,7 @@
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+
 /// Structured error type for parser failures.
 #[derive(Debug, Clone, PartialEq, thiserror::Error)]
 pub enum ParseError {
@@ -10,6 +14,38 @@ pub enum ParseError {
     /// A parsed value was out of range or otherwise invalid.
     #[error("invalid value: {0}")]
     InvalidValue(&'static str),
+    /// A numeric value does not fit the target type.
+    #[error("overflow: {0}")]
+    Overflow(&'static str),
+    /// Failure past a [cut] point: alternatives, options and repetitions
+    /// pass it on instead of trying another branch.
+    #[error("{0}")]
+    Cut(Box<ParseError>),
+    /// A mandatory key of a [Permutation] is absent.
+    #[error("missing key: {0}")]
+    MissingKey(String),
+    /// A key of a [Permutation] occurs twice.
+    #[error("duplicate key: {0}")]
+    DuplicateKey(String),
+    /// A [Recursive] parser nested deeper than [MAX_DEPTH].
+    #[error("nesting deeper than {0} levels")]
+    TooDeep(usize),
+}
+impl ParseError {
+    /// `true` for a [ParseError::Cut] failure, which must not be backtracked
+    pub fn is_cut(&self) -> bool {
+        matches!(self, ParseError::Cut(_))
+    }
+}
+/// Runs a parser that may be backtracked: `Ok(None)` on a plain failure,
+/// which also drops the warnings it reported, the error itself on a
+/// [ParseError::Cut]
+fn backtrack<T>(parse: impl FnOnce() -> Result<T, ParseError>) -> Result<Option<T>, ParseError> {
+    match rewind_warnings(parse, Result::is_ok) {
+        Ok(ok) => Ok(Some(ok)),
+        Err(err) if err.is_cut() => Err(err),
+        Err(_) => Ok(None),
+    }
 }
 
 /// Трейт, чтобы **реализовывать** и **требовать** метод 'распарсь и покажи,
@@ -55,15 +91,195 @@ pub trait Parser {
     {
         StripWhitespace { parser: self }
     }
+
+    /// Fluent combinator: `None` instead of an error, consuming nothing.
+    ///
+    /// Equivalent to `opt(self)`.
+    fn opt(self) -> Opt<Self>
+    where
+        Self: Sized,
+    {
+        opt(self)
+    }
+
+    /// Fluent combinator: apply as many times as possible, possibly zero.
+    ///
+    /// Equivalent to `many0(self)`.
+    fn many0(self) -> Many<Self>
+    where
+        Self: Sized,
+    {
+        many0(self)
+    }
+
+    /// Fluent combinator: apply as many times as possible, at least once.
+    ///
+    /// Equivalent to `many1(self)`.
+    fn many1(self) -> Many<Self>
+    where
+        Self: Sized,
+    {
+        many1(self)
+    }
+
+    /// Fluent combinator: zero or more elements separated by `separator`,
+    /// without a trailing one.
+    ///
+    /// Equivalent to `separated_list(self, separator)`:
+    /// `primitives::U32.separated_by(tag(","))` parses `"1,2,3"`.
+    fn separated_by<S: Parser>(self, separator: S) -> SeparatedList<Self, S>
+    where
+        Self: Sized,
+    {
+        separated_list(self, separator)
+    }
+
+    /// Fluent combinator: the consumed input instead of the parsed value.
+    ///
+    /// Equivalent to `recognize(self)`.
+    fn recognize(self) -> Recognize<Self>
+    where
+        Self: Sized,
+    {
+        recognize(self)
+    }
+
+    /// Fluent combinator: parse without consuming input.
+    ///
+    /// Equivalent to `peek(self)`.
+    fn peek(self) -> Peek<Self>
+    where
+        Self: Sized,
+    {
+        peek(self)
+    }
+
+    /// Fluent combinator: succeed, consuming nothing, only where `self` fails.
+    ///
+    /// Equivalent to `not(self)`.
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        not(self)
+    }
+
+    /// Fluent combinator: turn any failure into a [ParseError::Cut].
+    ///
+    /// Equivalent to `cut(self)`. Put it on whatever follows a prefix that
+    /// settles the branch: in `alt2(unquote().cut().preceded_by(tag("msg=")), ...)`
+    /// a bad quote after `msg=` is reported instead of trying the next branch.
+    fn cut(self) -> Cut<Self>
+    where
+        Self: Sized,
+    {
+        cut(self)
+    }
+
+    /// Fluent combinator: reject a parsed value failing `predicate`.
+    ///
+    /// Equivalent to `verify(self, predicate)`.
+    fn verify<F: Fn(&Self::Dest) -> bool>(self, predicate: F) -> Verify<Self, F>
+    where
+        Self: Sized,
+    {
+        verify(self, predicate)
+    }
+
+    /// Fluent combinator: replace the parsed value with a constant.
+    ///
+    /// Equivalent to `value(self, value)`: `tag("on").value(true)`.
+    fn value<V: Clone>(self, value: V) -> Constant<Self, V>
+    where
+        Self: Sized,
+    {
+        self::value(self, value)
+    }
+
+    /// Fluent combinator: erase the parser's type behind a [BoxedParser].
+    ///
+    /// Useful where the type cannot be written out, e.g. the grammar of a
+    /// [Recursive] parser.
+    fn boxed(self) -> BoxedParser<Self::Dest>
+    where
+        Self: Sized + 'static,
+    {
+        Box::new(self)
+    }
+}
+/// A borrowed parser, so one can be reused inside another combinator
+impl<P: Parser + ?Sized> Parser for &P {
+    type Dest = P::Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        (**self).parse(input)
+    }
+}
+impl<P: Parser + ?Sized> Parser for Box<P> {
+    type Dest = P::Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        (**self).parse(input)
+    }
 }
+/// Parser of a type that is not spelled out, see [Parser::boxed]
+pub type BoxedParser<Dest> = Box<dyn Parser<Dest = Dest>>;
 /// Вспомогательный трейт, чтобы писать собственный десериализатор
 /// (по решаемой задаче - отдалённый аналог `serde::Deserialize`)
 pub trait Parsable: Sized {
     type Parser: Parser<Dest = Self>;
     fn parser() -> Self::Parser;
 }
+/// Inverse of [Parsable]: writes a value back in the log grammar, so that
+/// parsing the output yields an equal value
+pub trait Emit {
+    fn emit(&self, out: &mut String);
+    /// [Emit::emit] into a fresh string
+    fn to_log_string(&self) -> String {
+        let mut out = String::new();
+        self.emit(&mut out);
+        out
+    }
+}
+impl Emit for std::num::NonZeroU32 {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+}
+macro_rules! impl_emit_display {
+    ($($T:ty),+) => {
+        $(impl Emit for $T {
+            fn emit(&self, out: &mut String) {
+                out.push_str(&self.to_string());
+            }
+        })+
+    };
+}
+impl_emit_display!(u64, i64, f64);
+/// Quoted, as read back by [unquote]
+impl Emit for String {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&quote(self));
+    }
+}
+/// `[item,item,]`, as read back by [list]
+impl<T: Emit> Emit for Vec<T> {
+    fn emit(&self, out: &mut String) {
+        out.push('[');
+        for item in self {
+            item.emit(out);
+            out.push(',');
+        }
+        out.push(']');
+    }
+}
+/// Writes `"key":value,` as read back by [key_value]
+pub(crate) fn emit_key_value(out: &mut String, key: &str, value: &impl Emit) {
+    out.push_str(&quote(key));
+    out.push(':');
+    value.emit(out);
+    out.push(',');
+}
 
-pub(crate) mod primitives {
+pub mod primitives {
     // parsers for std types
     use super::{ParseError, Parser};
     use std::num::NonZeroU32;
@@ -86,11 +302,202 @@ pub(crate) mod primitives {
                 })
                 .unwrap_or(remaining.len());
             let value = u32::from_str_radix(&remaining[..end_idx], if is_hex { 16 } else { 10 })
-                .map_err(|_| ParseError::InvalidValue("invalid u32 literal"))?;
+                .map_err(|err| match err.kind() {
+                    std::num::IntErrorKind::PosOverflow => ParseError::Overflow("u32 overflow"),
+                    _ => ParseError::InvalidValue("invalid u32 literal"),
+                })?;
             let non_zero = NonZeroU32::new(value).ok_or(ParseError::InvalidValue("zero is not allowed"))?;
             Ok((&remaining[end_idx..], non_zero))
         }
     }
+    /// Number literal `[+-]digits[.digits][e[+-]digits]` with `_` digit
+    /// separators removed: the value is `digits * 10^exponent`
+    struct Literal<'a> {
+        negative: bool,
+        digits: String,
+        exponent: i64,
+        remaining: &'a str,
+    }
+    /// Digits of `radix` with single `_` separators between them
+    fn digit_groups(input: &str, radix: u32) -> Result<(&str, String), ParseError> {
+        let mut digits = String::new();
+        let mut end = 0;
+        for (idx, c) in input.char_indices() {
+            match c {
+                '_' if digits.is_empty() || input[idx + 1..].chars().next().is_none_or(|c| !c.is_digit(radix)) => {
+                    return Err(ParseError::UnexpectedInput("misplaced digit separator"));
+                }
+                '_' => {}
+                c if c.is_digit(radix) => digits.push(c),
+                _ => break,
+            }
+            end = idx + c.len_utf8();
+        }
+        if digits.is_empty() {
+            return Err(ParseError::UnexpectedInput("expected decimal digits"));
+        }
+        Ok((&input[end..], digits))
+    }
+    /// Optional `-` or `+`; `true` if negative
+    fn sign(input: &str) -> (bool, &str) {
+        match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input.strip_prefix('+').unwrap_or(input)),
+        }
+    }
+    fn literal(input: &str) -> Result<Literal<'_>, ParseError> {
+        let (negative, input) = sign(input);
+        let (mut remaining, mut digits) = digit_groups(input, 10)?;
+        let mut exponent = 0i64;
+        if let Some(rest) = remaining.strip_prefix('.') {
+            let (rest, frac) = digit_groups(rest, 10).map_err(|err| match err {
+                ParseError::UnexpectedInput("expected decimal digits") => {
+                    ParseError::IncompleteInput("expected fraction digits")
+                }
+                err => err,
+            })?;
+            exponent -= frac.len() as i64;
+            digits.push_str(&frac);
+            remaining = rest;
+        }
+        // `e` not followed by digits is left to the caller, e.g. `5eur`
+        if let Some(rest) = remaining.strip_prefix(['e', 'E'])
+            && let (negative_exp, unsigned) = sign(rest)
+            && unsigned.starts_with(|c: char| c.is_ascii_digit())
+        {
+            let (rest, exp) = digit_groups(unsigned, 10)?;
+            let exp: i64 = exp
+                .parse()
+                .ok()
+                .filter(|exp| *exp <= MAX_EXPONENT)
+                .ok_or(ParseError::Overflow("exponent out of range"))?;
+            exponent += if negative_exp { -exp } else { exp };
+            remaining = rest;
+        }
+        Ok(Literal {
+            negative,
+            digits,
+            exponent,
+            remaining,
+        })
+    }
+    /// Largest exponent magnitude accepted in a literal.
+    const MAX_EXPONENT: i64 = 10_000;
+    enum ScaleError {
+        Fractional,
+        Overflow,
+    }
+    /// `digits * 10^exponent` as an integer
+    fn scaled(digits: &str, exponent: i64) -> Result<u64, ScaleError> {
+        let keep = match exponent {
+            0.. => digits.len(),
+            _ => digits.len().saturating_sub(exponent.unsigned_abs() as usize),
+        };
+        let (digits, dropped) = digits.split_at(keep);
+        if dropped.bytes().any(|b| b != b'0') {
+            return Err(ScaleError::Fractional);
+        }
+        let value = digits
+            .bytes()
+            .try_fold(0u64, |acc, b| acc.checked_mul(10)?.checked_add(u64::from(b - b'0')))
+            .ok_or(ScaleError::Overflow)?;
+        if value == 0 || exponent <= 0 {
+            return Ok(value);
+        }
+        u32::try_from(exponent)
+            .ok()
+            .and_then(|exp| 10u64.checked_pow(exp))
+            .and_then(|scale| value.checked_mul(scale))
+            .ok_or(ScaleError::Overflow)
+    }
+    /// Unsigned integer in a `u64`, zero allowed: decimal with optional `_`
+    /// separators and exponent (`1_000`, `15e2`), or hex after `0x`
+    #[derive(Debug, Clone)]
+    pub struct U64;
+    impl Parser for U64 {
+        type Dest = u64;
+        fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+            if let Some(hex) = input.strip_prefix("0x") {
+                let (remaining, digits) = digit_groups(hex, 16)
+                    .map_err(|_| ParseError::UnexpectedInput("expected hex digits"))?;
+                let value =
+                    u64::from_str_radix(&digits, 16).map_err(|_| ParseError::Overflow("u64 overflow"))?;
+                return Ok((remaining, value));
+            }
+            let literal = literal(input)?;
+            if literal.negative {
+                return Err(ParseError::InvalidValue("negative value"));
+            }
+            let value = scaled(&literal.digits, literal.exponent).map_err(|err| match err {
+                ScaleError::Fractional => ParseError::InvalidValue("not an integer"),
+                ScaleError::Overflow => ParseError::Overflow("u64 overflow"),
+            })?;
+            Ok((literal.remaining, value))
+        }
+    }
+    /// Signed integer in an `i64`, with the same syntax as [U64] minus hex
+    #[derive(Debug, Clone)]
+    pub struct I64;
+    impl Parser for I64 {
+        type Dest = i64;
+        fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+            let literal = literal(input)?;
+            let overflow = ParseError::Overflow("i64 overflow");
+            let magnitude = scaled(&literal.digits, literal.exponent).map_err(|err| match err {
+                ScaleError::Fractional => ParseError::InvalidValue("not an integer"),
+                ScaleError::Overflow => overflow.clone(),
+            })?;
+            let value = if literal.negative {
+                0i64.checked_sub_unsigned(magnitude)
+            } else {
+                i64::try_from(magnitude).ok()
+            };
+            Ok((literal.remaining, value.ok_or(overflow)?))
+        }
+    }
+    /// Finite `f64`: `-1.5`, `1_000.25`, `2.5e-3`; `inf` and `NaN` are not
+    /// accepted
+    #[derive(Debug, Clone)]
+    pub struct F64;
+    impl Parser for F64 {
+        type Dest = f64;
+        fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+            let literal = literal(input)?;
+            let sign = if literal.negative { "-" } else { "" };
+            let value: f64 = format!("{sign}{}e{}", literal.digits, literal.exponent)
+                .parse()
+                .map_err(|_| ParseError::InvalidValue("invalid float literal"))?;
+            if value.is_infinite() {
+                return Err(ParseError::Overflow("f64 overflow"));
+            }
+            Ok((literal.remaining, value))
+        }
+    }
+    /// Unsigned non-zero fixed-point decimal, `123`, `1_000.45` or `1.5e3`,
+    /// as a count of `10^-decimals` units; digits beyond `decimals` places
+    /// must be zero
+    #[derive(Debug, Clone)]
+    pub struct Decimal {
+        pub decimals: u32,
+    }
+    impl Parser for Decimal {
+        type Dest = u64;
+        fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+            if !input.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(ParseError::UnexpectedInput("expected decimal digits"));
+            }
+            let literal = literal(input)?;
+            let exponent = literal.exponent + i64::from(self.decimals);
+            let value = scaled(&literal.digits, exponent).map_err(|err| match err {
+                ScaleError::Fractional => ParseError::InvalidValue("too many decimal places"),
+                ScaleError::Overflow => ParseError::Overflow("decimal out of range"),
+            })?;
+            if value == 0 {
+                return Err(ParseError::InvalidValue("zero is not allowed"));
+            }
+            Ok((literal.remaining, value))
+        }
+    }
     /// Знаковые числа
     #[cfg(test)]
     #[derive(Debug)]
@@ -111,20 +518,74 @@ pub(crate) mod primitives {
             Ok((&input[end_idx..], value))
         }
     }
-    /// Шестнадцатеричные байты (пригодится при парсинге блобов)
+    /// Binary blob up to the next whitespace: hex digits, or base64
+    /// (standard alphabet, padding optional) after a `base64:` prefix
     #[derive(Debug, Clone)]
-    pub struct Byte;
-    impl Parser for Byte {
-        type Dest = u8;
+    pub struct Blob {
+        pub max_len: usize,
+    }
+    impl Parser for Blob {
+        type Dest = Vec<u8>;
         fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-            let (to_parse, remaining) = input.split_at_checked(2).ok_or(ParseError::IncompleteInput("expected 2 hex digits"))?;
-            if !to_parse.chars().all(|c| c.is_ascii_hexdigit()) {
-                return Err(ParseError::UnexpectedInput("expected hex digit"));
+            let end_idx = input.find(char::is_whitespace).unwrap_or(input.len());
+            let (token, remaining) = input.split_at(end_idx);
+            let bytes = match token.strip_prefix("base64:") {
+                Some(encoded) => decode_base64(encoded)?,
+                None => decode_hex(token)?,
+            };
+            if bytes.len() > self.max_len {
+                return Err(ParseError::InvalidValue("blob is longer than allowed"));
             }
-            let value = u8::from_str_radix(to_parse, 16).map_err(|_| ParseError::InvalidValue("invalid hex byte"))?;
-            Ok((remaining, value))
+            Ok((remaining, bytes))
         }
     }
+    fn decode_hex(token: &str) -> Result<Vec<u8>, ParseError> {
+        if token.is_empty() {
+            return Err(ParseError::IncompleteInput("expected hex digits"));
+        }
+        if !token.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseError::UnexpectedInput("non-hex character in blob"));
+        }
+        if !token.len().is_multiple_of(2) {
+            return Err(ParseError::IncompleteInput("odd number of hex digits"));
+        }
+        (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16).map_err(|_| ParseError::InvalidValue("invalid hex byte")))
+            .collect()
+    }
+    fn decode_base64(token: &str) -> Result<Vec<u8>, ParseError> {
+        let data = token.trim_end_matches('=');
+        if data.is_empty() {
+            return Err(ParseError::IncompleteInput("expected base64 data"));
+        }
+        if token.len() - data.len() > 2 || (data.len() != token.len() && !token.len().is_multiple_of(4)) {
+            return Err(ParseError::InvalidValue("invalid base64 padding"));
+        }
+        let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+        let (mut acc, mut bits) = (0u32, 0);
+        for c in data.bytes() {
+            let value = match c {
+                b'A'..=b'Z' => c - b'A',
+                b'a'..=b'z' => c - b'a' + 26,
+                b'0'..=b'9' => c - b'0' + 52,
+                b'+' => 62,
+                b'/' => 63,
+                _ => return Err(ParseError::UnexpectedInput("non-base64 character in blob")),
+            };
+            acc = (acc << 6) | u32::from(value);
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((acc >> bits) as u8);
+            }
+        }
+        if data.len() % 4 == 1 {
+            return Err(ParseError::IncompleteInput("truncated base64 data"));
+        }
+        Ok(bytes)
+    }
+
 }
 
 /// Распарсить строку, которую ранее [обернули в кавычки](quote)
@@ -172,21 +633,35 @@ impl Parser for Unquote {
 pub(crate) fn unquote() -> Unquote {
     Unquote
 }
+/// Wraps `input` in quotes, escaping `"` and `\`; the inverse of [Unquote]
+pub(crate) fn quote(input: &str) -> String {
+    let mut result = String::from("\"");
+    result.extend(
+        input
+            .chars()
+            .flat_map(|c| match c {
+                '\\' | '"' => ['\\', c].into_iter().take(2),
+                _ => [c, ' '].into_iter().take(1),
+            }),
+    );
+    result.push('"');
+    result
+}
 /// Парсер константных строк
 /// (аналог `nom::bytes::complete::tag`)
 #[derive(Debug, Clone)]
 pub struct Tag {
-    tag: &'static str,
+    tag: Cow<'static, str>,
 }
 impl Parser for Tag {
     type Dest = ();
     fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-        Ok((input.strip_prefix(self.tag).ok_or(ParseError::UnexpectedInput("tag mismatch"))?, ()))
+        Ok((input.strip_prefix(&*self.tag).ok_or(ParseError::UnexpectedInput("tag mismatch"))?, ()))
     }
 }
 /// Конструктор [Tag]
-pub(crate) fn tag(tag: &'static str) -> Tag {
-    Tag { tag }
+pub(crate) fn tag(tag: impl Into<Cow<'static, str>>) -> Tag {
+    Tag { tag: tag.into() }
 }
 /// Парсер [тэга](Tag), обёрнутого в кавычки
 #[derive(Debug, Clone)]
@@ -202,8 +677,8 @@ impl Parser for QuotedTag {
     }
 }
 /// Конструктор [QuotedTag]
-pub(crate) fn quoted_tag(tag: &'static str) -> QuotedTag {
-    QuotedTag(Tag { tag })
+pub(crate) fn quoted_tag(tag: impl Into<Cow<'static, str>>) -> QuotedTag {
+    QuotedTag(Tag { tag: tag.into() })
 }
 /// Комбинатор, пробрасывающий строку без лидирующих пробелов
 #[derive(Debug, Clone)]
@@ -335,12 +810,8 @@ impl_tuple!(@impl [A0 a0 0, A1 a1 1, A2 a2 2], A3 a3 3);
 /// простое '"ключ":значение' читаться не будет
 #[derive(Debug, Clone)]
 pub struct KeyValue<T> {
-    #[allow(clippy::type_complexity)]
-    parser: Delimited<
-        Tuple<(StripWhitespace<QuotedTag>, StripWhitespace<Tag>)>,
-        StripWhitespace<T>,
-        StripWhitespace<Tag>,
-    >,
+    key: QuotedTag,
+    value: T,
 }
 impl<T> Parser for KeyValue<T>
 where
@@ -348,120 +819,264 @@ where
 {
     type Dest = T::Dest;
     fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-        self.parser.parse(input)
+        let (remaining, ()) = strip_whitespace(&self.key).parse(input)?;
+        self.parse_value(remaining)
     }
 }
-/// Конструктор [KeyValue]
-pub(crate) fn key_value<T: Parser>(key: &'static str, value_parser: T) -> KeyValue<T> {
-    KeyValue {
-        parser: delimited(
-            tuple2(
-                strip_whitespace(quoted_tag(key)),
-                strip_whitespace(tag(":")),
-            ),
-            strip_whitespace(value_parser),
+impl<T: Parser> Field for KeyValue<T> {
+    type Value = T::Dest;
+    fn key(&self) -> &str {
+        &self.key.0.tag
+    }
+    fn parse_value<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Value), ParseError> {
+        delimited(
+            strip_whitespace(tag(":")),
+            strip_whitespace(&self.value),
             strip_whitespace(tag(",")),
-        ),
+        )
+        .parse(input)
     }
 }
-/// Комбинатор, который возвращает результаты дочерних парсеров, если их
-/// удалось применить друг после друга в любом порядке. Результат возвращается в
-/// том порядке, в каком `Permutation` был сконструирован
-/// (аналог `permutation` из `nom`)
+/// Constructor of [KeyValue]; the key may be built at runtime, e.g. from a
+/// [schema](super::Schema)
+pub(crate) fn key_value<T: Parser>(key: impl Into<Cow<'static, str>>, value_parser: T) -> KeyValue<T> {
+    KeyValue {
+        key: quoted_tag(key),
+        value: value_parser,
+    }
+}
+/// Field of a struct read by [Permutation]: the key and the parser of the
+/// value after it
+pub trait Field {
+    type Value;
+    fn key(&self) -> &str;
+    /// Parses `:value,` after the quoted key
+    fn parse_value<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Value), ParseError>;
+    /// Value of a missing key; `None` if the key is mandatory
+    fn default_value(&self) -> Option<Self::Value> {
+        None
+    }
+
+    /// Makes the key optional: [Permutation] takes `default` when it is missing.
+    ///
+    /// `key_value("fee", primitives::U64).with_default(0)`
+    fn with_default(self, default: Self::Value) -> WithDefault<Self, Self::Value>
+    where
+        Self: Sized,
+    {
+        WithDefault { field: self, default }
+    }
+}
+/// [Field] with a default value, built by [Field::with_default]
 #[derive(Debug, Clone)]
-pub struct Permutation<T> {
-    parsers: T,
+pub struct WithDefault<F, V> {
+    field: F,
+    default: V,
 }
-impl<A0, A1> Parser for Permutation<(A0, A1)>
-where
-    A0: Parser,
-    A1: Parser,
-{
-    type Dest = (A0::Dest, A1::Dest);
-    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-        match self.parsers.0.parse(input) {
-            Ok((remaining, a0)) => self
-                .parsers
-                .1
-                .parse(remaining)
-                .map(|(remaining, a1)| (remaining, (a0, a1))),
-            Err(_) => self.parsers.1.parse(input).and_then(|(remaining, a1)| {
-                self.parsers
-                    .0
-                    .parse(remaining)
-                    .map(|(remaining, a0)| (remaining, (a0, a1)))
-            }),
+impl<F: Field<Value = V>, V: Clone> Field for WithDefault<F, V> {
+    type Value = V;
+    fn key(&self) -> &str {
+        self.field.key()
+    }
+    fn parse_value<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Value), ParseError> {
+        self.field.parse_value(input)
+    }
+    fn default_value(&self) -> Option<Self::Value> {
+        Some(self.default.clone())
+    }
+}
+thread_local! {
+    /// Sink of the innermost [collect_warnings] call, if any
+    static WARNINGS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+/// Runs `f` and returns its result along with the warnings parsers reported
+/// meanwhile, e.g. unknown keys skipped by [Permutation].
+///
+/// [Parser::parse] has no room for warnings, so they are buffered here
+/// instead, and dropped again when a branch that reported them is
+/// backtracked over; this stays crate-private, and every public entry point that
+/// parses a line ([LogGrammar::parse_line](super::LogGrammar::parse_line),
+/// [crate::parse_line], [crate::LogIterator]) returns them.
+pub(crate) fn collect_warnings<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    /// Puts the outer sink back even if `f` panics
+    struct Restore(Option<Option<Vec<String>>>);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(outer) = self.0.take() {
+                WARNINGS.set(outer);
+            }
         }
     }
+    let mut restore = Restore(Some(WARNINGS.replace(Some(Vec::new()))));
+    let result = f();
+    let outer = restore.0.take().unwrap_or_default();
+    let warnings = WARNINGS.replace(outer).unwrap_or_default();
+    (result, warnings)
 }
-macro_rules! permutation_fn {
-    ($fn_name:ident, $($A:ident $a:ident),+) => {
-        pub(crate) fn $fn_name<$($A: Parser),+>($($a: $A),+) -> Permutation<($($A),+)> {
-            Permutation { parsers: ($($a),+) }
+/// Runs `f` and drops the warnings it reported unless `keep` accepts its
+/// result, so a branch that is backtracked over leaves none behind
+fn rewind_warnings<R>(f: impl FnOnce() -> R, keep: impl FnOnce(&R) -> bool) -> R {
+    let mark = WARNINGS.with_borrow(|sink| sink.as_ref().map(Vec::len));
+    let result = f();
+    if let Some(mark) = mark
+        && !keep(&result)
+    {
+        WARNINGS.with_borrow_mut(|sink| sink.as_mut().map(|sink| sink.truncate(mark)));
+    }
+    result
+}
+fn warn(message: String) {
+    WARNINGS.with_borrow_mut(|sink| {
+        if let Some(sink) = sink {
+            sink.push(message);
+        }
+    });
+}
+/// Skips `:value,` of an unknown key. The value is whatever comes before
+/// the next `,` outside of quotes, brackets and braces
+fn skip_value(input: &str) -> Result<&str, ParseError> {
+    let (mut remaining, ()) = strip_whitespace(tag(":")).parse(input)?;
+    let mut depth = 0usize;
+    loop {
+        remaining = match remaining.chars().next() {
+            None => return Err(ParseError::IncompleteInput("unterminated value of unknown key")),
+            Some('"') => unquote_escaped(remaining)?.0,
+            Some('[' | '{') => {
+                depth += 1;
+                &remaining[1..]
+            }
+            Some(']' | '}') if depth == 0 => {
+                return Err(ParseError::UnexpectedInput("expected ',' after value of unknown key"));
+            }
+            Some(']' | '}') => {
+                depth -= 1;
+                &remaining[1..]
+            }
+            Some(',') if depth == 0 => return Ok(remaining[1..].trim_start()),
+            Some(c) => &remaining[c.len_utf8()..],
+        }
+    }
+}
+/// Reads `"key":value,` pairs in any order until the input no longer starts
+/// with a quoted key and a colon. The value of `keys[idx]` is read by
+/// `parse_value(idx, ...)`; unknown keys are skipped with a
+/// [warning](collect_warnings), a repeated key is an error
+fn parse_fields<'a>(
+    input: &'a str,
+    keys: &[&str],
+    mut parse_value: impl FnMut(usize, &'a str) -> Result<&'a str, ParseError>,
+) -> Result<&'a str, ParseError> {
+    let mut seen = vec![false; keys.len()];
+    let mut remaining = input;
+    while let Ok((after_key, key)) = unquote_escaped(remaining.trim_start())
+        && after_key.trim_start().starts_with(':')
+    {
+        remaining = match keys.iter().position(|known| *known == key) {
+            Some(idx) if seen[idx] => return Err(ParseError::DuplicateKey(key)),
+            Some(idx) => {
+                seen[idx] = true;
+                parse_value(idx, after_key)?
+            }
+            None => {
+                warn(format!("unknown key {} skipped", quote(&key)));
+                skip_value(after_key)?
+            }
+        };
+    }
+    Ok(remaining)
+}
+/// The value read for `field`, else its default
+fn field_value<F: Field>(field: &F, value: Option<F::Value>) -> Result<F::Value, ParseError> {
+    value
+        .or_else(|| field.default_value())
+        .ok_or_else(|| ParseError::MissingKey(field.key().to_owned()))
+}
+/// Combinator that reads `"key":value,` [fields](Field) in any order. The
+/// values come back in the order the `Permutation` was built with. Unknown
+/// keys are skipped with a [warning](super::LogGrammar::parse_line); a repeated key, or a
+/// missing one without a [default](Field::with_default), is an error
+/// (like `permutation` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Permutation<T> {
+    fields: T,
+}
+macro_rules! impl_permutation {
+    ($($F:ident $f:ident $idx:tt),+) => {
+        impl<$($F: Field),+> Parser for Permutation<($($F,)+)> {
+            type Dest = ($($F::Value,)+);
+            fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+                $(let mut $f = None;)+
+                let keys = [$(self.fields.$idx.key()),+];
+                let remaining = parse_fields(input, &keys, |idx, input| match idx {
+                    $($idx => {
+                        let (remaining, value) = self.fields.$idx.parse_value(input)?;
+                        $f = Some(value);
+                        Ok(remaining)
+                    })+
+                    _ => unreachable!("no field #{idx}"),
+                })?;
+                Ok((remaining, ($(field_value(&self.fields.$idx, $f)?,)+)))
+            }
         }
     };
 }
-permutation_fn!(permutation2, A0 a0, A1 a1);
-impl<A0, A1, A2> Parser for Permutation<(A0, A1, A2)>
+impl_permutation!(F0 f0 0);
+impl_permutation!(F0 f0 0, F1 f1 1);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2, F3 f3 3);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2, F3 f3 3, F4 f4 4);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2, F3 f3 3, F4 f4 4, F5 f5 5);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2, F3 f3 3, F4 f4 4, F5 f5 5, F6 f6 6);
+impl_permutation!(F0 f0 0, F1 f1 1, F2 f2 2, F3 f3 3, F4 f4 4, F5 f5 5, F6 f6 6, F7 f7 7);
+/// Constructor of [Permutation] from a tuple of 1 to 8 [fields](Field)
+pub(crate) fn permutation<T>(fields: T) -> Permutation<T>
 where
-    A0: Parser,
-    A1: Parser,
-    A2: Parser,
+    Permutation<T>: Parser,
 {
-    type Dest = (A0::Dest, A1::Dest, A2::Dest);
+    Permutation { fields }
+}
+macro_rules! permutation_fn {
+    ($fn_name:ident, $($F:ident $f:ident),+) => {
+        pub(crate) fn $fn_name<$($F: Field),+>($($f: $F),+) -> Permutation<($($F),+)> {
+            permutation(($($f),+))
+        }
+    };
+}
+permutation_fn!(permutation2, F0 f0, F1 f1);
+permutation_fn!(permutation3, F0 f0, F1 f1, F2 f2);
+/// [Permutation] over a list of [fields](Field) of the same type whose
+/// length is only known at runtime
+#[derive(Debug, Clone)]
+pub struct PermutationList<F> {
+    fields: Vec<F>,
+}
+impl<F: Field> Parser for PermutationList<F> {
+    type Dest = Vec<F::Value>;
     fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-        match self.parsers.0.parse(input) {
-            Ok((remaining, a0)) => match self.parsers.1.parse(remaining) {
-                Ok((remaining, a1)) => self
-                    .parsers
-                    .2
-                    .parse(remaining)
-                    .map(|(remaining, a2)| (remaining, (a0, a1, a2))),
-                Err(_) => self.parsers.2.parse(remaining).and_then(|(remaining, a2)| {
-                    self.parsers
-                        .1
-                        .parse(remaining)
-                        .map(|(remaining, a1)| (remaining, (a0, a1, a2)))
-                }),
-            },
-            Err(_) => match self.parsers.1.parse(input) {
-                Ok((remaining, a1)) => match self.parsers.0.parse(remaining) {
-                    Ok((remaining, a0)) => self
-                        .parsers
-                        .2
-                        .parse(remaining)
-                        .map(|(remaining, a2)| (remaining, (a0, a1, a2))),
-                    Err(_) => self.parsers.2.parse(remaining).and_then(|(remaining, a2)| {
-                        self.parsers
-                            .0
-                            .parse(remaining)
-                            .map(|(remaining, a0)| (remaining, (a0, a1, a2)))
-                    }),
-                },
-                Err(_) => self.parsers.2.parse(input).and_then(|(remaining, a2)| {
-                    match self.parsers.0.parse(remaining) {
-                        Ok((remaining, a0)) => self
-                            .parsers
-                            .1
-                            .parse(remaining)
-                            .map(|(remaining, a1)| (remaining, (a0, a1, a2))),
-                        Err(_) => self.parsers.1.parse(remaining).and_then(|(remaining, a1)| {
-                            self.parsers
-                                .0
-                                .parse(remaining)
-                                .map(|(remaining, a0)| (remaining, (a0, a1, a2)))
-                        }),
-                    }
-                }),
-            },
-        }
-    }
-}
-permutation_fn!(permutation3, A0 a0, A1 a1, A2 a2);
-/// Комбинатор списка из любого числа элементов, которые надо читать
-/// вложенным парсером. Граница списка определяется квадратными (`[`&`]`)
-/// скобками.
-/// Для простоты реализации, после каждого элемента списка должна быть запятая
+        let keys: Vec<&str> = self.fields.iter().map(Field::key).collect();
+        let mut values: Vec<Option<F::Value>> = self.fields.iter().map(|_| None).collect();
+        let remaining = parse_fields(input, &keys, |idx, input| {
+            let (remaining, value) = self.fields[idx].parse_value(input)?;
+            values[idx] = Some(value);
+            Ok(remaining)
+        })?;
+        let values = self
+            .fields
+            .iter()
+            .zip(values)
+            .map(|(field, value)| field_value(field, value))
+            .collect::<Result<_, _>>()?;
+        Ok((remaining, values))
+    }
+}
+/// Constructor of [PermutationList]
+pub(crate) fn permutation_list<F: Field>(fields: Vec<F>) -> PermutationList<F> {
+    PermutationList { fields }
+}
+/// Combinator for a list of any number of elements read by the nested
+/// parser. The list is enclosed in square brackets (`[` & `]`), elements are
+/// separated by commas, and a comma after the last element is optional:
+/// `[1,2]` and `[1,2,]` read the same
 #[derive(Debug, Clone)]
 pub struct List<T> {
     parser: T,
@@ -469,24 +1084,14 @@ pub struct List<T> {
 impl<T: Parser> Parser for List<T> {
     type Dest = Vec<T::Dest>;
     fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-        let mut remaining = input.trim_start().strip_prefix('[').ok_or(ParseError::UnexpectedInput("expected '['"))?.trim_start();
-        let mut result = Vec::new();
-        while !remaining.is_empty() {
-            match remaining.strip_prefix(']') {
-                Some(remaining) => return Ok((remaining.trim_start(), result)),
-                None => {
-                    let (new_remaining, item) = self.parser.parse(remaining)?;
-                    let new_remaining = new_remaining
-                        .trim_start()
-                        .strip_prefix(',')
-                        .ok_or(ParseError::UnexpectedInput("expected ',' after list element"))?
-                        .trim_start();
-                    result.push(item);
-                    remaining = new_remaining;
-                }
-            }
+        let remaining = input.trim_start().strip_prefix('[').ok_or(ParseError::UnexpectedInput("expected '['"))?;
+        let (remaining, result) = strip_whitespace(&self.parser).separated_by(tag(",").strip_ws()).parse(remaining)?;
+        let (remaining, _) = tag(",").opt().strip_ws().parse(remaining)?;
+        match remaining.strip_prefix(']') {
+            Some(remaining) => Ok((remaining.trim_start(), result)),
+            None if remaining.is_empty() => Err(ParseError::IncompleteInput("unclosed list bracket")), // строка кончилась, не закрыв скобку
+            None => Err(ParseError::UnexpectedInput("expected ',' or ']' after list element")),
         }
-        Err(ParseError::IncompleteInput("unclosed list bracket")) // строка кончилась, не закрыв скобку
     }
 }
 /// Конструктор для [List]
@@ -518,7 +1123,7 @@ macro_rules! impl_alt {
         {
             type Dest = Dest;
             fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
-                $(if let Ok(ok) = self.parser.$idx.parse(input) { return Ok(ok); })+
+                $(if let Some(ok) = backtrack(|| self.parser.$idx.parse(input))? { return Ok(ok); })+
                 self.parser.$last_idx.parse(input)
             }
         }
@@ -528,32 +1133,338 @@ impl_alt!(alt2 [A0 a0 0] A1 a1 1);
 impl_alt!(alt3 [A0 a0 0, A1 a1 1] A2 a2 2);
 impl_alt!(alt4 [A0 a0 0, A1 a1 1, A2 a2 2] A3 a3 3);
 impl_alt!(@impl [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3] A4 a4 4);
-impl_alt!(@impl [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3, A4 a4 4] A5 a5 5);
-impl_alt!(@impl [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3, A4 a4 4, A5 a5 5] A6 a6 6);
+impl_alt!(alt6 [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3, A4 a4 4] A5 a5 5);
+impl_alt!(alt7 [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3, A4 a4 4, A5 a5 5] A6 a6 6);
 impl_alt!(alt8 [A0 a0 0, A1 a1 1, A2 a2 2, A3 a3 3, A4 a4 4, A5 a5 5, A6 a6 6] A7 a7 7);
 
-/// Комбинатор для применения дочернего парсера N раз
-/// (аналог `take` из `nom`)
-pub struct Take<T> {
-    count: usize,
+/// Combinator that does not fail when the child parser does not match:
+/// the result is then `None` and the input is left untouched
+/// (like `opt` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Opt<T> {
     parser: T,
 }
-impl<T: Parser> Parser for Take<T> {
+impl<T: Parser> Parser for Opt<T> {
+    type Dest = Option<T::Dest>;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        Ok(match backtrack(|| self.parser.parse(input))? {
+            Some((remaining, result)) => (remaining, Some(result)),
+            None => (input, None),
+        })
+    }
+}
+/// Constructor of [Opt]
+pub(crate) fn opt<T: Parser>(parser: T) -> Opt<T> {
+    Opt { parser }
+}
+/// Combinator that applies the child parser while it matches and requires
+/// at least `min` repetitions. A repetition that consumes no input ends the
+/// loop
+/// (like `many0`/`many1` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Many<T> {
+    parser: T,
+    min: usize,
+}
+impl<T: Parser> Parser for Many<T> {
     type Dest = Vec<T::Dest>;
     fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
         let mut remaining = input;
         let mut result = Vec::new();
-        for _ in 0..self.count {
-            let (new_remaining, new_result) = self.parser.parse(remaining)?;
-            result.push(new_result);
+        loop {
+            match rewind_warnings(|| self.parser.parse(remaining), Result::is_ok) {
+                Ok((new_remaining, _)) if new_remaining.len() == remaining.len() => break,
+                Ok((new_remaining, item)) => {
+                    result.push(item);
+                    remaining = new_remaining;
+                }
+                Err(err) if err.is_cut() || result.len() < self.min => return Err(err),
+                Err(_) => break,
+            }
+        }
+        if result.len() < self.min {
+            return Err(ParseError::UnexpectedInput("too few repetitions"));
+        }
+        Ok((remaining, result))
+    }
+}
+/// Constructor of [Many] with no required repetitions
+pub(crate) fn many0<T: Parser>(parser: T) -> Many<T> {
+    Many { parser, min: 0 }
+}
+/// Constructor of [Many] with at least one repetition
+pub(crate) fn many1<T: Parser>(parser: T) -> Many<T> {
+    Many { parser, min: 1 }
+}
+/// Combinator for elements separated by a separator, without brackets. A
+/// separator after the last element is not read and stays in the input
+/// (like `separated_list0` from `nom`)
+#[derive(Debug, Clone)]
+pub struct SeparatedList<T, S> {
+    parser: T,
+    separator: S,
+}
+impl<T: Parser, S: Parser> Parser for SeparatedList<T, S> {
+    type Dest = Vec<T::Dest>;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let Some((mut remaining, first)) = backtrack(|| self.parser.parse(input))? else {
+            return Ok((input, Vec::new()));
+        };
+        let mut result = vec![first];
+        let next = (&self.parser).preceded_by(&self.separator);
+        while let Some((new_remaining, item)) = backtrack(|| next.parse(remaining))? {
+            result.push(item);
             remaining = new_remaining;
         }
         Ok((remaining, result))
     }
 }
-/// Конструктор `Take`
-pub(crate) fn take<T: Parser>(count: usize, parser: T) -> Take<T> {
-    Take { count, parser }
+/// Constructor of [SeparatedList]
+pub(crate) fn separated_list<T: Parser, S: Parser>(parser: T, separator: S) -> SeparatedList<T, S> {
+    SeparatedList { parser, separator }
+}
+/// Combinator that returns the slice of input consumed by the child parser
+/// instead of its result
+/// (like `recognize` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Recognize<T> {
+    parser: T,
+}
+impl<T: Parser> Recognize<T> {
+    /// Like [Parser::parse], but borrows the consumed slice from `input`
+    pub fn parse_slice<'a>(&self, input: &'a str) -> Result<(&'a str, &'a str), ParseError> {
+        let (remaining, _) = self.parser.parse(input)?;
+        Ok((remaining, &input[..input.len() - remaining.len()]))
+    }
+}
+/// [Parser::Dest] cannot borrow from the input, so the slice is copied;
+/// use [Recognize::parse_slice] to avoid that
+impl<T: Parser> Parser for Recognize<T> {
+    type Dest = String;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        self.parse_slice(input)
+            .map(|(remaining, consumed)| (remaining, consumed.to_owned()))
+    }
+}
+/// Constructor of [Recognize]
+pub(crate) fn recognize<T: Parser>(parser: T) -> Recognize<T> {
+    Recognize { parser }
+}
+/// Combinator that parses without consuming input
+/// (like `peek` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Peek<T> {
+    parser: T,
+}
+impl<T: Parser> Parser for Peek<T> {
+    type Dest = T::Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        // nothing is consumed, so warnings are left to whatever parses it next
+        rewind_warnings(|| self.parser.parse(input), |_| false).map(|(_, result)| (input, result))
+    }
+}
+/// Constructor of [Peek]
+pub(crate) fn peek<T: Parser>(parser: T) -> Peek<T> {
+    Peek { parser }
+}
+/// Combinator that matches where the child parser does not, and consumes
+/// nothing
+/// (like `not` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Not<T> {
+    parser: T,
+}
+impl<T: Parser> Parser for Not<T> {
+    type Dest = ();
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        match backtrack(|| self.parser.parse(input))? {
+            Some(_) => Err(ParseError::UnexpectedInput("negated parser matched")),
+            None => Ok((input, ())),
+        }
+    }
+}
+/// Constructor of [Not]
+pub(crate) fn not<T: Parser>(parser: T) -> Not<T> {
+    Not { parser }
+}
+/// Combinator past which there is no backtracking: any error of the child
+/// parser becomes a [ParseError::Cut]
+/// (like `cut` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Cut<T> {
+    parser: T,
+}
+impl<T: Parser> Parser for Cut<T> {
+    type Dest = T::Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        self.parser.parse(input).map_err(|err| match err {
+            ParseError::Cut(_) => err,
+            err => ParseError::Cut(Box::new(err)),
+        })
+    }
+}
+/// Constructor of [Cut]
+pub(crate) fn cut<T: Parser>(parser: T) -> Cut<T> {
+    Cut { parser }
+}
+/// Combinator that rejects the child parser's result when `predicate` is
+/// false for it
+/// (like `verify` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Verify<T, F> {
+    parser: T,
+    predicate: F,
+}
+impl<T: Parser, F: Fn(&T::Dest) -> bool> Parser for Verify<T, F> {
+    type Dest = T::Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, result) = self.parser.parse(input)?;
+        if !(self.predicate)(&result) {
+            return Err(ParseError::InvalidValue("value rejected by verify"));
+        }
+        Ok((remaining, result))
+    }
+}
+/// Constructor of [Verify]
+pub(crate) fn verify<T: Parser, F: Fn(&T::Dest) -> bool>(parser: T, predicate: F) -> Verify<T, F> {
+    Verify { parser, predicate }
+}
+/// Combinator that replaces the child parser's result with a constant
+/// (like `value` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Constant<T, V> {
+    parser: T,
+    value: V,
+}
+impl<T: Parser, V: Clone> Parser for Constant<T, V> {
+    type Dest = V;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        self.parser
+            .parse(input)
+            .map(|(remaining, _)| (remaining, self.value.clone()))
+    }
+}
+/// Constructor of [Constant]
+pub(crate) fn value<T: Parser, V: Clone>(parser: T, value: V) -> Constant<T, V> {
+    Constant { parser, value }
+}
+/// Parser of the end of input: matches the empty string only
+/// (like `eof` from `nom`)
+#[derive(Debug, Clone)]
+pub struct Eof;
+impl Parser for Eof {
+    type Dest = ();
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        if !input.is_empty() {
+            return Err(ParseError::UnexpectedInput("expected end of input"));
+        }
+        Ok((input, ()))
+    }
+}
+/// Constructor of [Eof]
+pub(crate) fn eof() -> Eof {
+    Eof
+}
+/// Parser of the longest (possibly empty) prefix of characters for which
+/// `predicate` is true
+/// (like `take_while` from `nom`)
+#[derive(Debug, Clone)]
+pub struct TakeWhile<F> {
+    predicate: F,
+}
+impl<F: Fn(char) -> bool> Parser for TakeWhile<F> {
+    type Dest = String;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let end_idx = input.find(|c| !(self.predicate)(c)).unwrap_or(input.len());
+        Ok((&input[end_idx..], input[..end_idx].to_owned()))
+    }
+}
+/// Constructor of [TakeWhile]
+pub(crate) fn take_while<F: Fn(char) -> bool>(predicate: F) -> TakeWhile<F> {
+    TakeWhile { predicate }
+}
+
+/// How deep a [Recursive] parser may nest before it fails with
+/// [ParseError::TooDeep], so that untrusted input cannot overflow the stack
+pub const MAX_DEPTH: usize = 128;
+/// Grammar of a [Recursive] parser and how deep it is nested right now
+struct RecursiveGrammar<Dest> {
+    parser: BoxedParser<Dest>,
+    depth: Cell<usize>,
+}
+impl<Dest> RecursiveGrammar<Dest> {
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Dest), ParseError> {
+        let depth = self.depth.get();
+        if depth >= MAX_DEPTH {
+            // cut, so that alternatives at every level do not retry
+            return Err(ParseError::Cut(Box::new(ParseError::TooDeep(MAX_DEPTH))));
+        }
+        self.depth.set(depth + 1);
+        let result = self.parser.parse(input);
+        self.depth.set(depth);
+        result
+    }
+}
+/// Recursive parser, the fixed point of the grammar passed to [recursive]:
+/// the grammar gets a [reference](RecursiveRef) to itself, which is only
+/// resolved at parse time. Nesting is limited to [MAX_DEPTH] levels
+#[derive(Clone)]
+pub struct Recursive<Dest> {
+    grammar: Rc<RecursiveGrammar<Dest>>,
+}
+impl<Dest> Parser for Recursive<Dest> {
+    type Dest = Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        self.grammar.parse(input)
+    }
+}
+impl<Dest> std::fmt::Debug for Recursive<Dest> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Recursive").finish_non_exhaustive()
+    }
+}
+/// The [Recursive] parser as seen from inside its own grammar. It holds a
+/// weak reference, so the grammar does not keep itself alive
+pub struct RecursiveRef<Dest> {
+    grammar: Weak<RecursiveGrammar<Dest>>,
+}
+impl<Dest> Clone for RecursiveRef<Dest> {
+    fn clone(&self) -> Self {
+        RecursiveRef {
+            grammar: self.grammar.clone(),
+        }
+    }
+}
+impl<Dest> Parser for RecursiveRef<Dest> {
+    type Dest = Dest;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let grammar = self
+            .grammar
+            .upgrade()
+            .ok_or(ParseError::InvalidValue("recursive parser was dropped"))?;
+        grammar.parse(input)
+    }
+}
+impl<Dest> std::fmt::Debug for RecursiveRef<Dest> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecursiveRef").finish_non_exhaustive()
+    }
+}
+/// Constructor of [Recursive]: `grammar` gets a reference to the parser
+/// being built, e.g. `recursive(|nested| alt2(list(nested).map(...), ...))`
+pub(crate) fn recursive<Dest, P, F>(grammar: F) -> Recursive<Dest>
+where
+    P: Parser<Dest = Dest> + 'static,
+    F: FnOnce(RecursiveRef<Dest>) -> P,
+{
+    Recursive {
+        grammar: Rc::new_cyclic(|weak| RecursiveGrammar {
+            parser: Box::new(grammar(RecursiveRef {
+                grammar: weak.clone(),
+            })),
+            depth: Cell::new(0),
+        }),
+    }
 }
 
 #[cfg(test)]
@@ -589,6 +1500,62 @@ mod tests {
         assert!(primitives::U32.parse("0x").is_err());
     }
 
+    #[test]
+    fn test_decimal() {
+        let cents = primitives::Decimal { decimals: 2 };
+        assert_eq!(cents.parse("1000,"), Ok((",", 100_000)));
+        assert_eq!(cents.parse("1000.5 usd"), Ok((" usd", 100_050)));
+        assert_eq!(cents.parse("0.01"), Ok(("", 1)));
+        assert_eq!(cents.parse("0.00"), Err(ParseError::InvalidValue("zero is not allowed")));
+        assert_eq!(cents.parse("1.234"), Err(ParseError::InvalidValue("too many decimal places")));
+        assert_eq!(cents.parse("1."), Err(ParseError::IncompleteInput("expected fraction digits")));
+        assert_eq!(cents.parse(".5"), Err(ParseError::UnexpectedInput("expected decimal digits")));
+        assert_eq!(
+            cents.parse("184467440737095517"),
+            Err(ParseError::Overflow("decimal out of range"))
+        );
+        assert_eq!(primitives::Decimal { decimals: 0 }.parse("7.0"), Ok(("", 7)));
+        assert_eq!(primitives::Decimal { decimals: 0 }.parse("7.5"), Err(ParseError::InvalidValue("too many decimal places")));
+        assert_eq!(cents.parse("1_000.5e1"), Ok(("", 1_000_500)));
+    }
+
+    #[test]
+    fn test_u64() {
+        assert_eq!(primitives::U64.parse("0,"), Ok((",", 0)));
+        assert_eq!(primitives::U64.parse("1_000_000 "), Ok((" ", 1_000_000)));
+        assert_eq!(primitives::U64.parse("15e2"), Ok(("", 1500)));
+        assert_eq!(primitives::U64.parse("1.5E3"), Ok(("", 1500)));
+        assert_eq!(primitives::U64.parse("5eur"), Ok(("eur", 5)));
+        assert_eq!(primitives::U64.parse("0xff_ff"), Ok(("", 0xffff)));
+        assert_eq!(primitives::U64.parse("18446744073709551615"), Ok(("", u64::MAX)));
+        assert_eq!(primitives::U64.parse("18446744073709551616"), Err(ParseError::Overflow("u64 overflow")));
+        assert_eq!(primitives::U64.parse("2e19"), Err(ParseError::Overflow("u64 overflow")));
+        assert_eq!(primitives::U64.parse("1e99999"), Err(ParseError::Overflow("exponent out of range")));
+        assert_eq!(primitives::U64.parse("1.5"), Err(ParseError::InvalidValue("not an integer")));
+        assert_eq!(primitives::U64.parse("-1"), Err(ParseError::InvalidValue("negative value")));
+        assert_eq!(primitives::U64.parse("1__000"), Err(ParseError::UnexpectedInput("misplaced digit separator")));
+        assert_eq!(primitives::U64.parse("1_"), Err(ParseError::UnexpectedInput("misplaced digit separator")));
+        assert_eq!(primitives::U64.parse("_1"), Err(ParseError::UnexpectedInput("misplaced digit separator")));
+        assert_eq!(primitives::U32.parse("4294967296"), Err(ParseError::Overflow("u32 overflow")));
+    }
+
+    #[test]
+    fn test_i64_f64() {
+        assert_eq!(primitives::I64.parse("-42,"), Ok((",", -42)));
+        assert_eq!(primitives::I64.parse("+7"), Ok(("", 7)));
+        assert_eq!(primitives::I64.parse("-9_223_372_036_854_775_808"), Ok(("", i64::MIN)));
+        assert_eq!(primitives::I64.parse("9223372036854775808"), Err(ParseError::Overflow("i64 overflow")));
+        assert_eq!(primitives::I64.parse("-2.5e1"), Ok(("", -25)));
+        assert_eq!(primitives::I64.parse("-2.5"), Err(ParseError::InvalidValue("not an integer")));
+
+        assert_eq!(primitives::F64.parse("-1.5,"), Ok((",", -1.5)));
+        assert_eq!(primitives::F64.parse("2.5e-3"), Ok(("", 0.0025)));
+        assert_eq!(primitives::F64.parse("1_000.25"), Ok(("", 1000.25)));
+        assert_eq!(primitives::F64.parse("1e400"), Err(ParseError::Overflow("f64 overflow")));
+        assert!(primitives::F64.parse("inf").is_err());
+        assert!(primitives::F64.parse("NaN").is_err());
+    }
+
     #[test]
     fn test_i32() {
         assert_eq!(primitives::I32.parse("411"), Ok(("", 411)));
@@ -599,20 +1566,6 @@ mod tests {
         assert!(primitives::I32.parse("-").is_err());
     }
 
-    fn quote(input: &str) -> String {
-        let mut result = String::from("\"");
-        result.extend(
-            input
-                .chars()
-                .flat_map(|c| match c {
-                    '\\' | '"' => ['\\', c].into_iter().take(2),
-                    _ => [c, ' '].into_iter().take(1),
-                }),
-        );
-        result.push('"');
-        result
-    }
-
     #[test]
     fn test_quote() {
         assert_eq!(quote(r#"411"#), r#""411""#.to_string());
@@ -712,6 +1665,110 @@ mod tests {
         );
         assert!(list(primitives::U32).parse("1,2,3,4,").is_err());
         assert_eq!(list(primitives::U32).parse("[]"), Ok(("", vec![])));
+        assert_eq!(
+            list(primitives::U32).parse("[1,2] nice"),
+            Ok(("nice", vec![nz(1), nz(2)]))
+        );
+        assert_eq!(list(primitives::U32).parse("[ 1 ]"), Ok(("", vec![nz(1)])));
+        assert_eq!(
+            list(primitives::U32).parse("[1 2]"),
+            Err(ParseError::UnexpectedInput("expected ',' or ']' after list element"))
+        );
+        assert_eq!(
+            list(primitives::U32).parse("[1,2"),
+            Err(ParseError::IncompleteInput("unclosed list bracket"))
+        );
+        assert!(list(primitives::U32).parse("[1,,]").is_err());
+    }
+
+    #[test]
+    fn test_opt_many() {
+        assert_eq!(tag("a").opt().parse("ab"), Ok(("b", Some(()))));
+        assert_eq!(tag("a").opt().parse("b"), Ok(("b", None)));
+        assert_eq!(tag("ab").many0().parse("ababa"), Ok(("a", vec![(), ()])));
+        assert_eq!(tag("ab").many0().parse("x"), Ok(("x", vec![])));
+        assert_eq!(tag("ab").many1().parse("abx"), Ok(("x", vec![()])));
+        assert_eq!(
+            tag("ab").many1().parse("x"),
+            Err(ParseError::UnexpectedInput("tag mismatch"))
+        );
+        // A repetition that consumes nothing ends the loop instead of spinning
+        assert_eq!(tag("a").opt().many0().parse("aab"), Ok(("b", vec![Some(()), Some(())])));
+        assert_eq!(tag("").many1().parse("x"), Err(ParseError::UnexpectedInput("too few repetitions")));
+    }
+
+    #[test]
+    fn test_separated_list() {
+        let parser = primitives::U32.separated_by(tag(","));
+        assert_eq!(parser.parse("1,2,3"), Ok(("", vec![nz(1), nz(2), nz(3)])));
+        // the trailing separator is left to the caller
+        assert_eq!(parser.parse("1,2,"), Ok((",", vec![nz(1), nz(2)])));
+        assert_eq!(parser.parse("1,x"), Ok((",x", vec![nz(1)])));
+        assert_eq!(parser.parse("x"), Ok(("x", vec![])));
+        assert_eq!(
+            separated_list(unquote(), tag(";").strip_ws()).parse(r#""a" ; "b""#),
+            Ok(("", vec!["a".to_string(), "b".to_string()]))
+        );
+    }
+
+    #[test]
+    fn test_recognize_peek_not() {
+        let digits = take_while(|c| c.is_ascii_digit());
+        assert_eq!(digits.parse("12ab"), Ok(("ab", "12".to_string())));
+        assert_eq!(digits.parse("ab"), Ok(("ab", String::new())));
+
+        let number = primitives::U32.preceded_by(tag("-").opt()).recognize();
+        assert_eq!(number.parse("-1_2"), Ok(("_2", "-1".to_string())));
+        assert_eq!(number.parse_slice("0x1f rest"), Ok((" rest", "0x1f")));
+        assert!(number.parse("x").is_err());
+
+        assert_eq!(tag("ab").peek().parse("abc"), Ok(("abc", ())));
+        assert!(tag("ab").peek().parse("xbc").is_err());
+        assert_eq!(tag("ab").not().parse("xbc"), Ok(("xbc", ())));
+        assert_eq!(
+            tag("ab").not().parse("abc"),
+            Err(ParseError::UnexpectedInput("negated parser matched"))
+        );
+        assert_eq!(eof().parse(""), Ok(("", ())));
+        assert_eq!(eof().parse(" "), Err(ParseError::UnexpectedInput("expected end of input")));
+    }
+
+    #[test]
+    fn test_verify_value() {
+        let even = primitives::U64.verify(|n| n % 2 == 0);
+        assert_eq!(even.parse("42"), Ok(("", 42)));
+        assert_eq!(even.parse("7"), Err(ParseError::InvalidValue("value rejected by verify")));
+
+        let switch = alt2(tag("on").value(true), tag("off").value(false));
+        assert_eq!(switch.parse("off!"), Ok(("!", false)));
+        assert_eq!(switch.parse("on"), Ok(("", true)));
+    }
+
+    #[test]
+    fn test_cut() {
+        // without a cut, alt falls through to the last branch and reports its error
+        let loose = alt2(unquote().preceded_by(tag("msg=")), primitives::U64.map(|n| n.to_string()));
+        assert_eq!(
+            loose.parse("msg=oops"),
+            Err(ParseError::UnexpectedInput("expected decimal digits"))
+        );
+        // after `msg=` the branch is settled, the quote error is what the caller sees
+        let strict = alt2(unquote().cut().preceded_by(tag("msg=")), primitives::U64.map(|n| n.to_string()));
+        let err = strict.parse("msg=oops").unwrap_err();
+        assert_eq!(err, ParseError::Cut(Box::new(ParseError::UnexpectedInput("expected opening quote"))));
+        assert!(err.is_cut());
+        assert_eq!(err.to_string(), "unexpected input: expected opening quote");
+        assert_eq!(strict.parse("12"), Ok(("", "12".to_string())));
+        // nested cuts are not wrapped twice
+        assert_eq!(tag("a").cut().cut().parse("b"), Err(ParseError::Cut(Box::new(ParseError::UnexpectedInput("tag mismatch")))));
+
+        // opt, many and separated lists stop backtracking too; permutations never backtrack
+        let item = tag("]").cut().preceded_by(tag("["));
+        assert!((&item).opt().parse("[x").unwrap_err().is_cut());
+        assert!((&item).many0().parse("[][x").unwrap_err().is_cut());
+        assert!((&item).separated_by(tag(",")).parse("[],[x").unwrap_err().is_cut());
+        assert!((&item).not().parse("[x").unwrap_err().is_cut());
+        assert!(permutation_list(vec![key_value("a", &item)]).parse(r#""a":[x"#).unwrap_err().is_cut());
     }
 
     #[test]
@@ -765,7 +1822,137 @@ mod tests {
             key_value("c", primitives::U32),
         );
         // Only two of three fields provided
-        assert!(parser.parse(r#""a":1,"b":2,"#).is_err());
+        assert_eq!(
+            parser.parse(r#""a":1,"b":2,"#),
+            Err(ParseError::MissingKey("c".into()))
+        );
+    }
+
+    #[test]
+    fn test_permutation_keys() {
+        let parser = permutation((
+            key_value("a", primitives::U32),
+            key_value("b", unquote()),
+            key_value("c", primitives::U64).with_default(7),
+            key_value("d", primitives::U64),
+            key_value("e", list(primitives::U32)).with_default(vec![]),
+        ));
+        assert_eq!(
+            parser.parse(r#""e":[1], "d":4, "b":"x", "c":3, "a":1, }"#),
+            Ok(("}", (nz(1), "x".to_string(), 3, 4, vec![nz(1)])))
+        );
+        // optional keys take their defaults
+        assert_eq!(
+            parser.parse(r#""d":4,"a":1,"b":"x","#),
+            Ok(("", (nz(1), "x".to_string(), 7, 4, vec![])))
+        );
+        // unknown keys are skipped with a warning, whatever their value
+        let (result, warnings) = collect_warnings(|| {
+            parser.parse(r#""a":1,"zz":"q\",}", "b":"x", "nested":{"k":[1,2],"s":"]"}, "d":4, "n":-1.5e3,"#)
+        });
+        assert_eq!(result, Ok(("", (nz(1), "x".to_string(), 7, 4, vec![]))));
+        assert_eq!(
+            warnings,
+            [
+                r#"unknown key "zz" skipped"#,
+                r#"unknown key "nested" skipped"#,
+                r#"unknown key "n" skipped"#,
+            ]
+        );
+        // outside of collect_warnings they are dropped
+        assert_eq!(collect_warnings(|| ()).1, Vec::<String>::new());
+        assert_eq!(
+            parser.parse(r#""a":1,"b":"x","d":4,"a":2,"#),
+            Err(ParseError::DuplicateKey("a".into()))
+        );
+        assert_eq!(
+            parser.parse(r#""a":1,"c":3,"#),
+            Err(ParseError::MissingKey("b".into()))
+        );
+        assert_eq!(
+            parser.parse(r#""a":1,"b":"x","d":4,"zz":[1,"#),
+            Err(ParseError::IncompleteInput("unterminated value of unknown key"))
+        );
+        assert_eq!(
+            ParseError::MissingKey("b".into()).to_string(),
+            "missing key: b"
+        );
+    }
+
+    #[test]
+    fn test_warnings_of_backtracked_branches() {
+        let fields = || permutation((key_value("a", primitives::U32),));
+        // the first branch skips "zz", then fails on the missing `}`
+        let parser = alt2(
+            delimited(tag("{"), fields(), tag("}")).map(|(a,)| a),
+            delimited(tag("{"), fields(), tag(";")).map(|(a,)| a),
+        );
+        let (result, warnings) = collect_warnings(|| parser.parse(r#"{"zz":0,"a":1,;"#));
+        assert_eq!(result, Ok(("", nz(1))));
+        assert_eq!(warnings, [r#"unknown key "zz" skipped"#]);
+        // a failed optional branch and a lookahead leave none either
+        let (result, warnings) =
+            collect_warnings(|| delimited(tag("{"), fields(), tag("}")).opt().parse(r#"{"zz":0,"a":1,"#));
+        assert_eq!(result, Ok((r#"{"zz":0,"a":1,"#, None)));
+        assert!(warnings.is_empty());
+        let (_, warnings) = collect_warnings(|| peek(fields()).parse(r#""zz":0,"a":1,"#));
+        assert!(warnings.is_empty());
+        // the outer sink is restored when the inner parse panics
+        let (_, warnings) = collect_warnings(|| {
+            let inner = std::panic::catch_unwind(|| collect_warnings(|| panic!("parser bug")));
+            assert!(inner.is_err());
+            fields().parse(r#""zz":0,"a":1,"#)
+        });
+        assert_eq!(warnings, [r#"unknown key "zz" skipped"#]);
+    }
+
+    #[test]
+    fn test_permutation_list() {
+        let keys = ["a", "b", "c"].map(String::from);
+        let parser = permutation_list(keys.into_iter().map(|key| key_value(key, primitives::U32)).collect());
+        assert_eq!(
+            parser.parse(r#""c":3,"a":1,"b":2, rest"#),
+            Ok(("rest", vec![nz(1), nz(2), nz(3)]))
+        );
+        assert_eq!(
+            parser.parse(r#""a":1,"a":1,"b":2,"#),
+            Err(ParseError::DuplicateKey("a".into()))
+        );
+        assert_eq!(permutation_list(Vec::<KeyValue<Tag>>::new()).parse("x"), Ok(("x", vec![])));
+    }
+
+    #[test]
+    fn test_recursive() {
+        // nesting depth of `(((x)))`
+        let depth = recursive(|nested| {
+            alt2(
+                delimited(tag("("), nested, tag(")")).map(|depth: u32| depth + 1),
+                tag("x").value(0),
+            )
+        });
+        assert_eq!(depth.parse("x"), Ok(("", 0)));
+        assert_eq!(depth.parse("(((x))) rest"), Ok((" rest", 3)));
+        assert!(depth.parse("((x)").is_err());
+        assert_eq!(depth.clone().boxed().parse("(x)"), Ok(("", 1)));
+
+        // nesting is limited, and the depth is reset after every parse
+        let nested = |n: usize| format!("{}x{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(depth.parse(&nested(MAX_DEPTH - 1)), Ok(("", MAX_DEPTH as u32 - 1)));
+        let too_deep = Err(ParseError::Cut(Box::new(ParseError::TooDeep(MAX_DEPTH))));
+        assert_eq!(depth.parse(&nested(MAX_DEPTH)), too_deep);
+        assert_eq!(depth.parse(&nested(10_000)), too_deep);
+        assert_eq!(depth.parse("((x))"), Ok(("", 2)));
+
+        // the grammar does not keep itself alive
+        let mut escaped = None;
+        drop(recursive(|nested: RecursiveRef<u32>| {
+            escaped = Some(nested.clone());
+            nested
+        }));
+        assert_eq!(
+            escaped.unwrap().parse("x"),
+            Err(ParseError::InvalidValue("recursive parser was dropped"))
+        );
     }
 
     #[test]
@@ -794,7 +1981,62 @@ mod tests {
         );
     }
 
+    /// `1234567` -> `1_234_567`
+    fn group_digits(digits: &str) -> String {
+        let mut out = String::new();
+        for (idx, c) in digits.chars().enumerate() {
+            if idx > 0 && (digits.len() - idx).is_multiple_of(3) {
+                out.push('_');
+            }
+            out.push(c);
+        }
+        out
+    }
+
     proptest! {
+        #[test]
+        fn test_u64_roundtrip(n: u64) {
+            let (plain, hex) = (n.to_string(), format!("{n:#x}"));
+            let grouped = group_digits(&plain);
+            prop_assert_eq!(primitives::U64.parse(&plain), Ok(("", n)));
+            prop_assert_eq!(primitives::U64.parse(&grouped), Ok(("", n)));
+            prop_assert_eq!(primitives::U64.parse(&hex), Ok(("", n)));
+        }
+
+        #[test]
+        fn test_u64_exponent(mantissa: u32, exp in 0u32..30) {
+            let expected = 10u64.checked_pow(exp).and_then(|scale| u64::from(mantissa).checked_mul(scale));
+            let text = format!("{mantissa}e{exp}");
+            let parsed = primitives::U64.parse(&text);
+            match expected {
+                Some(n) => prop_assert_eq!(parsed, Ok(("", n))),
+                None => prop_assert_eq!(parsed, Err(ParseError::Overflow("u64 overflow"))),
+            }
+        }
+
+        #[test]
+        fn test_i64_roundtrip(n: i64) {
+            let text = n.to_string();
+            prop_assert_eq!(primitives::I64.parse(&text), Ok(("", n)));
+        }
+
+        #[test]
+        fn test_f64_roundtrip(x in any::<f64>().prop_filter("finite", |x| x.is_finite())) {
+            let (plain, exponent) = (x.to_string(), format!("{x:e}"));
+            prop_assert_eq!(primitives::F64.parse(&plain), Ok(("", x)));
+            prop_assert_eq!(primitives::F64.parse(&exponent), Ok(("", x)));
+        }
+
+        #[test]
+        fn test_decimal_roundtrip(n in 1u64.., decimals in 0u32..6) {
+            let scale = 10u64.pow(decimals);
+            let text = match decimals {
+                0 => n.to_string(),
+                _ => format!("{}.{:0width$}", n / scale, n % scale, width = decimals as usize),
+            };
+            prop_assert_eq!(primitives::Decimal { decimals }.parse(&text), Ok(("", n)));
+        }
+
         #[test]
         fn test_quote_unquote_roundtrip(s in ".*") {
             let quoted = quote(&s);
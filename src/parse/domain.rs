@@ -3,29 +3,50 @@ use std::fmt;
 use super::combinators::primitives;
 use super::combinators::*;
 
-const AUTHDATA_SIZE: usize = 1024;
+/// Upper bound on decoded auth data; the exchange currently sends 1024 bytes.
+pub const MAX_AUTHDATA_SIZE: usize = 4096;
 
 /// Данные для авторизации
+///
+/// Written as hex digits or as `base64:<data>`; any non-empty length up to
+/// [MAX_AUTHDATA_SIZE] is accepted.
 #[derive(Debug, Clone, PartialEq)]
-pub struct AuthData(pub(crate) [u8; AUTHDATA_SIZE]);
+pub struct AuthData(pub(crate) Vec<u8>);
+impl AuthData {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
 impl Parsable for AuthData {
-    type Parser = Map<Take<primitives::Byte>, fn(Vec<u8>) -> Self>;
+    type Parser = Map<primitives::Blob, fn(Vec<u8>) -> Self>;
     fn parser() -> Self::Parser {
-        take(AUTHDATA_SIZE, primitives::Byte)
-            .map(|authdata| AuthData(authdata.try_into().unwrap_or([0; AUTHDATA_SIZE])))
+        primitives::Blob {
+            max_len: MAX_AUTHDATA_SIZE,
+        }
+        .map(AuthData)
     }
 }
 impl fmt::Display for AuthData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for byte in &self.0[..16] {
+        for byte in self.0.iter().take(16) {
             write!(f, "{byte:02x}")?;
         }
-        write!(f, "...({} bytes)", AUTHDATA_SIZE)
+        if self.0.len() > 16 {
+            write!(f, "...")?;
+        }
+        write!(f, "({} bytes)", self.0.len())
+    }
+}
+impl Emit for AuthData {
+    fn emit(&self, out: &mut String) {
+        for byte in &self.0 {
+            out.push_str(&format!("{byte:02x}"));
+        }
     }
 }
 
 /// Newtype wrapper around String for type-safe user identification.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct UserId(pub String);
 impl Parsable for UserId {
     type Parser = Map<Unquote, fn(String) -> Self>;
@@ -38,9 +59,14 @@ impl fmt::Display for UserId {
         write!(f, "{}", self.0)
     }
 }
+impl Emit for UserId {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&quote(&self.0));
+    }
+}
 
 /// Newtype wrapper around String for type-safe asset identification.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct AssetId(pub String);
 impl Parsable for AssetId {
     type Parser = Map<Unquote, fn(String) -> Self>;
@@ -53,6 +79,11 @@ impl fmt::Display for AssetId {
         write!(f, "{}", self.0)
     }
 }
+impl Emit for AssetId {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&quote(&self.0));
+    }
+}
 
 /// Пара 'сокращённое название предмета' - 'его описание'
 #[derive(Debug, Clone, PartialEq)]
@@ -85,6 +116,14 @@ impl fmt::Display for AssetDsc {
         write!(f, "{} ({})", self.id, self.dsc)
     }
 }
+impl Emit for AssetDsc {
+    fn emit(&self, out: &mut String) {
+        out.push_str("AssetDsc{");
+        emit_key_value(out, "id", &self.id);
+        emit_key_value(out, "dsc", &self.dsc);
+        out.push('}');
+    }
+}
 /// Сведение о предмете в некотором количестве
 #[derive(Debug, Clone, PartialEq)]
 pub struct Backet {
@@ -117,27 +156,173 @@ impl fmt::Display for Backet {
         write!(f, "{}x{}", self.count, self.asset_id)
     }
 }
+impl Emit for Backet {
+    fn emit(&self, out: &mut String) {
+        out.push_str("Backet{");
+        emit_key_value(out, "asset_id", &self.asset_id);
+        emit_key_value(out, "count", &self.count);
+        out.push('}');
+    }
+}
+/// Three-letter currency code, stored lowercase, or [Currency::UNSPECIFIED].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+impl Currency {
+    /// Currency of amounts written without a code; no code maps to it.
+    pub const UNSPECIFIED: Currency = Currency([0; 3]);
+    pub const USD: Currency = Currency(*b"usd");
+    /// Three ASCII letters in any case; `None` otherwise.
+    pub fn new(code: &str) -> Option<Self> {
+        let code: [u8; 3] = code.as_bytes().try_into().ok()?;
+        code.iter()
+            .all(u8::is_ascii_alphabetic)
+            .then(|| Currency(code.map(|b| b.to_ascii_lowercase())))
+    }
+    /// The lowercase code, or `unspecified`.
+    pub fn as_str(&self) -> &str {
+        if *self == Currency::UNSPECIFIED {
+            return "unspecified";
+        }
+        std::str::from_utf8(&self.0).expect("currency codes are ASCII letters")
+    }
+}
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+/// Failure of checked [Money] arithmetic.
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+pub enum MoneyError {
+    #[error("currency mismatch: {0} and {1}")]
+    CurrencyMismatch(Currency, Currency),
+    #[error("amount out of range")]
+    Overflow,
+}
+/// Amount of money: a fixed-point decimal with [Money::DECIMALS] places and
+/// a currency
+///
+/// Written as `1000`, `1000.50` or `1000.50 usd`; the code is
+/// case-insensitive, and an amount without one is in
+/// [Currency::UNSPECIFIED], which no other currency adds up with. Amounts in
+/// the log are positive, results of arithmetic may be negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64,
+    currency: Currency,
+}
+impl Money {
+    pub const DECIMALS: u32 = 2;
+    const SCALE: i64 = 10i64.pow(Self::DECIMALS);
+
+    /// Amount in `10^-DECIMALS` units, e.g. cents.
+    pub fn from_minor(minor: i64, currency: Currency) -> Self {
+        Money { minor, currency }
+    }
+    pub fn zero(currency: Currency) -> Self {
+        Money::from_minor(0, currency)
+    }
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+    /// Approximate value in whole units, for metrics and exports.
+    pub fn to_f64(&self) -> f64 {
+        self.minor as f64 / Self::SCALE as f64
+    }
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        let minor = self.minor.checked_add(other.minor).ok_or(MoneyError::Overflow)?;
+        Ok(Money::from_minor(minor, self.currency))
+    }
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        let negated = other.minor.checked_neg().ok_or(MoneyError::Overflow)?;
+        self.checked_add(Money::from_minor(negated, other.currency))
+    }
+    /// The number without currency: `1000` or `1000.50`.
+    pub fn amount(&self) -> String {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let scale = Self::SCALE.unsigned_abs();
+        let (int, frac) = (self.minor.unsigned_abs() / scale, self.minor.unsigned_abs() % scale);
+        match frac {
+            0 => format!("{sign}{int}"),
+            _ => format!("{sign}{int}.{frac:0width$}", width = Self::DECIMALS as usize),
+        }
+    }
+}
+/// Parser of [Money]: a [primitives::Decimal], then an optional currency code
+/// after whitespace
+#[derive(Debug, Clone)]
+pub struct MoneyParser;
+impl Parser for MoneyParser {
+    type Dest = Money;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, minor) = primitives::Decimal {
+            decimals: Money::DECIMALS,
+        }
+        .parse(input)?;
+        let minor = i64::try_from(minor).map_err(|_| ParseError::Overflow("amount out of range"))?;
+        let code = remaining.trim_start();
+        let code_end = code
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(code.len());
+        match Currency::new(&code[..code_end]) {
+            Some(currency) if code.len() < remaining.len() => {
+                Ok((&code[code_end..], Money::from_minor(minor, currency)))
+            }
+            _ => Ok((remaining, Money::from_minor(minor, Currency::UNSPECIFIED))),
+        }
+    }
+}
+impl Parsable for Money {
+    type Parser = MoneyParser;
+    fn parser() -> Self::Parser {
+        MoneyParser
+    }
+}
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.currency {
+            Currency::UNSPECIFIED => f.write_str(&self.amount()),
+            currency => write!(f, "{} {currency}", self.amount()),
+        }
+    }
+}
+/// The code is left out for [Currency::UNSPECIFIED] only
+impl Emit for Money {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&self.amount());
+        if self.currency != Currency::UNSPECIFIED {
+            out.push(' ');
+            out.push_str(self.currency.as_str());
+        }
+    }
+}
 /// Фиатные деньги конкретного пользователя
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserCash {
     pub user_id: UserId,
-    pub count: std::num::NonZeroU32,
+    pub count: Money,
 }
 impl Parsable for UserCash {
     type Parser = Map<
         Delimited<
             Tuple<(StripWhitespace<Tag>, StripWhitespace<Tag>)>,
-            Permutation<(KeyValue<<UserId as Parsable>::Parser>, KeyValue<primitives::U32>)>,
+            Permutation<(KeyValue<<UserId as Parsable>::Parser>, KeyValue<<Money as Parsable>::Parser>)>,
             StripWhitespace<Tag>,
         >,
-        fn((UserId, std::num::NonZeroU32)) -> Self,
+        fn((UserId, Money)) -> Self,
     >;
     fn parser() -> Self::Parser {
         delimited(
             tuple2(tag("UserCash").strip_ws(), tag("{").strip_ws()),
             permutation2(
                 key_value("user_id", UserId::parser()),
-                key_value("count", primitives::U32),
+                key_value("count", Money::parser()),
             ),
             tag("}").strip_ws(),
         )
@@ -149,6 +334,14 @@ impl fmt::Display for UserCash {
         write!(f, "user={}, amount={}", self.user_id, self.count)
     }
 }
+impl Emit for UserCash {
+    fn emit(&self, out: &mut String) {
+        out.push_str("UserCash{");
+        emit_key_value(out, "user_id", &self.user_id);
+        emit_key_value(out, "count", &self.count);
+        out.push('}');
+    }
+}
 /// [Backet] конкретного пользователя
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserBacket {
@@ -181,6 +374,14 @@ impl fmt::Display for UserBacket {
         write!(f, "user={}, backet={}", self.user_id, self.backet)
     }
 }
+impl Emit for UserBacket {
+    fn emit(&self, out: &mut String) {
+        out.push_str("UserBacket{");
+        emit_key_value(out, "user_id", &self.user_id);
+        emit_key_value(out, "backet", &self.backet);
+        out.push('}');
+    }
+}
 /// [Бакеты](Backet) конкретного пользователя
 #[derive(Debug, Clone, PartialEq)]
 pub struct UserBackets {
@@ -223,9 +424,23 @@ impl fmt::Display for UserBackets {
         write!(f, "]")
     }
 }
+impl Emit for UserBackets {
+    fn emit(&self, out: &mut String) {
+        out.push_str("UserBackets{");
+        emit_key_value(out, "user_id", &self.user_id);
+        emit_key_value(out, "backets", &self.backets);
+        out.push('}');
+    }
+}
 /// Список опубликованных бакетов
 #[derive(Debug, Clone, PartialEq)]
-pub struct Announcements(Vec<UserBackets>);
+pub struct Announcements(pub(crate) Vec<UserBackets>);
+impl Announcements {
+    /// Published offers, in the order they appear in the log.
+    pub fn user_backets(&self) -> &[UserBackets] {
+        &self.0
+    }
+}
 impl Parsable for Announcements {
     type Parser = Map<List<<UserBackets as Parsable>::Parser>, fn(Vec<UserBackets>) -> Self>;
     fn parser() -> Self::Parser {
@@ -244,6 +459,11 @@ impl fmt::Display for Announcements {
         write!(f, "]")
     }
 }
+impl Emit for Announcements {
+    fn emit(&self, out: &mut String) {
+        self.0.emit(out);
+    }
+}
 
 /// Generic wrapper for parsing any [Parsable] type.
 pub fn just_parse<T: Parsable>(input: &str) -> Result<(&str, T), ParseError> {
@@ -259,6 +479,10 @@ mod tests {
         NonZeroU32::new(n).unwrap()
     }
 
+    fn bare(n: i64) -> Money {
+        Money::from_minor(n * 100, Currency::UNSPECIFIED)
+    }
+
     #[test]
     fn test_authdata() {
         let s = "30c305825b900077ae7f8259c1c328aa3e124a07f3bfbbf216dfc6e308beea6e474b9a7ea6c24d003a6ae4fcf04a9e6ef7c7f17cdaa0296f66a88036badcf01f053da806fad356546349deceff24621b895440d05a715b221af8e9e068073d6dec04f148175717d3c2d1b6af84e2375718ab4a1eba7e037c1c1d43b4cf422d6f2aa9194266f0a7544eaeff8167f0e993d0ea6a8ddb98bfeb8805635d5ea9f6592fd5297e6f83b6834190f99449722cd0de87a4c122f08bbe836fd3092e5f0d37a3057e90f3dd41048da66cad3e8fd3ef72a9d86ecd9009c2db996af29dc62af5ef5eb04d0e16ce8fcecba92a4a9888f52d5d575e7dbc302ed97dbf69df15bb4f5c5601d38fbe3bd89d88768a6aed11ce2f95a6ad30bb72e787bfb734701cea1f38168be44ea19d3e98dd3c953fdb9951ac9c6e221bb0f980d8f0952ac8127da5bda7077dd25ffc8e1515c529f29516dacec6be9c084e6c91698267b2aed9038eca5ebafad479c5fb17652e25bb5b85586fae645bd7c3253d9916c0af65a20253412d5484ac15d288c6ca8823469090ded5ce0975dada63653797129f0e926af6247b457b067db683e37d848e0acf30e5602b78f1848e8da4b640ed08b75f3519a40ec96b2be964234beab37759504376c6e5ebfacdc57e4c7a22cf1e879d7bde29a2dca5fe20420215b59d102fd016606c533e8e36f7da114910664bade9b295d9043a01bc0dc4d8abbc16b1cec7789d89e699ad99dae597c7f10d6f047efc011d67444695cb8e6e8b3dba17ccc693729d01312d0f12a3fc76e12c2e4984af5cb3049b9d8a13124a1f770e96bae1fb153ba4c91bea4fae6f03010275d5a9b14012bdd678e037934dc6762005de54b32a7684e03060d5cc80378e9bef05b8f0692202944401bd06e4553e4490a0e57c5a72fc8abb1f714e22ea950fb2f1de284d6ff3da435954de355c677f60db4252a510919cbe7dadfed0441cf125fd8894753af8114f2ddacb75c3daa460920fc47d285e59fe9110e4151fcef03fa246cd2dd9a4d573e1dbbda1c6968cf4f546289b95ce1bf0a55eea6531382826d4002bc46bf441ce16056d42b5a2079e299e3191c23a7604cde03de6081e06f93cfe632c9a6088cd328662d47a4954934832df5b5f3765dbe136114c73c55cb7ce639e5d40d1d1d8f540d3c8e1bc7423f032c0da5264353468f009c973eec0448e41f9289e8d9dadc68da77d3c3ab3a6477d44024f21fba0bd4477d81c6027657527aa0413b45f417cb7b3beea835a1d5d795414d38156324cb5c1303e9924dbe40cd497c4c23c221cb912058c939bea8b79b3fea360fecaa83375a9a84e338d9e863e8021ad2df4430b8dea0c1714e1bdc478f559705549ad738453ab65c0ffcc8cf0e3bafaf4afad75ecc4dfad0de0cfe27d50d656456ea6c361b76508357714079424";
@@ -374,6 +598,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_money() {
+        let eur = Currency::new("EUR").unwrap();
+        assert_eq!(Money::parser().parse("1000,"), Ok((",", bare(1000))));
+        assert_eq!(
+            Money::parser().parse("1000 usd,"),
+            Ok((",", Money::from_minor(100_000, Currency::USD)))
+        );
+        assert_eq!(
+            Money::parser().parse("1000.5 eur,"),
+            Ok((",", Money::from_minor(100_050, eur)))
+        );
+        // Not a currency code: left for the next parser.
+        assert_eq!(Money::parser().parse("7 units"), Ok((" units", bare(7))));
+        assert_eq!(
+            Money::parser().parse("92233720368547758.08"),
+            Err(ParseError::Overflow("amount out of range"))
+        );
+        assert!(Money::parser().parse("-5").is_err());
+
+        let amount = Money::from_minor(100_050, eur);
+        assert_eq!(amount.to_string(), "1000.50 eur");
+        assert_eq!(amount.to_log_string(), "1000.50 eur");
+        assert_eq!(bare(10).to_string(), "10");
+        assert_eq!(bare(10).to_log_string(), "10");
+        // The code the log wrote is kept, USD included.
+        let usd = Money::from_minor(1000, Currency::USD);
+        assert_eq!(usd.to_log_string(), "10 usd");
+        assert_eq!(bare(10).checked_sub(bare(12)).unwrap().amount(), "-2");
+        assert_eq!(
+            bare(10).checked_add(usd),
+            Err(MoneyError::CurrencyMismatch(Currency::UNSPECIFIED, Currency::USD))
+        );
+        assert_eq!(
+            MoneyError::CurrencyMismatch(Currency::UNSPECIFIED, eur).to_string(),
+            "currency mismatch: unspecified and eur"
+        );
+        assert_eq!(
+            Money::from_minor(i64::MIN, eur).checked_sub(Money::from_minor(1, eur)),
+            Err(MoneyError::Overflow)
+        );
+    }
+
     #[test]
     fn test_just_parse_user_cash() {
         assert_eq!(
@@ -382,7 +649,7 @@ mod tests {
                 "",
                 UserCash {
                     user_id: UserId("Alice".into()),
-                    count: nz(500)
+                    count: bare(500)
                 }
             ))
         );
@@ -452,14 +719,30 @@ mod tests {
         assert!(just_parse::<Announcements>("not a list").is_err());
     }
 
+    #[test]
+    fn test_authdata_variable_length_and_base64() {
+        let short = AuthData(vec![0x30, 0xc3, 0x05]);
+        assert_eq!(AuthData::parser().parse("30c305 requestid=1"), Ok((" requestid=1", short.clone())));
+        assert_eq!(AuthData::parser().parse("base64:MMMF"), Ok(("", short.clone())));
+        assert_eq!(AuthData::parser().parse("base64:MMM="), Ok(("", AuthData(vec![0x30, 0xc3]))));
+        assert_eq!(AuthData::parser().parse("base64:MMM"), Ok(("", AuthData(vec![0x30, 0xc3]))));
+        assert_eq!(short.to_string(), "30c305(3 bytes)");
+    }
+
     #[test]
     fn test_authdata_error_cases() {
-        // Empty input
-        assert!(AuthData::parser().parse("").is_err());
-        // Too short (only a few hex digits)
-        assert!(AuthData::parser().parse("30c305").is_err());
-        // Non-hex characters
-        assert!(AuthData::parser().parse("zzzz").is_err());
+        fn err(s: &str) -> ParseError {
+            AuthData::parser().parse(s).unwrap_err()
+        }
+        assert_eq!(err(""), ParseError::IncompleteInput("expected hex digits"));
+        assert_eq!(err("30c"), ParseError::IncompleteInput("odd number of hex digits"));
+        assert_eq!(err("zzzz"), ParseError::UnexpectedInput("non-hex character in blob"));
+        assert_eq!(err("30c3z5"), ParseError::UnexpectedInput("non-hex character in blob"));
+        assert_eq!(err(&"00".repeat(MAX_AUTHDATA_SIZE + 1)), ParseError::InvalidValue("blob is longer than allowed"));
+        assert_eq!(err("base64:"), ParseError::IncompleteInput("expected base64 data"));
+        assert_eq!(err("base64:MMMF!"), ParseError::UnexpectedInput("non-base64 character in blob"));
+        assert_eq!(err("base64:MMMFM"), ParseError::IncompleteInput("truncated base64 data"));
+        assert_eq!(err("base64:MM==="), ParseError::InvalidValue("invalid base64 padding"));
     }
 
     #[test]
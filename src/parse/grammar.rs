@@ -0,0 +1,334 @@
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use super::attributes::*;
+use super::combinators::*;
+use super::log::*;
+
+/// Object-safe view of a payload type registered in a [LogGrammar].
+trait DynPayload: Any + fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn dyn_eq(&self, other: &dyn Any) -> bool;
+    fn dyn_emit(&self, out: &mut String);
+}
+
+impl<T: Emit + fmt::Debug + PartialEq + Send + Sync + 'static> DynPayload for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+
+    fn dyn_emit(&self, out: &mut String) {
+        self.emit(out);
+    }
+}
+
+/// Payload of a [LogKind::Custom] line, a value of the type produced by the
+/// registered parser. Cloning is cheap, the value is shared.
+#[derive(Clone)]
+pub struct CustomPayload(Arc<dyn DynPayload>);
+
+impl CustomPayload {
+    pub fn new<T: Emit + fmt::Debug + PartialEq + Send + Sync + 'static>(value: T) -> Self {
+        CustomPayload(Arc::new(value))
+    }
+
+    /// The payload as `T`, `None` if it is of another type.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl fmt::Debug for CustomPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl PartialEq for CustomPayload {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.dyn_eq(other.0.as_any())
+    }
+}
+
+impl Emit for CustomPayload {
+    fn emit(&self, out: &mut String) {
+        self.0.dyn_emit(out);
+    }
+}
+
+type PayloadParser =
+    dyn for<'a> Fn(&'a str) -> Result<(&'a str, CustomPayload), ParseError> + Send + Sync;
+
+struct CustomVariant {
+    source: String,
+    category: String,
+    variant: String,
+    parser: Box<PayloadParser>,
+}
+
+/// A variant passed to [LogGrammar::register] that cannot be added.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum GrammarError {
+    #[error("{0} is a built-in log variant")]
+    Builtin(String),
+    #[error("{0} is already registered")]
+    Duplicate(String),
+}
+
+/// The built-in log grammar plus variants registered at runtime, so
+/// house-specific lines parse into [LogKind::Custom] instead of
+/// [LogKind::Unknown].
+///
+/// A line whose `Source::Category Variant` matches a registered variant is
+/// parsed with its payload parser only; if that fails the line is an error.
+/// Other lines go through the built-in [LogLine] grammar.
+///
+/// ```
+/// use analysis::parse::{LogGrammar, LogKind, primitives};
+///
+/// let mut grammar = LogGrammar::default();
+/// grammar.register("App", "Journal", "Rebate", primitives::U64).unwrap();
+/// let (log, warnings) = grammar.parse_line("App::Journal Rebate 250 requestid=7").unwrap();
+/// assert!(warnings.is_empty());
+/// let LogKind::Custom { payload, .. } = &log.kind else { unreachable!() };
+/// assert_eq!(payload.downcast_ref::<u64>(), Some(&250));
+/// ```
+#[derive(Clone, Default)]
+pub struct LogGrammar {
+    variants: Vec<Arc<CustomVariant>>,
+}
+
+impl LogGrammar {
+    /// Adds a variant whose payload is read by `parser`; the parsed value
+    /// must be writable back with [Emit] so custom lines round-trip.
+    pub fn register<P>(
+        &mut self,
+        source: &str,
+        category: &str,
+        variant: &str,
+        parser: P,
+    ) -> Result<&mut Self, GrammarError>
+    where
+        P: Parser + Send + Sync + 'static,
+        P::Dest: Emit + fmt::Debug + PartialEq + Send + Sync + 'static,
+    {
+        let name = format!("{source}::{category} {variant}");
+        if UnknownKind::is_known(source, category, variant) {
+            return Err(GrammarError::Builtin(name));
+        }
+        if self.find(source, category, variant).is_some() {
+            return Err(GrammarError::Duplicate(name));
+        }
+        self.variants.push(Arc::new(CustomVariant {
+            source: source.to_owned(),
+            category: category.to_owned(),
+            variant: variant.to_owned(),
+            parser: Box::new(move |input| {
+                parser
+                    .parse(input)
+                    .map(|(remaining, value)| (remaining, CustomPayload::new(value)))
+            }),
+        }));
+        Ok(self)
+    }
+
+    fn find(&self, source: &str, category: &str, variant: &str) -> Option<&CustomVariant> {
+        self.variants
+            .iter()
+            .map(|custom| custom.as_ref())
+            .find(|custom| {
+                (
+                    custom.source.as_str(),
+                    custom.category.as_str(),
+                    custom.variant.as_str(),
+                ) == (source, category, variant)
+            })
+    }
+
+    /// Parses a log kind: a registered variant, a built-in one or
+    /// [LogKind::Unknown].
+    pub fn parse_kind<'a>(&self, input: &'a str) -> Result<(&'a str, LogKind), ParseError> {
+        let input = input.trim_start();
+        if let Ok((remaining, (source, category, variant))) = UnknownKind::skeleton(input)
+            && let Some(custom) = self.find(source, category, variant)
+        {
+            let (remaining, payload) = (custom.parser)(remaining.trim_start())?;
+            let kind = LogKind::Custom {
+                source: custom.source.clone(),
+                category: custom.category.clone(),
+                variant: custom.variant.clone(),
+                payload,
+            };
+            return Ok((remaining.trim_start(), kind));
+        }
+        LogKind::parser().parse(input)
+    }
+
+    /// Parses a single log line along with its warnings, e.g. unknown keys
+    /// that were skipped; `None` if it is blank or does not match the
+    /// grammar, as [crate::parse_line].
+    pub fn parse_line(&self, line: &str) -> Option<(LogLine, Vec<String>)> {
+        let (parsed, warnings) = collect_warnings(|| self.parse(line.trim()));
+        let (remaining, log) = parsed.ok()?;
+        remaining.trim().is_empty().then_some((log, warnings))
+    }
+}
+
+impl Parser for LogGrammar {
+    type Dest = LogLine;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, kind) = self.parse_kind(input)?;
+        let (remaining, (request_id, attributes)) = Trailer.parse(remaining)?;
+        let log = LogLine {
+            kind,
+            request_id,
+            attributes,
+        };
+        Ok((remaining, log))
+    }
+}
+
+impl fmt::Debug for LogGrammar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(
+                self.variants.iter().map(|custom| {
+                    format!("{}::{} {}", custom.source, custom.category, custom.variant)
+                }),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Utf8Policy;
+    use crate::parse::{AssetId, UserId};
+
+    /// `{"user_id":..,"asset_id":..,}`, a house-specific journal payload.
+    #[derive(Debug, PartialEq)]
+    struct Airdrop {
+        user_id: UserId,
+        asset_id: AssetId,
+    }
+
+    impl Emit for Airdrop {
+        fn emit(&self, out: &mut String) {
+            out.push('{');
+            emit_key_value(out, "user_id", &self.user_id);
+            emit_key_value(out, "asset_id", &self.asset_id);
+            out.push('}');
+        }
+    }
+
+    struct AirdropParser;
+    impl Parser for AirdropParser {
+        type Dest = Airdrop;
+        fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+            delimited(
+                tag("{"),
+                tuple2(
+                    key_value("user_id", UserId::parser()),
+                    key_value("asset_id", AssetId::parser()),
+                ),
+                tag("}"),
+            )
+            .map(|(user_id, asset_id)| Airdrop { user_id, asset_id })
+            .parse(input)
+        }
+    }
+
+    fn grammar() -> LogGrammar {
+        let mut grammar = LogGrammar::default();
+        grammar
+            .register("App", "Journal", "Airdrop", AirdropParser)
+            .unwrap()
+            .register("App", "Trace", "Latency", primitives::U64)
+            .unwrap();
+        grammar
+    }
+
+    #[test]
+    fn test_custom_variants() {
+        let grammar = grammar();
+        let line = r#"App::Journal Airdrop {"user_id":"Bob","asset_id":"milk",} requestid=4"#;
+        let (log, warnings) = grammar.parse_line(line).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!((log.kind.source(), log.kind.variant()), ("App", "Airdrop"));
+        let LogKind::Custom { payload, .. } = &log.kind else {
+            panic!("{log:?}");
+        };
+        assert_eq!(
+            payload.downcast_ref::<Airdrop>(),
+            Some(&Airdrop {
+                user_id: UserId("Bob".into()),
+                asset_id: AssetId("milk".into()),
+            })
+        );
+        assert_eq!(payload.downcast_ref::<u64>(), None);
+        assert_eq!(log.to_log_string(), line);
+        assert_eq!(
+            grammar.parse_line(&log.to_log_string()),
+            Some((log.clone(), vec![]))
+        );
+        let logs =
+            crate::LogIterator::with_grammar(line.as_bytes(), Utf8Policy::Lossy, grammar.clone())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+        assert_eq!(logs, [log]);
+
+        // Built-in and unregistered variants are unaffected.
+        let (latency, _) = grammar
+            .parse_line("App::Trace Latency 1_500 requestid=5")
+            .unwrap();
+        assert_eq!(
+            latency.to_log_string(),
+            "App::Trace Latency 1500 requestid=5"
+        );
+        assert!(matches!(
+            grammar
+                .parse_line("App::Trace Heartbeat requestid=6")
+                .unwrap()
+                .0
+                .kind,
+            LogKind::Unknown { .. }
+        ));
+        assert_eq!(
+            grammar.parse_line(r#"App::Error LackOf "milk" requestid=6"#),
+            crate::parse_line(r#"App::Error LackOf "milk" requestid=6"#)
+        );
+        // A registered variant with a bad payload is rejected.
+        assert_eq!(
+            grammar.parse_line("App::Trace Latency soon requestid=5"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let mut grammar = grammar();
+        assert_eq!(
+            grammar
+                .register("App", "Journal", "BuyAsset", primitives::U64)
+                .err(),
+            Some(GrammarError::Builtin("App::Journal BuyAsset".into()))
+        );
+        assert_eq!(
+            grammar
+                .register("App", "Trace", "Latency", primitives::I64)
+                .err()
+                .map(|err| err.to_string()),
+            Some("App::Trace Latency is already registered".into())
+        );
+        assert_eq!(
+            format!("{grammar:?}"),
+            r#"["App::Journal Airdrop", "App::Trace Latency"]"#
+        );
+    }
+}
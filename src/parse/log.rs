@@ -1,20 +1,41 @@
 use std::fmt;
 
-use super::combinators::primitives;
+use super::attributes::*;
 use super::combinators::*;
 use super::domain::*;
+use super::grammar::CustomPayload;
+use super::value::Value;
 
 /// Все виды логов
 #[derive(Debug, Clone, PartialEq)]
 pub enum LogKind {
     System(SystemLogKind),
     App(AppLogKind),
+    /// A `Source::Category Variant ...` line the grammar does not know yet,
+    /// e.g. a variant added by a newer exchange; the payload is kept verbatim
+    Unknown {
+        source: String,
+        category: String,
+        variant: String,
+        raw_payload: String,
+    },
+    /// A variant registered at runtime in a [LogGrammar](super::LogGrammar)
+    Custom {
+        source: String,
+        category: String,
+        variant: String,
+        payload: CustomPayload,
+    },
 }
 /// Все виды [системных](LogKind) логов
 #[derive(Debug, Clone, PartialEq)]
 pub enum SystemLogKind {
     Error(SystemLogErrorKind),
     Trace(SystemLogTraceKind),
+    Debug(FreeText),
+    Info(FreeText),
+    Warn(FreeText),
+    Fatal(FreeText),
 }
 /// Trace [системы](SystemLogKind)
 #[derive(Debug, Clone, PartialEq)]
@@ -34,6 +55,10 @@ pub enum AppLogKind {
     Error(AppLogErrorKind),
     Trace(AppLogTraceKind),
     Journal(AppLogJournalKind),
+    Debug(FreeText),
+    Info(FreeText),
+    Warn(FreeText),
+    Fatal(FreeText),
 }
 /// Error [приложения](AppLogKind)
 #[derive(Debug, Clone, PartialEq)]
@@ -54,7 +79,7 @@ pub enum AppLogTraceKind {
 pub enum AppLogJournalKind {
     CreateUser {
         user_id: UserId,
-        authorized_capital: std::num::NonZeroU32,
+        authorized_capital: Money,
     },
     DeleteUser {
         user_id: UserId,
@@ -62,7 +87,7 @@ pub enum AppLogJournalKind {
     RegisterAsset {
         asset_id: AssetId,
         user_id: UserId,
-        liquidity: std::num::NonZeroU32,
+        liquidity: Money,
     },
     UnregisterAsset {
         asset_id: AssetId,
@@ -73,11 +98,382 @@ pub enum AppLogJournalKind {
     BuyAsset(UserBacket),
     SellAsset(UserBacket),
 }
+/// Entry of a message category (`Debug`, `Info`, `Warn` or `Fatal`): its
+/// variant tag and the text after it up to the [Trailer], kept verbatim
+#[derive(Debug, Clone, PartialEq)]
+pub struct FreeText {
+    pub variant: String,
+    pub text: String,
+}
+impl fmt::Display for FreeText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.text.is_empty() {
+            true => f.write_str(&self.variant),
+            false => write!(f, "{} {}", self.variant, self.text),
+        }
+    }
+}
+impl Emit for FreeText {
+    fn emit(&self, out: &mut String) {
+        out.push_str(&self.variant);
+        if !self.text.is_empty() {
+            out.push(' ');
+            out.push_str(&self.text);
+        }
+    }
+}
+/// Parser of a message category: the category tag, then any variant with
+/// its [FreeText]
+#[derive(Debug, Clone)]
+pub struct FreeTextParser {
+    category: &'static str,
+}
+impl Parser for FreeTextParser {
+    type Dest = FreeText;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, ()) = tag(self.category).parse(input)?;
+        if !remaining.starts_with(char::is_whitespace) {
+            return Err(ParseError::UnexpectedInput("expected whitespace after log category"));
+        }
+        let (remaining, variant) = UnknownKind::identifier(remaining.trim_start(), "expected log variant")?;
+        if !remaining.is_empty() && !remaining.starts_with(char::is_whitespace) {
+            return Err(ParseError::UnexpectedInput("expected whitespace after log variant"));
+        }
+        let end = Trailer::start(remaining);
+        let text = FreeText {
+            variant: variant.to_owned(),
+            text: remaining[..end].trim().to_owned(),
+        };
+        Ok((&remaining[end..], text))
+    }
+}
+/// Constructor of [FreeTextParser]
+pub(crate) fn free_text(category: &'static str) -> FreeTextParser {
+    FreeTextParser { category }
+}
+/// Severity of a log line, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+}
+impl Severity {
+    pub const ALL: [Severity; 6] = [
+        Severity::Trace,
+        Severity::Debug,
+        Severity::Info,
+        Severity::Warn,
+        Severity::Error,
+        Severity::Fatal,
+    ];
+
+    /// Severity of a log category: its own level, [Severity::Info] for
+    /// `Journal`, `None` for categories the grammar does not know.
+    pub fn of_category(category: &str) -> Option<Severity> {
+        match category {
+            "Journal" => Some(Severity::Info),
+            _ => Severity::ALL.into_iter().find(|severity| severity.as_str() == category),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Trace => "Trace",
+            Severity::Debug => "Debug",
+            Severity::Info => "Info",
+            Severity::Warn => "Warn",
+            Severity::Error => "Error",
+            Severity::Fatal => "Fatal",
+        }
+    }
+}
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl LogKind {
+    /// Source of the log line as written in the log: `"System"` or `"App"`.
+    pub fn source(&self) -> &str {
+        match self {
+            LogKind::System(_) => "System",
+            LogKind::App(_) => "App",
+            LogKind::Unknown { source, .. } | LogKind::Custom { source, .. } => source,
+        }
+    }
+
+    /// Category within the source: a [severity](Severity) such as `"Error"`
+    /// or `"Trace"`, or `"Journal"`.
+    pub fn category(&self) -> &str {
+        match self {
+            LogKind::System(SystemLogKind::Error(_)) | LogKind::App(AppLogKind::Error(_)) => "Error",
+            LogKind::System(SystemLogKind::Trace(_)) | LogKind::App(AppLogKind::Trace(_)) => "Trace",
+            LogKind::System(SystemLogKind::Debug(_)) | LogKind::App(AppLogKind::Debug(_)) => "Debug",
+            LogKind::System(SystemLogKind::Info(_)) | LogKind::App(AppLogKind::Info(_)) => "Info",
+            LogKind::System(SystemLogKind::Warn(_)) | LogKind::App(AppLogKind::Warn(_)) => "Warn",
+            LogKind::System(SystemLogKind::Fatal(_)) | LogKind::App(AppLogKind::Fatal(_)) => "Fatal",
+            LogKind::App(AppLogKind::Journal(_)) => "Journal",
+            LogKind::Unknown { category, .. } | LogKind::Custom { category, .. } => category,
+        }
+    }
+
+    /// Severity of the line, by its category; journal operations and
+    /// unknown or custom lines of an unrecognized category are [Severity::Info].
+    pub fn severity(&self) -> Severity {
+        Severity::of_category(self.category()).unwrap_or(Severity::Info)
+    }
+
+    /// Free-text message of an error line, `None` for other categories.
+    pub fn error_message(&self) -> Option<&str> {
+        match self {
+            LogKind::System(SystemLogKind::Error(
+                SystemLogErrorKind::NetworkError(msg) | SystemLogErrorKind::AccessDenied(msg),
+            ))
+            | LogKind::App(AppLogKind::Error(
+                AppLogErrorKind::LackOf(msg) | AppLogErrorKind::SystemError(msg),
+            )) => Some(msg),
+            _ => None,
+        }
+    }
+
+    /// Message of a `Debug`, `Info`, `Warn` or `Fatal` line, `None` for other categories.
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            LogKind::System(SystemLogKind::Debug(kind)) => Some(&kind.text),
+            LogKind::System(SystemLogKind::Info(kind)) => Some(&kind.text),
+            LogKind::System(SystemLogKind::Warn(kind)) => Some(&kind.text),
+            LogKind::System(SystemLogKind::Fatal(kind)) => Some(&kind.text),
+            LogKind::App(AppLogKind::Debug(kind)) => Some(&kind.text),
+            LogKind::App(AppLogKind::Info(kind)) => Some(&kind.text),
+            LogKind::App(AppLogKind::Warn(kind)) => Some(&kind.text),
+            LogKind::App(AppLogKind::Fatal(kind)) => Some(&kind.text),
+            _ => None,
+        }
+    }
+
+    /// Variant tag as written in the log, e.g. `"NetworkError"` or `"BuyAsset"`.
+    pub fn variant(&self) -> &str {
+        match self {
+            LogKind::System(SystemLogKind::Error(kind)) => match kind {
+                SystemLogErrorKind::NetworkError(_) => "NetworkError",
+                SystemLogErrorKind::AccessDenied(_) => "AccessDenied",
+            },
+            LogKind::System(SystemLogKind::Trace(kind)) => match kind {
+                SystemLogTraceKind::SendRequest(_) => "SendRequest",
+                SystemLogTraceKind::GetResponse(_) => "GetResponse",
+            },
+            LogKind::System(SystemLogKind::Debug(kind)) => &kind.variant,
+            LogKind::System(SystemLogKind::Info(kind)) => &kind.variant,
+            LogKind::System(SystemLogKind::Warn(kind)) => &kind.variant,
+            LogKind::System(SystemLogKind::Fatal(kind)) => &kind.variant,
+            LogKind::App(AppLogKind::Debug(kind)) => &kind.variant,
+            LogKind::App(AppLogKind::Info(kind)) => &kind.variant,
+            LogKind::App(AppLogKind::Warn(kind)) => &kind.variant,
+            LogKind::App(AppLogKind::Fatal(kind)) => &kind.variant,
+            LogKind::App(AppLogKind::Error(kind)) => match kind {
+                AppLogErrorKind::LackOf(_) => "LackOf",
+                AppLogErrorKind::SystemError(_) => "SystemError",
+            },
+            LogKind::App(AppLogKind::Trace(kind)) => match kind {
+                AppLogTraceKind::Connect(_) => "Connect",
+                AppLogTraceKind::SendRequest(_) => "SendRequest",
+                AppLogTraceKind::Check(_) => "Check",
+                AppLogTraceKind::GetResponse(_) => "GetResponse",
+            },
+            LogKind::App(AppLogKind::Journal(kind)) => match kind {
+                AppLogJournalKind::CreateUser { .. } => "CreateUser",
+                AppLogJournalKind::DeleteUser { .. } => "DeleteUser",
+                AppLogJournalKind::RegisterAsset { .. } => "RegisterAsset",
+                AppLogJournalKind::UnregisterAsset { .. } => "UnregisterAsset",
+                AppLogJournalKind::DepositCash(_) => "DepositCash",
+                AppLogJournalKind::WithdrawCash(_) => "WithdrawCash",
+                AppLogJournalKind::BuyAsset(_) => "BuyAsset",
+                AppLogJournalKind::SellAsset(_) => "SellAsset",
+            },
+            LogKind::Unknown { variant, .. } | LogKind::Custom { variant, .. } => variant,
+        }
+    }
+
+    /// Free-text request or response payload of a trace line, or the raw
+    /// payload of an [unknown](LogKind::Unknown) line or [FreeText] variant.
+    pub fn payload_mut(&mut self) -> Option<&mut String> {
+        match self {
+            LogKind::Unknown { raw_payload, .. } => Some(raw_payload),
+            LogKind::System(
+                SystemLogKind::Debug(other)
+                | SystemLogKind::Info(other)
+                | SystemLogKind::Warn(other)
+                | SystemLogKind::Fatal(other),
+            )
+            | LogKind::App(
+                AppLogKind::Debug(other)
+                | AppLogKind::Info(other)
+                | AppLogKind::Warn(other)
+                | AppLogKind::Fatal(other),
+            ) => Some(&mut other.text),
+            LogKind::System(SystemLogKind::Trace(
+                SystemLogTraceKind::SendRequest(payload) | SystemLogTraceKind::GetResponse(payload),
+            ))
+            | LogKind::App(AppLogKind::Trace(
+                AppLogTraceKind::SendRequest(payload) | AppLogTraceKind::GetResponse(payload),
+            )) => Some(payload),
+            _ => None,
+        }
+    }
+
+    /// Raw payload of an [unknown](LogKind::Unknown) line read as a generic
+    /// [Value], if it is well-formed.
+    pub fn payload_value(&self) -> Option<Value> {
+        match self {
+            LogKind::Unknown { raw_payload, .. } => Value::from_payload(raw_payload).ok(),
+            _ => None,
+        }
+    }
+
+    /// Calls `f` on every free-text field of the line: error and other
+    /// messages, trace payloads and the raw payload of an unknown line.
+    /// [Custom](LogKind::Custom) payloads are opaque and not visited.
+    pub fn texts_mut(&mut self, mut f: impl FnMut(&mut String)) {
+        match self {
+            LogKind::System(SystemLogKind::Error(
+                SystemLogErrorKind::NetworkError(msg) | SystemLogErrorKind::AccessDenied(msg),
+            ))
+            | LogKind::App(AppLogKind::Error(
+                AppLogErrorKind::LackOf(msg) | AppLogErrorKind::SystemError(msg),
+            )) => f(msg),
+            _ => {
+                if let Some(payload) = self.payload_mut() {
+                    f(payload);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on every user and asset id in the structured fields of the
+    /// line; ids inside free-text payloads are not visited, see
+    /// [map_payload_ids].
+    pub fn ids_mut(&mut self, mut f: impl FnMut(IdKind, &mut String)) {
+        match self {
+            LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) => {
+                for offer in &mut announcements.0 {
+                    f(IdKind::User, &mut offer.user_id.0);
+                    for backet in &mut offer.backets {
+                        f(IdKind::Asset, &mut backet.asset_id.0);
+                    }
+                }
+            }
+            LogKind::App(AppLogKind::Journal(journal)) => match journal {
+                AppLogJournalKind::CreateUser { user_id, .. }
+                | AppLogJournalKind::DeleteUser { user_id } => f(IdKind::User, &mut user_id.0),
+                AppLogJournalKind::RegisterAsset {
+                    asset_id, user_id, ..
+                }
+                | AppLogJournalKind::UnregisterAsset { asset_id, user_id } => {
+                    f(IdKind::Asset, &mut asset_id.0);
+                    f(IdKind::User, &mut user_id.0);
+                }
+                AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+                    f(IdKind::User, &mut cash.user_id.0)
+                }
+                AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => {
+                    f(IdKind::User, &mut trade.user_id.0);
+                    f(IdKind::Asset, &mut trade.backet.asset_id.0);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Kind of id visited by [LogKind::ids_mut] and [map_payload_ids].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum IdKind {
+    User,
+    Asset,
+}
+
+/// Replaces the values of `"user_id"` and `"asset_id"` keys in free text,
+/// quoted (`"Bob"`) or bare (`10`), with `f(kind, value)`; quotes are kept.
+pub fn map_payload_ids(text: &str, mut f: impl FnMut(IdKind, &str) -> String) -> String {
+    const KEYS: [(&str, IdKind); 2] = [
+        ("\"user_id\"", IdKind::User),
+        ("\"asset_id\"", IdKind::Asset),
+    ];
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((pos, key, kind)) = KEYS
+        .iter()
+        .filter_map(|&(key, kind)| rest.find(key).map(|pos| (pos, key, kind)))
+        .min_by_key(|&(pos, ..)| pos)
+    {
+        out.push_str(&rest[..pos + key.len()]);
+        rest = &rest[pos + key.len()..];
+        let Some(value) = rest.trim_start().strip_prefix(':') else {
+            continue;
+        };
+        let value = value.trim_start();
+        out.push_str(&rest[..rest.len() - value.len()]);
+        rest = value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            out.push('"');
+            out.push_str(&f(kind, &quoted[..end]));
+            rest = &quoted[end..];
+        } else {
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                .unwrap_or(rest.len());
+            if end > 0 {
+                out.push_str(&f(kind, &rest[..end]));
+                rest = &rest[end..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+impl AppLogJournalKind {
+    /// User the operation is performed for.
+    pub fn user_id(&self) -> &UserId {
+        match self {
+            AppLogJournalKind::CreateUser { user_id, .. }
+            | AppLogJournalKind::DeleteUser { user_id }
+            | AppLogJournalKind::RegisterAsset { user_id, .. }
+            | AppLogJournalKind::UnregisterAsset { user_id, .. } => user_id,
+            AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+                &cash.user_id
+            }
+            AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => {
+                &trade.user_id
+            }
+        }
+    }
+}
 impl fmt::Display for LogKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LogKind::System(kind) => write!(f, "System::{kind}"),
             LogKind::App(kind) => write!(f, "App::{kind}"),
+            LogKind::Unknown {
+                source,
+                category,
+                variant,
+                raw_payload,
+            } => write!(f, "{source}::{category} {variant} {raw_payload}"),
+            LogKind::Custom {
+                source,
+                category,
+                variant,
+                payload,
+            } => write!(
+                f,
+                "{source}::{category} {variant} {}",
+                payload.to_log_string()
+            ),
         }
     }
 }
@@ -87,6 +483,10 @@ impl fmt::Display for SystemLogKind {
         match self {
             SystemLogKind::Error(kind) => write!(f, "Error {kind}"),
             SystemLogKind::Trace(kind) => write!(f, "Trace {kind}"),
+            SystemLogKind::Debug(kind) => write!(f, "Debug {kind}"),
+            SystemLogKind::Info(kind) => write!(f, "Info {kind}"),
+            SystemLogKind::Warn(kind) => write!(f, "Warn {kind}"),
+            SystemLogKind::Fatal(kind) => write!(f, "Fatal {kind}"),
         }
     }
 }
@@ -115,6 +515,10 @@ impl fmt::Display for AppLogKind {
             AppLogKind::Error(kind) => write!(f, "Error {kind}"),
             AppLogKind::Trace(kind) => write!(f, "Trace {kind}"),
             AppLogKind::Journal(kind) => write!(f, "Journal {kind}"),
+            AppLogKind::Debug(kind) => write!(f, "Debug {kind}"),
+            AppLogKind::Info(kind) => write!(f, "Info {kind}"),
+            AppLogKind::Warn(kind) => write!(f, "Warn {kind}"),
+            AppLogKind::Fatal(kind) => write!(f, "Fatal {kind}"),
         }
     }
 }
@@ -170,7 +574,233 @@ impl fmt::Display for AppLogJournalKind {
 
 impl fmt::Display for LogLine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "[req={}] {}", self.request_id, self.kind)
+        write!(f, "[req={}", self.request_id)?;
+        for attribute in &self.attributes {
+            write!(f, " {attribute}")?;
+        }
+        write!(f, "] {}", self.kind)
+    }
+}
+
+impl Emit for LogKind {
+    fn emit(&self, out: &mut String) {
+        match self {
+            LogKind::System(kind) => {
+                out.push_str("System::");
+                kind.emit(out);
+            }
+            LogKind::App(kind) => {
+                out.push_str("App::");
+                kind.emit(out);
+            }
+            LogKind::Unknown {
+                source,
+                category,
+                variant,
+                raw_payload,
+            } => {
+                out.push_str(&format!("{source}::{category} {variant}"));
+                if !raw_payload.is_empty() {
+                    out.push(' ');
+                    out.push_str(raw_payload);
+                }
+            }
+            LogKind::Custom {
+                source,
+                category,
+                variant,
+                payload,
+            } => {
+                out.push_str(&format!("{source}::{category} {variant} "));
+                payload.emit(out);
+            }
+        }
+    }
+}
+
+impl Emit for SystemLogKind {
+    fn emit(&self, out: &mut String) {
+        match self {
+            SystemLogKind::Error(kind) => {
+                out.push_str("Error ");
+                kind.emit(out);
+            }
+            SystemLogKind::Trace(kind) => {
+                out.push_str("Trace ");
+                kind.emit(out);
+            }
+            SystemLogKind::Debug(kind) => {
+                out.push_str("Debug ");
+                kind.emit(out);
+            }
+            SystemLogKind::Info(kind) => {
+                out.push_str("Info ");
+                kind.emit(out);
+            }
+            SystemLogKind::Warn(kind) => {
+                out.push_str("Warn ");
+                kind.emit(out);
+            }
+            SystemLogKind::Fatal(kind) => {
+                out.push_str("Fatal ");
+                kind.emit(out);
+            }
+        }
+    }
+}
+
+impl Emit for SystemLogTraceKind {
+    fn emit(&self, out: &mut String) {
+        let (tag, msg) = match self {
+            SystemLogTraceKind::SendRequest(msg) => ("SendRequest ", msg),
+            SystemLogTraceKind::GetResponse(msg) => ("GetResponse ", msg),
+        };
+        out.push_str(tag);
+        msg.emit(out);
+    }
+}
+
+impl Emit for SystemLogErrorKind {
+    fn emit(&self, out: &mut String) {
+        let (tag, msg) = match self {
+            SystemLogErrorKind::NetworkError(msg) => ("NetworkError ", msg),
+            SystemLogErrorKind::AccessDenied(msg) => ("AccessDenied ", msg),
+        };
+        out.push_str(tag);
+        msg.emit(out);
+    }
+}
+
+impl Emit for AppLogKind {
+    fn emit(&self, out: &mut String) {
+        match self {
+            AppLogKind::Error(kind) => {
+                out.push_str("Error ");
+                kind.emit(out);
+            }
+            AppLogKind::Trace(kind) => {
+                out.push_str("Trace ");
+                kind.emit(out);
+            }
+            AppLogKind::Journal(kind) => {
+                out.push_str("Journal ");
+                kind.emit(out);
+            }
+            AppLogKind::Debug(kind) => {
+                out.push_str("Debug ");
+                kind.emit(out);
+            }
+            AppLogKind::Info(kind) => {
+                out.push_str("Info ");
+                kind.emit(out);
+            }
+            AppLogKind::Warn(kind) => {
+                out.push_str("Warn ");
+                kind.emit(out);
+            }
+            AppLogKind::Fatal(kind) => {
+                out.push_str("Fatal ");
+                kind.emit(out);
+            }
+        }
+    }
+}
+
+impl Emit for AppLogErrorKind {
+    fn emit(&self, out: &mut String) {
+        let (tag, msg) = match self {
+            AppLogErrorKind::LackOf(msg) => ("LackOf ", msg),
+            AppLogErrorKind::SystemError(msg) => ("SystemError ", msg),
+        };
+        out.push_str(tag);
+        msg.emit(out);
+    }
+}
+
+impl Emit for AppLogTraceKind {
+    fn emit(&self, out: &mut String) {
+        match self {
+            AppLogTraceKind::Connect(auth) => {
+                out.push_str("Connect ");
+                auth.emit(out);
+            }
+            AppLogTraceKind::SendRequest(msg) => {
+                out.push_str("SendRequest ");
+                msg.emit(out);
+            }
+            AppLogTraceKind::Check(announcements) => {
+                out.push_str("Check ");
+                announcements.emit(out);
+            }
+            AppLogTraceKind::GetResponse(msg) => {
+                out.push_str("GetResponse ");
+                msg.emit(out);
+            }
+        }
+    }
+}
+
+impl Emit for AppLogJournalKind {
+    fn emit(&self, out: &mut String) {
+        match self {
+            AppLogJournalKind::CreateUser {
+                user_id,
+                authorized_capital,
+            } => {
+                out.push_str("CreateUser {");
+                emit_key_value(out, "user_id", user_id);
+                emit_key_value(out, "authorized_capital", authorized_capital);
+                out.push('}');
+            }
+            AppLogJournalKind::DeleteUser { user_id } => {
+                out.push_str("DeleteUser {");
+                emit_key_value(out, "user_id", user_id);
+                out.push('}');
+            }
+            AppLogJournalKind::RegisterAsset {
+                asset_id,
+                user_id,
+                liquidity,
+            } => {
+                out.push_str("RegisterAsset {");
+                emit_key_value(out, "asset_id", asset_id);
+                emit_key_value(out, "user_id", user_id);
+                emit_key_value(out, "liquidity", liquidity);
+                out.push('}');
+            }
+            AppLogJournalKind::UnregisterAsset { asset_id, user_id } => {
+                out.push_str("UnregisterAsset {");
+                emit_key_value(out, "asset_id", asset_id);
+                emit_key_value(out, "user_id", user_id);
+                out.push('}');
+            }
+            AppLogJournalKind::DepositCash(cash) => {
+                out.push_str("DepositCash ");
+                cash.emit(out);
+            }
+            AppLogJournalKind::WithdrawCash(cash) => {
+                out.push_str("WithdrawCash ");
+                cash.emit(out);
+            }
+            AppLogJournalKind::BuyAsset(backet) => {
+                out.push_str("BuyAsset ");
+                backet.emit(out);
+            }
+            AppLogJournalKind::SellAsset(backet) => {
+                out.push_str("SellAsset ");
+                backet.emit(out);
+            }
+        }
+    }
+}
+
+/// `<kind> requestid=<id>`, one physical log line without the newline
+impl Emit for LogLine {
+    fn emit(&self, out: &mut String) {
+        self.kind.emit(out);
+        out.push_str(" requestid=");
+        self.request_id.emit(out);
+        self.attributes.emit(out);
     }
 }
 
@@ -239,15 +869,23 @@ impl Parsable for SystemLogKind {
                     <SystemLogErrorKind as Parsable>::Parser,
                     fn(SystemLogErrorKind) -> SystemLogKind,
                 >,
+                Map<FreeTextParser, fn(FreeText) -> SystemLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> SystemLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> SystemLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> SystemLogKind>,
             )>,
         >,
     >;
     fn parser() -> Self::Parser {
-        alt2(
+        alt6(
             SystemLogTraceKind::parser()
                 .map(SystemLogKind::Trace as fn(SystemLogTraceKind) -> _),
             SystemLogErrorKind::parser()
                 .map(SystemLogKind::Error as fn(SystemLogErrorKind) -> _),
+            free_text("Debug").map(SystemLogKind::Debug as fn(FreeText) -> _),
+            free_text("Info").map(SystemLogKind::Info as fn(FreeText) -> _),
+            free_text("Warn").map(SystemLogKind::Warn as fn(FreeText) -> _),
+            free_text("Fatal").map(SystemLogKind::Fatal as fn(FreeText) -> _),
         )
         .preceded_by(tag("System::"))
         .strip_ws()
@@ -332,15 +970,15 @@ impl Parsable for AppLogJournalKind {
                     StripWhitespace<Tag>,
                     Delimited<
                         Tag,
-                        Permutation<(KeyValue<<UserId as Parsable>::Parser>, KeyValue<primitives::U32>)>,
+                        Permutation<(KeyValue<<UserId as Parsable>::Parser>, KeyValue<<Money as Parsable>::Parser>)>,
                         Tag,
                     >,
                 >,
-                fn((UserId, std::num::NonZeroU32)) -> AppLogJournalKind,
+                fn((UserId, Money)) -> AppLogJournalKind,
             >,
             Map<
-                Preceded<StripWhitespace<Tag>, Delimited<Tag, KeyValue<<UserId as Parsable>::Parser>, Tag>>,
-                fn(UserId) -> AppLogJournalKind,
+                Preceded<StripWhitespace<Tag>, Delimited<Tag, Permutation<(KeyValue<<UserId as Parsable>::Parser>,)>, Tag>>,
+                fn((UserId,)) -> AppLogJournalKind,
             >,
             Map<
                 Preceded<
@@ -350,12 +988,12 @@ impl Parsable for AppLogJournalKind {
                         Permutation<(
                             KeyValue<<AssetId as Parsable>::Parser>,
                             KeyValue<<UserId as Parsable>::Parser>,
-                            KeyValue<primitives::U32>,
+                            KeyValue<<Money as Parsable>::Parser>,
                         )>,
                         Tag,
                     >,
                 >,
-                fn((AssetId, UserId, std::num::NonZeroU32)) -> AppLogJournalKind,
+                fn((AssetId, UserId, Money)) -> AppLogJournalKind,
             >,
             Map<
                 Preceded<
@@ -388,7 +1026,7 @@ impl Parsable for AppLogJournalKind {
                 tag("{"),
                 permutation2(
                     key_value("user_id", UserId::parser()),
-                    key_value("authorized_capital", primitives::U32),
+                    key_value("authorized_capital", Money::parser()),
                 ),
                 tag("}"),
             )
@@ -396,17 +1034,17 @@ impl Parsable for AppLogJournalKind {
             .map((|(user_id, authorized_capital)| AppLogJournalKind::CreateUser {
                 user_id,
                 authorized_capital,
-            }) as fn((UserId, std::num::NonZeroU32)) -> _),
-            delimited(tag("{"), key_value("user_id", UserId::parser()), tag("}"))
+            }) as fn((UserId, Money)) -> _),
+            delimited(tag("{"), permutation((key_value("user_id", UserId::parser()),)), tag("}"))
                 .preceded_by(tag("DeleteUser").strip_ws())
-                .map((|user_id| AppLogJournalKind::DeleteUser { user_id })
-                    as fn(UserId) -> _),
+                .map((|(user_id,)| AppLogJournalKind::DeleteUser { user_id })
+                    as fn((UserId,)) -> _),
             delimited(
                 tag("{"),
                 permutation3(
                     key_value("asset_id", AssetId::parser()),
                     key_value("user_id", UserId::parser()),
-                    key_value("liquidity", primitives::U32),
+                    key_value("liquidity", Money::parser()),
                 ),
                 tag("}"),
             )
@@ -415,7 +1053,7 @@ impl Parsable for AppLogJournalKind {
                 asset_id,
                 user_id,
                 liquidity,
-            }) as fn((AssetId, UserId, std::num::NonZeroU32)) -> _),
+            }) as fn((AssetId, UserId, Money)) -> _),
             delimited(
                 tag("{"),
                 permutation2(
@@ -453,58 +1091,135 @@ impl Parsable for AppLogKind {
                 Map<<AppLogErrorKind as Parsable>::Parser, fn(AppLogErrorKind) -> AppLogKind>,
                 Map<<AppLogTraceKind as Parsable>::Parser, fn(AppLogTraceKind) -> AppLogKind>,
                 Map<<AppLogJournalKind as Parsable>::Parser, fn(AppLogJournalKind) -> AppLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> AppLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> AppLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> AppLogKind>,
+                Map<FreeTextParser, fn(FreeText) -> AppLogKind>,
             )>,
         >,
     >;
     fn parser() -> Self::Parser {
-        alt3(
+        alt7(
             AppLogErrorKind::parser()
                 .map(AppLogKind::Error as fn(AppLogErrorKind) -> _),
             AppLogTraceKind::parser()
                 .map(AppLogKind::Trace as fn(AppLogTraceKind) -> _),
             AppLogJournalKind::parser()
                 .map(AppLogKind::Journal as fn(AppLogJournalKind) -> _),
+            free_text("Debug").map(AppLogKind::Debug as fn(FreeText) -> _),
+            free_text("Info").map(AppLogKind::Info as fn(FreeText) -> _),
+            free_text("Warn").map(AppLogKind::Warn as fn(FreeText) -> _),
+            free_text("Fatal").map(AppLogKind::Fatal as fn(FreeText) -> _),
         )
         .preceded_by(tag("App::"))
         .strip_ws()
     }
 }
+/// Fallback for [LogKind]: reads the `Source::Category Variant` skeleton and
+/// keeps everything up to the [Trailer] as the raw payload.
+/// Variants the grammar knows are rejected here, so a malformed known line
+/// stays an error instead of turning into [LogKind::Unknown]
+#[derive(Debug, Clone)]
+pub struct UnknownKind;
+impl UnknownKind {
+    /// Reads `Source::Category Variant` up to the whitespace after the variant
+    pub(crate) fn skeleton(input: &str) -> Result<(&str, (&str, &str, &str)), ParseError> {
+        let (remaining, source) = Self::identifier(input, "expected log source")?;
+        let remaining = remaining
+            .strip_prefix("::")
+            .ok_or(ParseError::UnexpectedInput("expected '::' after log source"))?;
+        let (remaining, category) = Self::identifier(remaining, "expected log category")?;
+        let (remaining, variant) = Self::identifier(remaining.trim_start(), "expected log variant")?;
+        if !remaining.is_empty() && !remaining.starts_with(char::is_whitespace) {
+            return Err(ParseError::UnexpectedInput("expected whitespace after log variant"));
+        }
+        Ok((remaining, (source, category, variant)))
+    }
+
+    fn identifier<'a>(input: &'a str, what: &'static str) -> Result<(&'a str, &'a str), ParseError> {
+        let end = input
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(input.len());
+        if end == 0 {
+            return Err(ParseError::UnexpectedInput(what));
+        }
+        Ok((&input[end..], &input[..end]))
+    }
+
+    pub(crate) fn is_known(source: &str, category: &str, variant: &str) -> bool {
+        matches!(
+            (source, category, variant),
+            ("System", "Error", "NetworkError" | "AccessDenied")
+                | ("System", "Trace", "SendRequest" | "GetResponse")
+                | ("App", "Error", "LackOf" | "SystemError")
+                | ("App", "Trace", "Connect" | "SendRequest" | "Check" | "GetResponse")
+                | (
+                    "App",
+                    "Journal",
+                    "CreateUser"
+                        | "DeleteUser"
+                        | "RegisterAsset"
+                        | "UnregisterAsset"
+                        | "DepositCash"
+                        | "WithdrawCash"
+                        | "BuyAsset"
+                        | "SellAsset"
+                )
+        )
+    }
+}
+impl Parser for UnknownKind {
+    type Dest = LogKind;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, (source, category, variant)) = Self::skeleton(input)?;
+        if Self::is_known(source, category, variant) {
+            return Err(ParseError::UnexpectedInput("malformed payload of a known log variant"));
+        }
+        let end = Trailer::start(remaining);
+        let kind = LogKind::Unknown {
+            source: source.to_owned(),
+            category: category.to_owned(),
+            variant: variant.to_owned(),
+            raw_payload: remaining[..end].trim().to_owned(),
+        };
+        Ok((&remaining[end..], kind))
+    }
+}
 impl Parsable for LogKind {
     type Parser = StripWhitespace<
         Alt<(
             Map<<SystemLogKind as Parsable>::Parser, fn(SystemLogKind) -> LogKind>,
             Map<<AppLogKind as Parsable>::Parser, fn(AppLogKind) -> LogKind>,
+            UnknownKind,
         )>,
     >;
     fn parser() -> Self::Parser {
-        alt2(
+        alt3(
             SystemLogKind::parser().map(LogKind::System as fn(SystemLogKind) -> _),
             AppLogKind::parser().map(LogKind::App as fn(AppLogKind) -> _),
+            UnknownKind,
         )
         .strip_ws()
     }
 }
-/// Строка логов, [лог](AppLogKind) с `request_id`
+/// A log line: a [log](AppLogKind) with its `request_id` and the
+/// `key=value` [attributes](Attributes) around it
 #[derive(Debug, Clone, PartialEq)]
 pub struct LogLine {
     pub kind: LogKind,
     pub request_id: std::num::NonZeroU32,
+    pub attributes: Attributes,
 }
 impl Parsable for LogLine {
     type Parser = Map<
-        Tuple<(
-            <LogKind as Parsable>::Parser,
-            StripWhitespace<Preceded<Tag, primitives::U32>>,
-        )>,
-        fn((LogKind, std::num::NonZeroU32)) -> Self,
+        Tuple<(<LogKind as Parsable>::Parser, Trailer)>,
+        fn((LogKind, (std::num::NonZeroU32, Attributes))) -> Self,
     >;
     fn parser() -> Self::Parser {
-        tuple2(
-            LogKind::parser(),
-            primitives::U32.preceded_by(tag("requestid=")).strip_ws(),
+        tuple2(LogKind::parser(), Trailer).map(
+            (|(kind, (request_id, attributes))| LogLine { kind, request_id, attributes })
+                as fn((LogKind, (std::num::NonZeroU32, Attributes))) -> _,
         )
-        .map((|(kind, request_id)| LogLine { kind, request_id })
-            as fn((LogKind, std::num::NonZeroU32)) -> _)
     }
 }
 
@@ -518,6 +1233,10 @@ mod tests {
         NonZeroU32::new(n).unwrap()
     }
 
+    fn bare(n: i64) -> Money {
+        Money::from_minor(n * 100, Currency::UNSPECIFIED)
+    }
+
     #[test]
     fn test_log_kind() {
         assert_eq!(
@@ -535,7 +1254,7 @@ mod tests {
                 )))
             ))
         );
-        assert_eq!(LogKind::parser().parse(r#"App::Trace Connect 30c305825b900077ae7f8259c1c328aa3e124a07f3bfbbf216dfc6e308beea6e474b9a7ea6c24d003a6ae4fcf04a9e6ef7c7f17cdaa0296f66a88036badcf01f053da806fad356546349deceff24621b895440d05a715b221af8e9e068073d6dec04f148175717d3c2d1b6af84e2375718ab4a1eba7e037c1c1d43b4cf422d6f2aa9194266f0a7544eaeff8167f0e993d0ea6a8ddb98bfeb8805635d5ea9f6592fd5297e6f83b6834190f99449722cd0de87a4c122f08bbe836fd3092e5f0d37a3057e90f3dd41048da66cad3e8fd3ef72a9d86ecd9009c2db996af29dc62af5ef5eb04d0e16ce8fcecba92a4a9888f52d5d575e7dbc302ed97dbf69df15bb4f5c5601d38fbe3bd89d88768a6aed11ce2f95a6ad30bb72e787bfb734701cea1f38168be44ea19d3e98dd3c953fdb9951ac9c6e221bb0f980d8f0952ac8127da5bda7077dd25ffc8e1515c529f29516dacec6be9c084e6c91698267b2aed9038eca5ebafad479c5fb17652e25bb5b85586fae645bd7c3253d9916c0af65a20253412d5484ac15d288c6ca8823469090ded5ce0975dada63653797129f0e926af6247b457b067db683e37d848e0acf30e5602b78f1848e8da4b640ed08b75f3519a40ec96b2be964234beab37759504376c6e5ebfacdc57e4c7a22cf1e879d7bde29a2dca5fe20420215b59d102fd016606c533e8e36f7da114910664bade9b295d9043a01bc0dc4d8abbc16b1cec7789d89e699ad99dae597c7f10d6f047efc011d67444695cb8e6e8b3dba17ccc693729d01312d0f12a3fc76e12c2e4984af5cb3049b9d8a13124a1f770e96bae1fb153ba4c91bea4fae6f03010275d5a9b14012bdd678e037934dc6762005de54b32a7684e03060d5cc80378e9bef05b8f0692202944401bd06e4553e4490a0e57c5a72fc8abb1f714e22ea950fb2f1de284d6ff3da435954de355c677f60db4252a510919cbe7dadfed0441cf125fd8894753af8114f2ddacb75c3daa460920fc47d285e59fe9110e4151fcef03fa246cd2dd9a4d573e1dbbda1c6968cf4f546289b95ce1bf0a55eea6531382826d4002bc46bf441ce16056d42b5a2079e299e3191c23a7604cde03de6081e06f93cfe632c9a6088cd328662d47a4954934832df5b5f3765dbe136114c73c55cb7ce639e5d40d1d1d8f540d3c8e1bc7423f032c0da5264353468f009c973eec0448e41f9289e8d9dadc68da77d3c3ab3a6477d44024f21fba0bd4477d81c6027657527aa0413b45f417cb7b3beea835a1d5d795414d38156324cb5c1303e9924dbe40cd497c4c23c221cb912058c939bea8b79b3fea360fecaa83375a9a84e338d9e863e8021ad2df4430b8dea0c1714e1bdc478f559705549ad738453ab65c0ffcc8cf0e3bafaf4afad75ecc4dfad0de0cfe27d50d656456ea6c361b76508357714079424"#), Ok(("", LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(Box::new(AuthData([0x30,0xc3,0x05,0x82,0x5b,0x90,0x00,0x77,0xae,0x7f,0x82,0x59,0xc1,0xc3,0x28,0xaa,0x3e,0x12,0x4a,0x07,0xf3,0xbf,0xbb,0xf2,0x16,0xdf,0xc6,0xe3,0x08,0xbe,0xea,0x6e,0x47,0x4b,0x9a,0x7e,0xa6,0xc2,0x4d,0x00,0x3a,0x6a,0xe4,0xfc,0xf0,0x4a,0x9e,0x6e,0xf7,0xc7,0xf1,0x7c,0xda,0xa0,0x29,0x6f,0x66,0xa8,0x80,0x36,0xba,0xdc,0xf0,0x1f,0x05,0x3d,0xa8,0x06,0xfa,0xd3,0x56,0x54,0x63,0x49,0xde,0xce,0xff,0x24,0x62,0x1b,0x89,0x54,0x40,0xd0,0x5a,0x71,0x5b,0x22,0x1a,0xf8,0xe9,0xe0,0x68,0x07,0x3d,0x6d,0xec,0x04,0xf1,0x48,0x17,0x57,0x17,0xd3,0xc2,0xd1,0xb6,0xaf,0x84,0xe2,0x37,0x57,0x18,0xab,0x4a,0x1e,0xba,0x7e,0x03,0x7c,0x1c,0x1d,0x43,0xb4,0xcf,0x42,0x2d,0x6f,0x2a,0xa9,0x19,0x42,0x66,0xf0,0xa7,0x54,0x4e,0xae,0xff,0x81,0x67,0xf0,0xe9,0x93,0xd0,0xea,0x6a,0x8d,0xdb,0x98,0xbf,0xeb,0x88,0x05,0x63,0x5d,0x5e,0xa9,0xf6,0x59,0x2f,0xd5,0x29,0x7e,0x6f,0x83,0xb6,0x83,0x41,0x90,0xf9,0x94,0x49,0x72,0x2c,0xd0,0xde,0x87,0xa4,0xc1,0x22,0xf0,0x8b,0xbe,0x83,0x6f,0xd3,0x09,0x2e,0x5f,0x0d,0x37,0xa3,0x05,0x7e,0x90,0xf3,0xdd,0x41,0x04,0x8d,0xa6,0x6c,0xad,0x3e,0x8f,0xd3,0xef,0x72,0xa9,0xd8,0x6e,0xcd,0x90,0x09,0xc2,0xdb,0x99,0x6a,0xf2,0x9d,0xc6,0x2a,0xf5,0xef,0x5e,0xb0,0x4d,0x0e,0x16,0xce,0x8f,0xce,0xcb,0xa9,0x2a,0x4a,0x98,0x88,0xf5,0x2d,0x5d,0x57,0x5e,0x7d,0xbc,0x30,0x2e,0xd9,0x7d,0xbf,0x69,0xdf,0x15,0xbb,0x4f,0x5c,0x56,0x01,0xd3,0x8f,0xbe,0x3b,0xd8,0x9d,0x88,0x76,0x8a,0x6a,0xed,0x11,0xce,0x2f,0x95,0xa6,0xad,0x30,0xbb,0x72,0xe7,0x87,0xbf,0xb7,0x34,0x70,0x1c,0xea,0x1f,0x38,0x16,0x8b,0xe4,0x4e,0xa1,0x9d,0x3e,0x98,0xdd,0x3c,0x95,0x3f,0xdb,0x99,0x51,0xac,0x9c,0x6e,0x22,0x1b,0xb0,0xf9,0x80,0xd8,0xf0,0x95,0x2a,0xc8,0x12,0x7d,0xa5,0xbd,0xa7,0x07,0x7d,0xd2,0x5f,0xfc,0x8e,0x15,0x15,0xc5,0x29,0xf2,0x95,0x16,0xda,0xce,0xc6,0xbe,0x9c,0x08,0x4e,0x6c,0x91,0x69,0x82,0x67,0xb2,0xae,0xd9,0x03,0x8e,0xca,0x5e,0xba,0xfa,0xd4,0x79,0xc5,0xfb,0x17,0x65,0x2e,0x25,0xbb,0x5b,0x85,0x58,0x6f,0xae,0x64,0x5b,0xd7,0xc3,0x25,0x3d,0x99,0x16,0xc0,0xaf,0x65,0xa2,0x02,0x53,0x41,0x2d,0x54,0x84,0xac,0x15,0xd2,0x88,0xc6,0xca,0x88,0x23,0x46,0x90,0x90,0xde,0xd5,0xce,0x09,0x75,0xda,0xda,0x63,0x65,0x37,0x97,0x12,0x9f,0x0e,0x92,0x6a,0xf6,0x24,0x7b,0x45,0x7b,0x06,0x7d,0xb6,0x83,0xe3,0x7d,0x84,0x8e,0x0a,0xcf,0x30,0xe5,0x60,0x2b,0x78,0xf1,0x84,0x8e,0x8d,0xa4,0xb6,0x40,0xed,0x08,0xb7,0x5f,0x35,0x19,0xa4,0x0e,0xc9,0x6b,0x2b,0xe9,0x64,0x23,0x4b,0xea,0xb3,0x77,0x59,0x50,0x43,0x76,0xc6,0xe5,0xeb,0xfa,0xcd,0xc5,0x7e,0x4c,0x7a,0x22,0xcf,0x1e,0x87,0x9d,0x7b,0xde,0x29,0xa2,0xdc,0xa5,0xfe,0x20,0x42,0x02,0x15,0xb5,0x9d,0x10,0x2f,0xd0,0x16,0x60,0x6c,0x53,0x3e,0x8e,0x36,0xf7,0xda,0x11,0x49,0x10,0x66,0x4b,0xad,0xe9,0xb2,0x95,0xd9,0x04,0x3a,0x01,0xbc,0x0d,0xc4,0xd8,0xab,0xbc,0x16,0xb1,0xce,0xc7,0x78,0x9d,0x89,0xe6,0x99,0xad,0x99,0xda,0xe5,0x97,0xc7,0xf1,0x0d,0x6f,0x04,0x7e,0xfc,0x01,0x1d,0x67,0x44,0x46,0x95,0xcb,0x8e,0x6e,0x8b,0x3d,0xba,0x17,0xcc,0xc6,0x93,0x72,0x9d,0x01,0x31,0x2d,0x0f,0x12,0xa3,0xfc,0x76,0xe1,0x2c,0x2e,0x49,0x84,0xaf,0x5c,0xb3,0x04,0x9b,0x9d,0x8a,0x13,0x12,0x4a,0x1f,0x77,0x0e,0x96,0xba,0xe1,0xfb,0x15,0x3b,0xa4,0xc9,0x1b,0xea,0x4f,0xae,0x6f,0x03,0x01,0x02,0x75,0xd5,0xa9,0xb1,0x40,0x12,0xbd,0xd6,0x78,0xe0,0x37,0x93,0x4d,0xc6,0x76,0x20,0x05,0xde,0x54,0xb3,0x2a,0x76,0x84,0xe0,0x30,0x60,0xd5,0xcc,0x80,0x37,0x8e,0x9b,0xef,0x05,0xb8,0xf0,0x69,0x22,0x02,0x94,0x44,0x01,0xbd,0x06,0xe4,0x55,0x3e,0x44,0x90,0xa0,0xe5,0x7c,0x5a,0x72,0xfc,0x8a,0xbb,0x1f,0x71,0x4e,0x22,0xea,0x95,0x0f,0xb2,0xf1,0xde,0x28,0x4d,0x6f,0xf3,0xda,0x43,0x59,0x54,0xde,0x35,0x5c,0x67,0x7f,0x60,0xdb,0x42,0x52,0xa5,0x10,0x91,0x9c,0xbe,0x7d,0xad,0xfe,0xd0,0x44,0x1c,0xf1,0x25,0xfd,0x88,0x94,0x75,0x3a,0xf8,0x11,0x4f,0x2d,0xda,0xcb,0x75,0xc3,0xda,0xa4,0x60,0x92,0x0f,0xc4,0x7d,0x28,0x5e,0x59,0xfe,0x91,0x10,0xe4,0x15,0x1f,0xce,0xf0,0x3f,0xa2,0x46,0xcd,0x2d,0xd9,0xa4,0xd5,0x73,0xe1,0xdb,0xbd,0xa1,0xc6,0x96,0x8c,0xf4,0xf5,0x46,0x28,0x9b,0x95,0xce,0x1b,0xf0,0xa5,0x5e,0xea,0x65,0x31,0x38,0x28,0x26,0xd4,0x00,0x2b,0xc4,0x6b,0xf4,0x41,0xce,0x16,0x05,0x6d,0x42,0xb5,0xa2,0x07,0x9e,0x29,0x9e,0x31,0x91,0xc2,0x3a,0x76,0x04,0xcd,0xe0,0x3d,0xe6,0x08,0x1e,0x06,0xf9,0x3c,0xfe,0x63,0x2c,0x9a,0x60,0x88,0xcd,0x32,0x86,0x62,0xd4,0x7a,0x49,0x54,0x93,0x48,0x32,0xdf,0x5b,0x5f,0x37,0x65,0xdb,0xe1,0x36,0x11,0x4c,0x73,0xc5,0x5c,0xb7,0xce,0x63,0x9e,0x5d,0x40,0xd1,0xd1,0xd8,0xf5,0x40,0xd3,0xc8,0xe1,0xbc,0x74,0x23,0xf0,0x32,0xc0,0xda,0x52,0x64,0x35,0x34,0x68,0xf0,0x09,0xc9,0x73,0xee,0xc0,0x44,0x8e,0x41,0xf9,0x28,0x9e,0x8d,0x9d,0xad,0xc6,0x8d,0xa7,0x7d,0x3c,0x3a,0xb3,0xa6,0x47,0x7d,0x44,0x02,0x4f,0x21,0xfb,0xa0,0xbd,0x44,0x77,0xd8,0x1c,0x60,0x27,0x65,0x75,0x27,0xaa,0x04,0x13,0xb4,0x5f,0x41,0x7c,0xb7,0xb3,0xbe,0xea,0x83,0x5a,0x1d,0x5d,0x79,0x54,0x14,0xd3,0x81,0x56,0x32,0x4c,0xb5,0xc1,0x30,0x3e,0x99,0x24,0xdb,0xe4,0x0c,0xd4,0x97,0xc4,0xc2,0x3c,0x22,0x1c,0xb9,0x12,0x05,0x8c,0x93,0x9b,0xea,0x8b,0x79,0xb3,0xfe,0xa3,0x60,0xfe,0xca,0xa8,0x33,0x75,0xa9,0xa8,0x4e,0x33,0x8d,0x9e,0x86,0x3e,0x80,0x21,0xad,0x2d,0xf4,0x43,0x0b,0x8d,0xea,0x0c,0x17,0x14,0xe1,0xbd,0xc4,0x78,0xf5,0x59,0x70,0x55,0x49,0xad,0x73,0x84,0x53,0xab,0x65,0xc0,0xff,0xcc,0x8c,0xf0,0xe3,0xba,0xfa,0xf4,0xaf,0xad,0x75,0xec,0xc4,0xdf,0xad,0x0d,0xe0,0xcf,0xe2,0x7d,0x50,0xd6,0x56,0x45,0x6e,0xa6,0xc3,0x61,0xb7,0x65,0x08,0x35,0x77,0x14,0x07,0x94,0x24]))))))));
+        assert_eq!(LogKind::parser().parse(r#"App::Trace Connect 30c305825b900077ae7f8259c1c328aa3e124a07f3bfbbf216dfc6e308beea6e474b9a7ea6c24d003a6ae4fcf04a9e6ef7c7f17cdaa0296f66a88036badcf01f053da806fad356546349deceff24621b895440d05a715b221af8e9e068073d6dec04f148175717d3c2d1b6af84e2375718ab4a1eba7e037c1c1d43b4cf422d6f2aa9194266f0a7544eaeff8167f0e993d0ea6a8ddb98bfeb8805635d5ea9f6592fd5297e6f83b6834190f99449722cd0de87a4c122f08bbe836fd3092e5f0d37a3057e90f3dd41048da66cad3e8fd3ef72a9d86ecd9009c2db996af29dc62af5ef5eb04d0e16ce8fcecba92a4a9888f52d5d575e7dbc302ed97dbf69df15bb4f5c5601d38fbe3bd89d88768a6aed11ce2f95a6ad30bb72e787bfb734701cea1f38168be44ea19d3e98dd3c953fdb9951ac9c6e221bb0f980d8f0952ac8127da5bda7077dd25ffc8e1515c529f29516dacec6be9c084e6c91698267b2aed9038eca5ebafad479c5fb17652e25bb5b85586fae645bd7c3253d9916c0af65a20253412d5484ac15d288c6ca8823469090ded5ce0975dada63653797129f0e926af6247b457b067db683e37d848e0acf30e5602b78f1848e8da4b640ed08b75f3519a40ec96b2be964234beab37759504376c6e5ebfacdc57e4c7a22cf1e879d7bde29a2dca5fe20420215b59d102fd016606c533e8e36f7da114910664bade9b295d9043a01bc0dc4d8abbc16b1cec7789d89e699ad99dae597c7f10d6f047efc011d67444695cb8e6e8b3dba17ccc693729d01312d0f12a3fc76e12c2e4984af5cb3049b9d8a13124a1f770e96bae1fb153ba4c91bea4fae6f03010275d5a9b14012bdd678e037934dc6762005de54b32a7684e03060d5cc80378e9bef05b8f0692202944401bd06e4553e4490a0e57c5a72fc8abb1f714e22ea950fb2f1de284d6ff3da435954de355c677f60db4252a510919cbe7dadfed0441cf125fd8894753af8114f2ddacb75c3daa460920fc47d285e59fe9110e4151fcef03fa246cd2dd9a4d573e1dbbda1c6968cf4f546289b95ce1bf0a55eea6531382826d4002bc46bf441ce16056d42b5a2079e299e3191c23a7604cde03de6081e06f93cfe632c9a6088cd328662d47a4954934832df5b5f3765dbe136114c73c55cb7ce639e5d40d1d1d8f540d3c8e1bc7423f032c0da5264353468f009c973eec0448e41f9289e8d9dadc68da77d3c3ab3a6477d44024f21fba0bd4477d81c6027657527aa0413b45f417cb7b3beea835a1d5d795414d38156324cb5c1303e9924dbe40cd497c4c23c221cb912058c939bea8b79b3fea360fecaa83375a9a84e338d9e863e8021ad2df4430b8dea0c1714e1bdc478f559705549ad738453ab65c0ffcc8cf0e3bafaf4afad75ecc4dfad0de0cfe27d50d656456ea6c361b76508357714079424"#), Ok(("", LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(Box::new(AuthData(vec![0x30,0xc3,0x05,0x82,0x5b,0x90,0x00,0x77,0xae,0x7f,0x82,0x59,0xc1,0xc3,0x28,0xaa,0x3e,0x12,0x4a,0x07,0xf3,0xbf,0xbb,0xf2,0x16,0xdf,0xc6,0xe3,0x08,0xbe,0xea,0x6e,0x47,0x4b,0x9a,0x7e,0xa6,0xc2,0x4d,0x00,0x3a,0x6a,0xe4,0xfc,0xf0,0x4a,0x9e,0x6e,0xf7,0xc7,0xf1,0x7c,0xda,0xa0,0x29,0x6f,0x66,0xa8,0x80,0x36,0xba,0xdc,0xf0,0x1f,0x05,0x3d,0xa8,0x06,0xfa,0xd3,0x56,0x54,0x63,0x49,0xde,0xce,0xff,0x24,0x62,0x1b,0x89,0x54,0x40,0xd0,0x5a,0x71,0x5b,0x22,0x1a,0xf8,0xe9,0xe0,0x68,0x07,0x3d,0x6d,0xec,0x04,0xf1,0x48,0x17,0x57,0x17,0xd3,0xc2,0xd1,0xb6,0xaf,0x84,0xe2,0x37,0x57,0x18,0xab,0x4a,0x1e,0xba,0x7e,0x03,0x7c,0x1c,0x1d,0x43,0xb4,0xcf,0x42,0x2d,0x6f,0x2a,0xa9,0x19,0x42,0x66,0xf0,0xa7,0x54,0x4e,0xae,0xff,0x81,0x67,0xf0,0xe9,0x93,0xd0,0xea,0x6a,0x8d,0xdb,0x98,0xbf,0xeb,0x88,0x05,0x63,0x5d,0x5e,0xa9,0xf6,0x59,0x2f,0xd5,0x29,0x7e,0x6f,0x83,0xb6,0x83,0x41,0x90,0xf9,0x94,0x49,0x72,0x2c,0xd0,0xde,0x87,0xa4,0xc1,0x22,0xf0,0x8b,0xbe,0x83,0x6f,0xd3,0x09,0x2e,0x5f,0x0d,0x37,0xa3,0x05,0x7e,0x90,0xf3,0xdd,0x41,0x04,0x8d,0xa6,0x6c,0xad,0x3e,0x8f,0xd3,0xef,0x72,0xa9,0xd8,0x6e,0xcd,0x90,0x09,0xc2,0xdb,0x99,0x6a,0xf2,0x9d,0xc6,0x2a,0xf5,0xef,0x5e,0xb0,0x4d,0x0e,0x16,0xce,0x8f,0xce,0xcb,0xa9,0x2a,0x4a,0x98,0x88,0xf5,0x2d,0x5d,0x57,0x5e,0x7d,0xbc,0x30,0x2e,0xd9,0x7d,0xbf,0x69,0xdf,0x15,0xbb,0x4f,0x5c,0x56,0x01,0xd3,0x8f,0xbe,0x3b,0xd8,0x9d,0x88,0x76,0x8a,0x6a,0xed,0x11,0xce,0x2f,0x95,0xa6,0xad,0x30,0xbb,0x72,0xe7,0x87,0xbf,0xb7,0x34,0x70,0x1c,0xea,0x1f,0x38,0x16,0x8b,0xe4,0x4e,0xa1,0x9d,0x3e,0x98,0xdd,0x3c,0x95,0x3f,0xdb,0x99,0x51,0xac,0x9c,0x6e,0x22,0x1b,0xb0,0xf9,0x80,0xd8,0xf0,0x95,0x2a,0xc8,0x12,0x7d,0xa5,0xbd,0xa7,0x07,0x7d,0xd2,0x5f,0xfc,0x8e,0x15,0x15,0xc5,0x29,0xf2,0x95,0x16,0xda,0xce,0xc6,0xbe,0x9c,0x08,0x4e,0x6c,0x91,0x69,0x82,0x67,0xb2,0xae,0xd9,0x03,0x8e,0xca,0x5e,0xba,0xfa,0xd4,0x79,0xc5,0xfb,0x17,0x65,0x2e,0x25,0xbb,0x5b,0x85,0x58,0x6f,0xae,0x64,0x5b,0xd7,0xc3,0x25,0x3d,0x99,0x16,0xc0,0xaf,0x65,0xa2,0x02,0x53,0x41,0x2d,0x54,0x84,0xac,0x15,0xd2,0x88,0xc6,0xca,0x88,0x23,0x46,0x90,0x90,0xde,0xd5,0xce,0x09,0x75,0xda,0xda,0x63,0x65,0x37,0x97,0x12,0x9f,0x0e,0x92,0x6a,0xf6,0x24,0x7b,0x45,0x7b,0x06,0x7d,0xb6,0x83,0xe3,0x7d,0x84,0x8e,0x0a,0xcf,0x30,0xe5,0x60,0x2b,0x78,0xf1,0x84,0x8e,0x8d,0xa4,0xb6,0x40,0xed,0x08,0xb7,0x5f,0x35,0x19,0xa4,0x0e,0xc9,0x6b,0x2b,0xe9,0x64,0x23,0x4b,0xea,0xb3,0x77,0x59,0x50,0x43,0x76,0xc6,0xe5,0xeb,0xfa,0xcd,0xc5,0x7e,0x4c,0x7a,0x22,0xcf,0x1e,0x87,0x9d,0x7b,0xde,0x29,0xa2,0xdc,0xa5,0xfe,0x20,0x42,0x02,0x15,0xb5,0x9d,0x10,0x2f,0xd0,0x16,0x60,0x6c,0x53,0x3e,0x8e,0x36,0xf7,0xda,0x11,0x49,0x10,0x66,0x4b,0xad,0xe9,0xb2,0x95,0xd9,0x04,0x3a,0x01,0xbc,0x0d,0xc4,0xd8,0xab,0xbc,0x16,0xb1,0xce,0xc7,0x78,0x9d,0x89,0xe6,0x99,0xad,0x99,0xda,0xe5,0x97,0xc7,0xf1,0x0d,0x6f,0x04,0x7e,0xfc,0x01,0x1d,0x67,0x44,0x46,0x95,0xcb,0x8e,0x6e,0x8b,0x3d,0xba,0x17,0xcc,0xc6,0x93,0x72,0x9d,0x01,0x31,0x2d,0x0f,0x12,0xa3,0xfc,0x76,0xe1,0x2c,0x2e,0x49,0x84,0xaf,0x5c,0xb3,0x04,0x9b,0x9d,0x8a,0x13,0x12,0x4a,0x1f,0x77,0x0e,0x96,0xba,0xe1,0xfb,0x15,0x3b,0xa4,0xc9,0x1b,0xea,0x4f,0xae,0x6f,0x03,0x01,0x02,0x75,0xd5,0xa9,0xb1,0x40,0x12,0xbd,0xd6,0x78,0xe0,0x37,0x93,0x4d,0xc6,0x76,0x20,0x05,0xde,0x54,0xb3,0x2a,0x76,0x84,0xe0,0x30,0x60,0xd5,0xcc,0x80,0x37,0x8e,0x9b,0xef,0x05,0xb8,0xf0,0x69,0x22,0x02,0x94,0x44,0x01,0xbd,0x06,0xe4,0x55,0x3e,0x44,0x90,0xa0,0xe5,0x7c,0x5a,0x72,0xfc,0x8a,0xbb,0x1f,0x71,0x4e,0x22,0xea,0x95,0x0f,0xb2,0xf1,0xde,0x28,0x4d,0x6f,0xf3,0xda,0x43,0x59,0x54,0xde,0x35,0x5c,0x67,0x7f,0x60,0xdb,0x42,0x52,0xa5,0x10,0x91,0x9c,0xbe,0x7d,0xad,0xfe,0xd0,0x44,0x1c,0xf1,0x25,0xfd,0x88,0x94,0x75,0x3a,0xf8,0x11,0x4f,0x2d,0xda,0xcb,0x75,0xc3,0xda,0xa4,0x60,0x92,0x0f,0xc4,0x7d,0x28,0x5e,0x59,0xfe,0x91,0x10,0xe4,0x15,0x1f,0xce,0xf0,0x3f,0xa2,0x46,0xcd,0x2d,0xd9,0xa4,0xd5,0x73,0xe1,0xdb,0xbd,0xa1,0xc6,0x96,0x8c,0xf4,0xf5,0x46,0x28,0x9b,0x95,0xce,0x1b,0xf0,0xa5,0x5e,0xea,0x65,0x31,0x38,0x28,0x26,0xd4,0x00,0x2b,0xc4,0x6b,0xf4,0x41,0xce,0x16,0x05,0x6d,0x42,0xb5,0xa2,0x07,0x9e,0x29,0x9e,0x31,0x91,0xc2,0x3a,0x76,0x04,0xcd,0xe0,0x3d,0xe6,0x08,0x1e,0x06,0xf9,0x3c,0xfe,0x63,0x2c,0x9a,0x60,0x88,0xcd,0x32,0x86,0x62,0xd4,0x7a,0x49,0x54,0x93,0x48,0x32,0xdf,0x5b,0x5f,0x37,0x65,0xdb,0xe1,0x36,0x11,0x4c,0x73,0xc5,0x5c,0xb7,0xce,0x63,0x9e,0x5d,0x40,0xd1,0xd1,0xd8,0xf5,0x40,0xd3,0xc8,0xe1,0xbc,0x74,0x23,0xf0,0x32,0xc0,0xda,0x52,0x64,0x35,0x34,0x68,0xf0,0x09,0xc9,0x73,0xee,0xc0,0x44,0x8e,0x41,0xf9,0x28,0x9e,0x8d,0x9d,0xad,0xc6,0x8d,0xa7,0x7d,0x3c,0x3a,0xb3,0xa6,0x47,0x7d,0x44,0x02,0x4f,0x21,0xfb,0xa0,0xbd,0x44,0x77,0xd8,0x1c,0x60,0x27,0x65,0x75,0x27,0xaa,0x04,0x13,0xb4,0x5f,0x41,0x7c,0xb7,0xb3,0xbe,0xea,0x83,0x5a,0x1d,0x5d,0x79,0x54,0x14,0xd3,0x81,0x56,0x32,0x4c,0xb5,0xc1,0x30,0x3e,0x99,0x24,0xdb,0xe4,0x0c,0xd4,0x97,0xc4,0xc2,0x3c,0x22,0x1c,0xb9,0x12,0x05,0x8c,0x93,0x9b,0xea,0x8b,0x79,0xb3,0xfe,0xa3,0x60,0xfe,0xca,0xa8,0x33,0x75,0xa9,0xa8,0x4e,0x33,0x8d,0x9e,0x86,0x3e,0x80,0x21,0xad,0x2d,0xf4,0x43,0x0b,0x8d,0xea,0x0c,0x17,0x14,0xe1,0xbd,0xc4,0x78,0xf5,0x59,0x70,0x55,0x49,0xad,0x73,0x84,0x53,0xab,0x65,0xc0,0xff,0xcc,0x8c,0xf0,0xe3,0xba,0xfa,0xf4,0xaf,0xad,0x75,0xec,0xc4,0xdf,0xad,0x0d,0xe0,0xcf,0xe2,0x7d,0x50,0xd6,0x56,0x45,0x6e,0xa6,0xc3,0x61,0xb7,0x65,0x08,0x35,0x77,0x14,0x07,0x94,0x24]))))))));
         assert_eq!(
             LogKind::parser().parse(
                 r#"App::Journal CreateUser {"user_id": "Steeve", "authorized_capital": 10000,}"#
@@ -544,7 +1263,7 @@ mod tests {
                 "",
                 LogKind::App(AppLogKind::Journal(AppLogJournalKind::CreateUser {
                     user_id: UserId("Steeve".into()),
-                    authorized_capital: nz(10_000)
+                    authorized_capital: bare(10_000)
                 }))
             ))
         );
@@ -557,7 +1276,7 @@ mod tests {
                 }))
             ))
         );
-        assert_eq!(LogKind::parser().parse(r#"App::Journal RegisterAsset {"asset_id": "bayc", "liquidity": 100000000, "user_id": "Steeve",}"#), Ok(("", LogKind::App(AppLogKind::Journal(AppLogJournalKind::RegisterAsset{asset_id: AssetId("bayc".into()), user_id: UserId("Steeve".into()), liquidity: nz(100_000_000)})))));
+        assert_eq!(LogKind::parser().parse(r#"App::Journal RegisterAsset {"asset_id": "bayc", "liquidity": 100000000, "user_id": "Steeve",}"#), Ok(("", LogKind::App(AppLogKind::Journal(AppLogJournalKind::RegisterAsset{asset_id: AssetId("bayc".into()), user_id: UserId("Steeve".into()), liquidity: bare(100_000_000)})))));
         assert_eq!(
             LogKind::parser()
                 .parse(r#"App::Journal DepositCash UserCash{"user_id": "Steeve", "count": 10,}"#),
@@ -566,7 +1285,7 @@ mod tests {
                 LogKind::App(AppLogKind::Journal(AppLogJournalKind::DepositCash(
                     UserCash {
                         user_id: UserId("Steeve".into()),
-                        count: nz(10)
+                        count: bare(10)
                     }
                 )))
             ))
@@ -584,7 +1303,7 @@ mod tests {
                 LogKind::App(AppLogKind::Journal(AppLogJournalKind::WithdrawCash(
                     UserCash {
                         user_id: UserId("Alice".into()),
-                        count: nz(500)
+                        count: bare(500)
                     }
                 )))
             ))
@@ -600,7 +1319,7 @@ mod tests {
                 "",
                 AppLogJournalKind::WithdrawCash(UserCash {
                     user_id: UserId("Bob".into()),
-                    count: nz(100)
+                    count: bare(100)
                 })
             ))
         );
@@ -647,7 +1366,177 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unknown_kind() {
+        let unknown = |source: &str, category: &str, variant: &str, raw_payload: &str| LogKind::Unknown {
+            source: source.into(),
+            category: category.into(),
+            variant: variant.into(),
+            raw_payload: raw_payload.into(),
+        };
+        assert_eq!(
+            LogLine::parser().parse(r#"App::Trace Heartbeat {"uptime": 5, "note": "requestid=1"} requestid=12"#),
+            Ok(("", LogLine {
+                kind: unknown("App", "Trace", "Heartbeat", r#"{"uptime": 5, "note": "requestid=1"}"#),
+                request_id: nz(12),
+                attributes: Attributes::default(),
+            }))
+        );
+        assert_eq!(
+            LogLine::parser().parse("System::Audit DiskFull requestid=3"),
+            Ok(("", LogLine {
+                kind: unknown("System", "Audit", "DiskFull", ""),
+                request_id: nz(3),
+                attributes: Attributes::default(),
+            }))
+        );
+        // Known variants with a bad payload are still errors, not unknown lines.
+        assert_eq!(
+            LogKind::parser().parse(r#"App::Journal BuyAsset {"user_id": 1}"#),
+            Err(ParseError::UnexpectedInput("malformed payload of a known log variant"))
+        );
+        assert!(LogLine::parser().parse("System::Warn requestid=3").is_err());
+        assert!(LogLine::parser().parse("Warn DiskFull requestid=3").is_err());
+
+        // Well-formed payloads of unknown lines can still be queried.
+        let heartbeat = unknown("App", "Trace", "Heartbeat", r#"Stats{"uptime": 5, "peers": ["a", "b"],}"#);
+        let value = heartbeat.payload_value().unwrap();
+        assert_eq!(value.query("peers.1").and_then(Value::as_str), Some("b"));
+        assert_eq!(unknown("App", "Trace", "Heartbeat", "up 5s").payload_value(), None);
+        // Attributes before `requestid` and quoted `requestid=` text stay out of the payload.
+        let (_, log) = LogLine::parser()
+            .parse(r#"Db::Warn Slow {"ms":900} session=Bob requestid=3 note="see requestid=4""#)
+            .unwrap();
+        assert_eq!(log.kind, unknown("Db", "Warn", "Slow", r#"{"ms":900}"#));
+        assert_eq!(log.request_id, nz(3));
+        assert_eq!(log.attributes.get("session").map(|a| a.text()), Some("Bob"));
+        assert_eq!(log.attributes.get("note").map(|a| a.text()), Some("see requestid=4"));
+        let nested = format!("{}1{}", "[".repeat(10_000), "]".repeat(10_000));
+        assert_eq!(unknown("App", "Trace", "Heartbeat", &nested).payload_value(), None);
+
+        let log = LogLine::parser().parse(r#"App::Trace Heartbeat "user_id":"Bob" requestid=2"#).unwrap().1;
+        assert_eq!(log.kind.variant(), "Heartbeat");
+        assert_eq!(log.to_log_string(), r#"App::Trace Heartbeat "user_id":"Bob" requestid=2"#);
+        let mut kind = log.kind;
+        assert_eq!(kind.payload_mut().map(|p| p.as_str()), Some(r#""user_id":"Bob""#));
+    }
+
+    #[test]
+    fn test_severity_kinds() {
+        let parse = |line: &str| LogLine::parser().parse(line).map(|(_, log)| log.kind);
+        let free_text = |variant: &str, text: &str| FreeText {
+            variant: variant.into(),
+            text: text.into(),
+        };
+        // Every variant keeps its text verbatim, quotes included.
+        assert_eq!(
+            parse(r#"System::Warn DiskFull "90% used" requestid=1"#),
+            Ok(LogKind::System(SystemLogKind::Warn(free_text("DiskFull", r#""90% used""#))))
+        );
+        let mut kind = parse("App::Debug CacheMiss key 42 requestid=3").unwrap();
+        assert_eq!(kind, LogKind::App(AppLogKind::Debug(free_text("CacheMiss", "key 42"))));
+        assert_eq!((kind.category(), kind.variant(), kind.message()), ("Debug", "CacheMiss", Some("key 42")));
+        assert_eq!(kind.payload_mut().map(|p| p.as_str()), Some("key 42"));
+        assert_eq!(
+            parse("System::Info Ready requestid=4"),
+            Ok(LogKind::System(SystemLogKind::Info(free_text("Ready", ""))))
+        );
+
+        // The text ends where the trailer starts, attributes before `requestid` included.
+        let (_, log) = LogLine::parser().parse(r#"App::Warn Throttled "slow" session=Bob requestid=8"#).unwrap();
+        assert_eq!(log.kind, LogKind::App(AppLogKind::Warn(free_text("Throttled", r#""slow""#))));
+        assert_eq!(log.attributes.get("session").map(|a| a.text()), Some("Bob"));
+        let (_, log) = LogLine::parser()
+            .parse(r#"App::Fatal Halt "see requestid=3 x=1" requestid=8 note="requestid=2""#)
+            .unwrap();
+        assert_eq!(log.kind.message(), Some(r#""see requestid=3 x=1""#));
+        assert_eq!(log.request_id, nz(8));
+        assert_eq!(log.attributes.get("note").map(|a| a.text()), Some("requestid=2"));
+        let (_, log) = LogLine::parser().parse("App::Debug Cache key=42 hit requestid=5").unwrap();
+        assert_eq!(log.kind.message(), Some("key=42 hit"));
+        assert!(log.attributes.is_empty());
+
+        for line in [
+            r#"System::Debug Config "retries=3" requestid=1"#,
+            r#"App::Info Shutdown "bye" requestid=1"#,
+            r#"App::Warn Retry "attempt 2" requestid=1"#,
+            "App::Warn Throttled requestid=1",
+            "System::Fatal Halt cpu 3 requestid=1",
+        ] {
+            let (_, log) = LogLine::parser().parse(line).unwrap();
+            assert_eq!(log.to_log_string(), line);
+        }
+    }
+
+    #[test]
+    fn test_severity_ordering() {
+        let severity = |line: &str| LogLine::parser().parse(line).unwrap().1.kind.severity();
+        assert_eq!(severity(r#"System::Trace GetResponse "HTTP 200" requestid=1"#), Severity::Trace);
+        assert_eq!(severity(r#"App::Error LackOf "milk" requestid=1"#), Severity::Error);
+        assert_eq!(severity(r#"App::Journal DeleteUser {"user_id":"Bob",} requestid=1"#), Severity::Info);
+        assert_eq!(severity("App::Warn Throttled requestid=1"), Severity::Warn);
+        // Unknown categories fall back to their name, else Info.
+        assert_eq!(severity("Db::Fatal Crash requestid=1"), Severity::Fatal);
+        assert_eq!(severity("System::Audit Login requestid=1"), Severity::Info);
+        assert!(Severity::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(Severity::of_category("Warn"), Some(Severity::Warn));
+        assert_eq!(Severity::of_category("Audit"), None);
+    }
+
+    #[test]
+    fn test_log_line_attributes() {
+        let line = r#"App::Journal SellAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":3,},} session=s1 requestid=9 latency_ms=12"#;
+        let (remaining, log) = LogLine::parser().parse(line).unwrap();
+        assert_eq!(remaining, "");
+        assert!(matches!(log.kind, LogKind::App(AppLogKind::Journal(AppLogJournalKind::SellAsset(_)))));
+        assert_eq!(log.request_id, nz(9));
+        assert_eq!(log.attributes.get("session").map(|a| a.text()), Some("s1"));
+        assert_eq!(log.attributes.get("latency_ms").map(|a| &a.value), Some(&AttributeValue::U64(12)));
+        // Attributes are written back in order, after `requestid`.
+        let emitted = log.to_log_string();
+        assert!(emitted.ends_with(" requestid=9 session=s1 latency_ms=12"), "{emitted}");
+        assert_eq!(LogLine::parser().parse(&emitted), Ok(("", log.clone())));
+        assert!(log.to_string().starts_with("[req=9 session=s1 latency_ms=12] "));
+
+        // The payload of an unknown variant ends where the trailer starts, so
+        // attributes on both sides of `requestid` are split off.
+        let (_, mut log) = LogLine::parser()
+            .parse("App::Trace Heartbeat up 5s uptime=5 requestid=2 exchange=Jupiter")
+            .unwrap();
+        assert_eq!(log.kind.payload_mut().map(|p| p.clone()), Some("up 5s".into()));
+        assert_eq!(log.attributes.get("exchange").map(|a| a.text()), Some("Jupiter"));
+        assert_eq!(log.attributes.get("uptime").map(|a| &a.value), Some(&AttributeValue::U64(5)));
+    }
+
+    #[test]
+    fn test_emit_roundtrip_example_log() {
+        for line in include_str!("../../example.log").lines().filter(|l| !l.trim().is_empty()) {
+            let (_, parsed) = LogLine::parser().parse(line).unwrap();
+            let emitted = parsed.to_log_string();
+            assert_eq!(LogLine::parser().parse(&emitted), Ok(("", parsed)), "{emitted}");
+        }
+        assert_eq!(
+            LogLine::parser()
+                .parse(r#"App::Journal CreateUser {"authorized_capital": 10, "user_id": "Bob",} requestid=3"#)
+                .unwrap()
+                .1
+                .to_log_string(),
+            r#"App::Journal CreateUser {"user_id":"Bob","authorized_capital":10,} requestid=3"#
+        );
+    }
+
     proptest! {
+        #[test]
+        fn test_emit_roundtrip_payload(msg in ".*", id in 1u32..) {
+            let log = LogLine {
+                kind: LogKind::App(AppLogKind::Trace(AppLogTraceKind::SendRequest(msg))),
+                request_id: nz(id),
+                attributes: Attributes::default(),
+            };
+            let emitted = log.to_log_string();
+            prop_assert_eq!(LogLine::parser().parse(&emitted), Ok(("", log)));
+        }
+
         #[test]
         fn test_logline_parser_no_panic(s in ".*") {
             // Parser must never panic on arbitrary input; errors are fine.
@@ -0,0 +1,490 @@
+use super::combinators::*;
+use super::domain::*;
+use super::grammar::{GrammarError, LogGrammar};
+
+/// Type of a key of a schema operation, as written after the colon in
+/// `"count: u32"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    UserId,
+    AssetId,
+    U32,
+    U64,
+    I64,
+    F64,
+    Money,
+    Backet,
+    String,
+}
+
+impl FieldType {
+    const ALL: [FieldType; 9] = [
+        FieldType::UserId,
+        FieldType::AssetId,
+        FieldType::U32,
+        FieldType::U64,
+        FieldType::I64,
+        FieldType::F64,
+        FieldType::Money,
+        FieldType::Backet,
+        FieldType::String,
+    ];
+
+    /// Name of the type in a schema.
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldType::UserId => "UserId",
+            FieldType::AssetId => "AssetId",
+            FieldType::U32 => "u32",
+            FieldType::U64 => "u64",
+            FieldType::I64 => "i64",
+            FieldType::F64 => "f64",
+            FieldType::Money => "Money",
+            FieldType::Backet => "Backet",
+            FieldType::String => "String",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|ty| ty.name() == name)
+    }
+}
+
+/// Reads a value of the type
+impl Parser for FieldType {
+    type Dest = FieldValue;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        match self {
+            FieldType::UserId => UserId::parser().map(FieldValue::UserId).parse(input),
+            FieldType::AssetId => AssetId::parser().map(FieldValue::AssetId).parse(input),
+            FieldType::U32 => {
+                let (remaining, value) = primitives::U64.parse(input)?;
+                let value =
+                    u32::try_from(value).map_err(|_| ParseError::Overflow("u32 overflow"))?;
+                Ok((remaining, FieldValue::U32(value)))
+            }
+            FieldType::U64 => primitives::U64.map(FieldValue::U64).parse(input),
+            FieldType::I64 => primitives::I64.map(FieldValue::I64).parse(input),
+            FieldType::F64 => primitives::F64.map(FieldValue::F64).parse(input),
+            FieldType::Money => Money::parser().map(FieldValue::Money).parse(input),
+            FieldType::Backet => Backet::parser().map(FieldValue::Backet).parse(input),
+            FieldType::String => unquote().map(FieldValue::String).parse(input),
+        }
+    }
+}
+
+/// Value of a [Record] field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    UserId(UserId),
+    AssetId(AssetId),
+    U32(u32),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Money(Money),
+    Backet(Backet),
+    String(String),
+}
+
+impl Emit for FieldValue {
+    fn emit(&self, out: &mut String) {
+        match self {
+            FieldValue::UserId(user_id) => user_id.emit(out),
+            FieldValue::AssetId(asset_id) => asset_id.emit(out),
+            FieldValue::U32(value) => out.push_str(&value.to_string()),
+            FieldValue::U64(value) => value.emit(out),
+            FieldValue::I64(value) => value.emit(out),
+            FieldValue::F64(value) => value.emit(out),
+            FieldValue::Money(money) => money.emit(out),
+            FieldValue::Backet(backet) => backet.emit(out),
+            FieldValue::String(value) => value.emit(out),
+        }
+    }
+}
+
+/// Payload of a schema-defined operation: its fields in schema order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Name written before the braces, `UserCash` in `UserCash{...}`.
+    pub name: Option<String>,
+    pub fields: Vec<(String, FieldValue)>,
+}
+
+impl Record {
+    /// Value of the field `key`.
+    pub fn get(&self, key: &str) -> Option<&FieldValue> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// `Name{"key":value,...}`, as read back by [RecordParser]
+impl Emit for Record {
+    fn emit(&self, out: &mut String) {
+        if let Some(name) = &self.name {
+            out.push_str(name);
+        }
+        out.push('{');
+        for (key, value) in &self.fields {
+            emit_key_value(out, key, value);
+        }
+        out.push('}');
+    }
+}
+
+/// Parser of an [Operation]'s payload: `{"key":value,...}` with every key of
+/// the operation exactly once, in any order
+#[derive(Debug, Clone)]
+pub struct RecordParser {
+    name: Option<String>,
+    keys: Vec<String>,
+    #[allow(clippy::type_complexity)]
+    parser: Delimited<
+        Tuple<(StripWhitespace<Tag>, StripWhitespace<Tag>)>,
+        PermutationList<KeyValue<FieldType>>,
+        StripWhitespace<Tag>,
+    >,
+}
+
+impl Parser for RecordParser {
+    type Dest = Record;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, values) = self.parser.parse(input)?;
+        let record = Record {
+            name: self.name.clone(),
+            fields: self.keys.iter().cloned().zip(values).collect(),
+        };
+        Ok((remaining, record))
+    }
+}
+
+/// A journal operation described in a [Schema].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Operation {
+    /// `App` unless the schema says otherwise.
+    pub source: String,
+    /// `Journal` unless the schema says otherwise.
+    pub category: String,
+    /// Variant tag, e.g. `Airdrop` in `App::Journal Airdrop {...}`.
+    pub tag: String,
+    /// Name written before the braces of the payload, if any.
+    pub record: Option<String>,
+    pub keys: Vec<(String, FieldType)>,
+}
+
+impl Operation {
+    /// Parser of the operation's payload.
+    pub fn parser(&self) -> RecordParser {
+        let fields = self
+            .keys
+            .iter()
+            .map(|(key, ty)| key_value(key.clone(), *ty))
+            .collect();
+        RecordParser {
+            name: self.record.clone(),
+            keys: self.keys.iter().map(|(key, _)| key.clone()).collect(),
+            parser: delimited(
+                tuple2(
+                    tag(self.record.clone().unwrap_or_default()).strip_ws(),
+                    tag("{").strip_ws(),
+                ),
+                permutation_list(fields),
+                tag("}").strip_ws(),
+            ),
+        }
+    }
+}
+
+/// A schema that fails to load.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SchemaError {
+    #[error("invalid TOML: {0}")]
+    Syntax(String),
+    #[error("schema: {0}")]
+    Schema(String),
+    /// `operation` is the tag, or `#N` (1-based) when the tag is unusable.
+    #[error("operation {operation}: {reason}")]
+    Operation { operation: String, reason: String },
+    #[error(transparent)]
+    Grammar(#[from] GrammarError),
+}
+
+/// Journal operations described declaratively, so new operations can be
+/// parsed without code changes.
+///
+/// The TOML form has one `[[operation]]` table per operation:
+///
+/// ```toml
+/// [[operation]]
+/// tag = "Airdrop"              # App::Journal Airdrop {...}
+/// record = "UserBacket"        # optional: Airdrop UserBacket{...}
+/// keys = ["user_id: UserId", "backet: Backet", "count: u32"]
+/// ```
+///
+/// `source` and `category` default to `App` and `Journal`. Key types are
+/// `UserId`, `AssetId`, `u32`, `u64`, `i64`, `f64`, `Money`, `Backet` and
+/// `String`. Keys may appear in any order in the log; each is required.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    pub operations: Vec<Operation>,
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Schema {
+    /// Loads and validates a TOML schema.
+    pub fn from_toml(text: &str) -> Result<Self, SchemaError> {
+        let table: toml::Table = text
+            .parse()
+            .map_err(|err: toml::de::Error| SchemaError::Syntax(err.message().to_owned()))?;
+        if let Some(key) = table.keys().find(|key| *key != "operation") {
+            return Err(SchemaError::Schema(format!("unknown key `{key}`")));
+        }
+        let operations = match table.get("operation") {
+            None => Vec::new(),
+            Some(toml::Value::Array(operations)) => operations
+                .iter()
+                .enumerate()
+                .map(|(index, operation)| match operation {
+                    toml::Value::Table(operation) => Self::operation(index, operation),
+                    _ => Err(SchemaError::Schema(
+                        "`operation` must be an array of tables".into(),
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(SchemaError::Schema(
+                    "`operation` must be an array of tables".into(),
+                ));
+            }
+        };
+        Ok(Schema { operations })
+    }
+
+    fn operation(index: usize, table: &toml::Table) -> Result<Operation, SchemaError> {
+        let mut name = format!("#{}", index + 1);
+        let error = |name: &str, reason: String| SchemaError::Operation {
+            operation: name.to_owned(),
+            reason,
+        };
+        let string = |key: &str| match table.get(key) {
+            None => Ok(None),
+            Some(toml::Value::String(value)) => Ok(Some(value.as_str())),
+            Some(_) => Err(format!("`{key}` must be a string")),
+        };
+        let identifier = |key: &str, default: Option<&str>| match string(key)?.or(default) {
+            Some(value) if !is_identifier(value) => {
+                Err(format!("`{key}` must be an identifier, got {value:?}"))
+            }
+            value => Ok(value.map(str::to_owned)),
+        };
+
+        let tag = identifier("tag", None)
+            .and_then(|tag| tag.ok_or("missing `tag`".to_owned()))
+            .map_err(|reason| error(&name, reason))?;
+        name = tag.clone();
+        if let Some(key) = table
+            .keys()
+            .find(|key| !["tag", "source", "category", "record", "keys"].contains(&key.as_str()))
+        {
+            return Err(error(&name, format!("unknown key `{key}`")));
+        }
+        let source = identifier("source", Some("App")).map_err(|reason| error(&name, reason))?;
+        let category =
+            identifier("category", Some("Journal")).map_err(|reason| error(&name, reason))?;
+        let record = identifier("record", None).map_err(|reason| error(&name, reason))?;
+
+        let entries = match table.get("keys") {
+            Some(toml::Value::Array(entries)) if !entries.is_empty() => entries,
+            Some(toml::Value::Array(_)) | None => {
+                return Err(error(&name, "`keys` must list at least one key".into()));
+            }
+            Some(_) => return Err(error(&name, "`keys` must be an array of strings".into())),
+        };
+        let mut keys: Vec<(String, FieldType)> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let entry = entry
+                .as_str()
+                .ok_or_else(|| error(&name, "`keys` must be an array of strings".into()))?;
+            let (key, ty) = entry
+                .split_once(':')
+                .map(|(key, ty)| (key.trim(), ty.trim()))
+                .ok_or_else(|| error(&name, format!("expected \"name: Type\", got {entry:?}")))?;
+            if key.is_empty() || key.contains(['"', '\\']) {
+                return Err(error(&name, format!("invalid key name {key:?}")));
+            }
+            let ty = FieldType::from_name(ty).ok_or_else(|| {
+                let known: Vec<_> = FieldType::ALL.iter().map(|ty| ty.name()).collect();
+                error(
+                    &name,
+                    format!(
+                        "key {key:?}: unknown type {ty:?}, expected one of {}",
+                        known.join(", ")
+                    ),
+                )
+            })?;
+            if keys.iter().any(|(existing, _)| existing == key) {
+                return Err(error(&name, format!("key {key:?} is listed twice")));
+            }
+            keys.push((key.to_owned(), ty));
+        }
+        Ok(Operation {
+            source: source.unwrap_or_default(),
+            category: category.unwrap_or_default(),
+            tag,
+            record,
+            keys,
+        })
+    }
+
+    /// Registers every operation in `grammar`, so its lines parse as
+    /// [LogKind::Custom](super::LogKind::Custom) with a [Record] payload.
+    pub fn register(&self, grammar: &mut LogGrammar) -> Result<(), SchemaError> {
+        for operation in &self.operations {
+            grammar.register(
+                &operation.source,
+                &operation.category,
+                &operation.tag,
+                operation.parser(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::LogKind;
+    use std::num::NonZeroU32;
+
+    const SCHEMA: &str = r#"
+[[operation]]
+tag = "Airdrop"
+keys = ["user_id: UserId", "backet: Backet", "note: String"]
+
+[[operation]]
+tag = "Rebate"
+category = "Ledger"
+record = "UserCash"
+keys = ["user_id: UserId", "count: u32", "amount: Money"]
+"#;
+
+    fn grammar() -> LogGrammar {
+        let mut grammar = LogGrammar::default();
+        Schema::from_toml(SCHEMA)
+            .unwrap()
+            .register(&mut grammar)
+            .unwrap();
+        grammar
+    }
+
+    fn record(line: &str) -> Record {
+        let (log, warnings) = grammar().parse_line(line).unwrap();
+        let LogKind::Custom { payload, .. } = &log.kind else {
+            panic!("{log:?}");
+        };
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(
+            grammar().parse_line(&log.to_log_string()),
+            Some((log.clone(), vec![]))
+        );
+        payload.downcast_ref::<Record>().unwrap().clone()
+    }
+
+    #[test]
+    fn test_schema_records() {
+        let airdrop = record(
+            r#"App::Journal Airdrop {"note": "promo", "user_id": "Bob", "backet": Backet{"asset_id":"milk","count":2,},} requestid=3"#,
+        );
+        assert_eq!(airdrop.name, None);
+        assert_eq!(
+            airdrop
+                .fields
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect::<Vec<_>>(),
+            ["user_id", "backet", "note"]
+        );
+        assert_eq!(
+            airdrop.get("backet"),
+            Some(&FieldValue::Backet(Backet {
+                asset_id: AssetId("milk".into()),
+                count: NonZeroU32::new(2).unwrap(),
+            }))
+        );
+
+        let rebate = record(
+            r#"App::Ledger Rebate UserCash{"user_id":"Bob","count":0,"amount":12.50 eur,} requestid=4"#,
+        );
+        assert_eq!(rebate.name.as_deref(), Some("UserCash"));
+        assert_eq!(rebate.get("count"), Some(&FieldValue::U32(0)));
+        assert_eq!(
+            rebate.get("amount"),
+            Some(&FieldValue::Money(Money::from_minor(
+                1250,
+                Currency::new("eur").unwrap()
+            )))
+        );
+
+        // Missing, duplicated and mistyped keys reject the line.
+        for line in [
+            r#"App::Journal Airdrop {"user_id":"Bob","note":"x",} requestid=3"#,
+            r#"App::Journal Airdrop {"user_id":"Bob","user_id":"Bob","note":"x",} requestid=3"#,
+            r#"App::Ledger Rebate UserCash{"user_id":"Bob","count":-1,"amount":1,} requestid=4"#,
+            r#"App::Ledger Rebate {"user_id":"Bob","count":1,"amount":1,} requestid=4"#,
+        ] {
+            assert_eq!(grammar().parse_line(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let error = |text: &str| Schema::from_toml(text).unwrap_err().to_string();
+        assert!(error("[[operation]\n").starts_with("invalid TOML: "));
+        assert_eq!(error("operations = []"), "schema: unknown key `operations`");
+        assert_eq!(
+            error("[[operation]]\nkeys = [\"a: u32\"]"),
+            "operation #1: missing `tag`"
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Air drop\"\nkeys = [\"a: u32\"]"),
+            r#"operation #1: `tag` must be an identifier, got "Air drop""#
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Airdrop\"\nkeys = [\"count: u128\"]"),
+            r#"operation Airdrop: key "count": unknown type "u128", expected one of UserId, AssetId, u32, u64, i64, f64, Money, Backet, String"#
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Airdrop\"\nkeys = [\"count: u32\", \"count:u64\"]"),
+            r#"operation Airdrop: key "count" is listed twice"#
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Airdrop\"\nkeys = [\"count\"]"),
+            r#"operation Airdrop: expected "name: Type", got "count""#
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Airdrop\"\nkeys = []"),
+            "operation Airdrop: `keys` must list at least one key"
+        );
+        assert_eq!(
+            error("[[operation]]\ntag = \"Airdrop\"\nfields = []"),
+            "operation Airdrop: unknown key `fields`"
+        );
+
+        let builtin =
+            Schema::from_toml("[[operation]]\ntag = \"BuyAsset\"\nkeys = [\"a: u32\"]").unwrap();
+        assert_eq!(
+            builtin
+                .register(&mut LogGrammar::default())
+                .unwrap_err()
+                .to_string(),
+            "App::Journal BuyAsset is a built-in log variant"
+        );
+    }
+}
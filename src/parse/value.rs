@@ -0,0 +1,410 @@
+use std::fmt;
+
+use super::combinators::*;
+
+/// Any well-formed payload, read without knowing its shape in advance, e.g.
+/// `UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],}`.
+///
+/// Objects and lists may nest up to [MAX_DEPTH] levels and take an optional
+/// trailing comma. A bare token is a number if it reads as one, else a blob
+/// of hex digits or `base64:` data. `NaN`, `inf` and `-inf` are floats, and
+/// a signed integer such as `+5` is an [I64](Value::I64) when it fits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `Name{"key":value,...}`; the name is optional.
+    Object {
+        name: Option<String>,
+        fields: Vec<(String, Value)>,
+    },
+    List(Vec<Value>),
+    Str(String),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    /// Parses `payload` as a whole, surrounding whitespace aside.
+    pub fn from_payload(payload: &str) -> Result<Value, ParseError> {
+        let (_, (value, ())) = tuple2(Value::parser().strip_ws(), eof()).parse(payload)?;
+        Ok(value)
+    }
+
+    /// Name of an object, e.g. `UserBackets`.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Value::Object { name, .. } => name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Field of an object; the first one if the key repeats.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object { fields, .. } => fields
+                .iter()
+                .find_map(|(name, value)| (name == key).then_some(value)),
+            _ => None,
+        }
+    }
+
+    /// Follows a dot-separated path of object keys and list indices, e.g.
+    /// `backets.0.asset_id`.
+    pub fn query(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, step| match value {
+            Value::List(items) => items.get(step.parse::<usize>().ok()?),
+            _ => value.get(step),
+        })
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::U64(n) => Some(*n),
+            Value::I64(n) => u64::try_from(*n).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::U64(n) => Some(*n as f64),
+            Value::I64(n) => Some(*n as f64),
+            Value::F64(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Parser of bare values: numbers and binary data
+#[derive(Debug, Clone)]
+struct Scalar;
+impl Parser for Scalar {
+    type Dest = Value;
+    fn parse<'a>(&self, input: &'a str) -> Result<(&'a str, Self::Dest), ParseError> {
+        let (remaining, token) = match input.strip_prefix("base64:") {
+            Some(data) => {
+                let (remaining, data) =
+                    take_while(|c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+                        .parse(data)?;
+                if data.is_empty() {
+                    return Ok((remaining, Value::Blob(Vec::new())));
+                }
+                (remaining, format!("base64:{data}"))
+            }
+            None => take_while(|c: char| is_identifier(c) || matches!(c, '-' | '+' | '.'))
+                .parse(input)?,
+        };
+        fn whole<P: Parser>(parser: P, token: &str) -> Option<P::Dest> {
+            let (_, (value, ())) = tuple2(parser, eof()).parse(token).ok()?;
+            Some(value)
+        }
+        let digits = token.trim_start_matches(['-', '+']);
+        let integer = !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit() || c == '_');
+        let signed = digits.len() < token.len();
+        let value = if integer && signed {
+            whole(primitives::I64, &token)
+                .map(Value::I64)
+                .or_else(|| whole(primitives::U64, &token).map(Value::U64))
+        } else if integer || token.starts_with("0x") {
+            whole(primitives::U64, &token).map(Value::U64)
+        } else {
+            match token.as_str() {
+                "NaN" => Some(f64::NAN),
+                "inf" => Some(f64::INFINITY),
+                "-inf" => Some(f64::NEG_INFINITY),
+                _ => whole(primitives::F64, &token),
+            }
+            .map(Value::F64)
+        };
+        let value = value
+            .or_else(|| {
+                whole(
+                    primitives::Blob {
+                        max_len: usize::MAX,
+                    },
+                    &token,
+                )
+                .map(Value::Blob)
+            })
+            .ok_or(ParseError::UnexpectedInput("expected payload value"))?;
+        Ok((remaining, value))
+    }
+}
+
+impl Parsable for Value {
+    type Parser = Recursive<Value>;
+    fn parser() -> Self::Parser {
+        recursive(|value| {
+            let field = tuple2(
+                unquote().strip_ws(),
+                value.clone().strip_ws().preceded_by(tag(":").strip_ws()),
+            );
+            let fields = delimited(
+                tag("{").strip_ws(),
+                field.separated_by(tag(",")).cut(),
+                tuple2(tag(",").opt(), tag("}").strip_ws()).cut(),
+            );
+            let object =
+                tuple2(take_while(is_identifier), fields).map(|(name, fields)| Value::Object {
+                    name: (!name.is_empty()).then_some(name),
+                    fields,
+                });
+            alt4(
+                object,
+                list(value).map(Value::List),
+                unquote().map(Value::Str),
+                Scalar,
+            )
+        })
+    }
+}
+
+impl Emit for Value {
+    fn emit(&self, out: &mut String) {
+        match self {
+            Value::Object { name, fields } => {
+                out.push_str(name.as_deref().unwrap_or_default());
+                out.push('{');
+                for (key, value) in fields {
+                    emit_key_value(out, key, value);
+                }
+                out.push('}');
+            }
+            Value::List(items) => items.emit(out),
+            Value::Str(text) => text.emit(out),
+            Value::U64(n) => n.emit(out),
+            // the sign keeps a non-negative one from reading back as a U64
+            Value::I64(n) => out.push_str(&format!("{n:+}")),
+            // `{:?}` keeps the `.0` of whole numbers, so they read back as
+            // floats, and writes `NaN`, `inf` and `-inf` as Scalar reads them
+            Value::F64(x) => out.push_str(&format!("{x:?}")),
+            Value::Blob(bytes) => {
+                let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+                // hex such as `1e10` or `00` would read back as a number
+                match Scalar.parse(&hex) {
+                    Ok(("", Value::Blob(_))) => out.push_str(&hex),
+                    _ => {
+                        out.push_str("base64:");
+                        out.push_str(&encode_base64(bytes));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Standard base64 with padding, as read by [primitives::Blob]
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |acc, (idx, byte)| {
+            acc | u32::from(*byte) << (16 - 8 * idx)
+        });
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * idx) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_log_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn object(name: Option<&str>, fields: Vec<(&str, Value)>) -> Value {
+        Value::Object {
+            name: name.map(String::from),
+            fields: fields
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_value_parse() {
+        let payload = r#"UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,}, Backet { "asset_id" : "eggs", "count" : 12 } ],}"#;
+        let backet = |asset: &str, count| {
+            object(
+                Some("Backet"),
+                vec![
+                    ("asset_id", Value::Str(asset.into())),
+                    ("count", Value::U64(count)),
+                ],
+            )
+        };
+        assert_eq!(
+            Value::from_payload(payload),
+            Ok(object(
+                Some("UserBackets"),
+                vec![
+                    ("user_id", Value::Str("Bob".into())),
+                    (
+                        "backets",
+                        Value::List(vec![backet("milk", 3), backet("eggs", 12)])
+                    ),
+                ]
+            ))
+        );
+        assert_eq!(
+            Value::from_payload(
+                r#" {"a": [[], [-1, 2.5, 1e3]], "b": {}, "auth": deadbeef, "c": base64:AQI=} "#
+            ),
+            Ok(object(
+                None,
+                vec![
+                    (
+                        "a",
+                        Value::List(vec![
+                            Value::List(vec![]),
+                            Value::List(vec![Value::I64(-1), Value::F64(2.5), Value::F64(1000.0)]),
+                        ])
+                    ),
+                    ("b", object(None, vec![])),
+                    ("auth", Value::Blob(vec![0xde, 0xad, 0xbe, 0xef])),
+                    ("c", Value::Blob(vec![1, 2])),
+                ]
+            ))
+        );
+        assert_eq!(Value::from_payload("0x10"), Ok(Value::U64(16)));
+        assert_eq!(Value::from_payload("1_000"), Ok(Value::U64(1000)));
+
+        assert_eq!(
+            Value::from_payload("abc"),
+            Err(ParseError::UnexpectedInput("expected payload value"))
+        );
+        assert!(Value::from_payload(r#"{"a":1"#).is_err());
+        assert!(Value::from_payload("[1,2] trailing").is_err());
+        // deep nesting from untrusted payloads fails instead of overflowing the stack
+        let deep = |n: usize| format!("{}1{}", "[".repeat(n), "]".repeat(n));
+        assert!(Value::from_payload(&deep(MAX_DEPTH - 1)).is_ok());
+        let object = |n: usize| format!("{}1{}", r#"{"a":"#.repeat(n), "}".repeat(n));
+        assert!(Value::from_payload(&object(MAX_DEPTH - 1)).is_ok());
+        assert!(Value::from_payload(&object(10_000)).is_err());
+        assert_eq!(
+            Value::from_payload(&deep(10_000)),
+            Err(ParseError::Cut(Box::new(ParseError::TooDeep(MAX_DEPTH))))
+        );
+        assert_eq!(
+            Value::from_payload(&deep(2)),
+            Ok(Value::List(vec![Value::List(vec![Value::U64(1)])]))
+        );
+        // Past `{` the object is settled, so the field's own error is reported
+        assert!(
+            Value::from_payload(r#"Backet{"count" 3}"#)
+                .unwrap_err()
+                .is_cut()
+        );
+    }
+
+    #[test]
+    fn test_value_query_emit() {
+        let value = Value::from_payload(
+            r#"UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],}"#,
+        )
+        .unwrap();
+        assert_eq!(value.name(), Some("UserBackets"));
+        assert_eq!(value.query("user_id").and_then(Value::as_str), Some("Bob"));
+        assert_eq!(
+            value.query("backets.0.count").and_then(Value::as_u64),
+            Some(3)
+        );
+        assert_eq!(
+            value.query("backets.0").and_then(Value::name),
+            Some("Backet")
+        );
+        assert_eq!(value.query("backets.1"), None);
+        assert_eq!(value.query("backets.count"), None);
+        assert_eq!(value.query("user_id.length"), None);
+
+        let text = value.to_log_string();
+        assert_eq!(
+            text,
+            r#"UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],}"#
+        );
+        assert_eq!(Value::from_payload(&text), Ok(value));
+
+        let scalars = Value::List(vec![
+            Value::F64(2.0),
+            Value::Blob(vec![0xab]),
+            Value::Blob(vec![0x12, 0x34]),
+            Value::Blob(vec![0x12, 0x34, 0x56, 0x78]),
+        ]);
+        assert_eq!(scalars.to_string(), "[2.0,ab,base64:EjQ=,base64:EjRWeA==,]");
+        assert_eq!(Value::from_payload(&scalars.to_string()), Ok(scalars));
+
+        // hex that reads as a number, and empty blobs, go out as base64
+        let blobs = Value::List(vec![
+            Value::Blob(vec![0x1e, 0x10]),
+            Value::Blob(vec![0x0e, 0x12]),
+            Value::Blob(vec![0x00]),
+            Value::Blob(vec![]),
+        ]);
+        assert_eq!(blobs.to_string(), "[base64:HhA=,base64:DhI=,base64:AA==,base64:,]");
+        assert_eq!(Value::from_payload(&blobs.to_string()), Ok(blobs));
+
+        let numbers = Value::List(vec![
+            Value::I64(5),
+            Value::I64(-5),
+            Value::F64(f64::INFINITY),
+            Value::F64(f64::NEG_INFINITY),
+        ]);
+        assert_eq!(numbers.to_string(), "[+5,-5,inf,-inf,]");
+        assert_eq!(Value::from_payload(&numbers.to_string()), Ok(numbers));
+        let nan = Value::from_payload(&Value::F64(f64::NAN).to_string());
+        assert!(nan.unwrap().as_f64().unwrap().is_nan());
+    }
+
+    fn any_value() -> impl Strategy<Value = Value> {
+        let leaf = prop_oneof![
+            ".*".prop_map(Value::Str),
+            any::<u64>().prop_map(Value::U64),
+            any::<i64>().prop_map(Value::I64),
+            any::<f64>()
+                .prop_filter("NaN is not equal to itself", |x| !x.is_nan())
+                .prop_map(Value::F64),
+            any::<Vec<u8>>().prop_map(Value::Blob),
+        ];
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            prop_oneof![
+                prop::collection::vec(inner.clone(), 0..4).prop_map(Value::List),
+                (
+                    prop::option::of("[A-Za-z_][A-Za-z0-9_]{0,8}"),
+                    prop::collection::vec((".*", inner), 0..4),
+                )
+                    .prop_map(|(name, fields)| Value::Object { name, fields }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_value_roundtrip(value in any_value()) {
+            prop_assert_eq!(Value::from_payload(&value.to_string()), Ok(value));
+        }
+    }
+}
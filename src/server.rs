@@ -0,0 +1,409 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::canonical::{Canonicalizer, Policy};
+use crate::json;
+use crate::ledger::Ledger;
+use crate::parse::LogLine;
+use crate::stats::Stats;
+use crate::{ReadMode, Utf8Policy, read_log_with_policy};
+
+/// How long a connection may stay silent before its request is given up.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause after a failed `accept`, e.g. when out of file descriptors.
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// Parsed request line of an HTTP/1.1 request; headers are read and dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    pub(crate) path: String,
+    /// Percent-decoded `key=value` pairs of the query string, in order.
+    pub(crate) query: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Reads the request line and headers from `stream`.
+    pub(crate) fn read(stream: &mut impl BufRead) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP request");
+        let mut request_line = String::new();
+        stream.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().ok_or_else(invalid)?.to_owned();
+        let target = parts.next().ok_or_else(invalid)?;
+        loop {
+            let mut header = String::new();
+            if stream.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+        }
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect();
+        Ok(Request {
+            method,
+            path: percent_decode(path),
+            query,
+        })
+    }
+
+    /// Last value of the query parameter `key`.
+    pub(crate) fn param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Status, content type and body of an HTTP response.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) content_type: &'static str,
+    pub(crate) body: String,
+}
+
+impl Response {
+    pub(crate) fn json(body: String) -> Self {
+        Response {
+            status: 200,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    pub(crate) fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: json::object([("error", json::string(message))]),
+        }
+    }
+
+    /// Writes the response with `Connection: close`.
+    pub(crate) fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        write!(
+            out,
+            "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        out.flush()
+    }
+}
+
+/// Reads one request from `stream`, answers it with `handler` and closes the connection.
+pub(crate) fn handle_connection(
+    stream: TcpStream,
+    read_timeout: Duration,
+    handler: impl FnOnce(&Request) -> Response,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(read_timeout))?;
+    let mut reader = BufReader::new(&stream);
+    let response = match Request::read(&mut reader) {
+        Ok(request) if request.method != "GET" => Response::error(405, "only GET is supported"),
+        Ok(request) => handler(&request),
+        Err(e) => Response::error(400, &e.to_string()),
+    };
+    response.write_to(&mut &stream)
+}
+
+/// Accepts connections forever, answering each on a thread of its own.
+/// A failed `accept` is logged and retried, so it doesn't stop the server.
+pub(crate) fn accept_loop(
+    listener: &TcpListener,
+    read_timeout: Duration,
+    handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+) {
+    let handler = Arc::new(handler);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("accept error: {e}");
+                std::thread::sleep(ACCEPT_RETRY);
+                continue;
+            }
+        };
+        let handler = Arc::clone(&handler);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, read_timeout, |request| handler(request)) {
+                eprintln!("connection error: {e}");
+            }
+        });
+    }
+}
+
+/// JSON query server over a fixed set of log files.
+///
+/// Files are re-read through [read_log](crate::read_log) on every request, so answers reflect
+/// lines appended since the server started. Endpoints (all `GET`, all JSON):
+///
+/// - `/lines?mode=all|errors|exchanges&request_id=1,2` — matching lines
+/// - `/requests/{id}` — lines of a single request
+/// - `/stats` — [Stats] summary
+/// - `/ledger` — [Ledger] replayed from the journal
+///
+/// With a [Policy], every endpoint answers with canonical user and asset ids.
+pub struct Server {
+    listener: TcpListener,
+    files: Arc<[PathBuf]>,
+    policy: Option<Policy>,
+    read_timeout: Duration,
+}
+
+impl Server {
+    /// Binds the listening socket; use port `0` to let the OS pick one.
+    pub fn bind(addr: impl ToSocketAddrs, files: Vec<PathBuf>) -> io::Result<Self> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            files: files.into(),
+            policy: None,
+            read_timeout: READ_TIMEOUT,
+        })
+    }
+
+    /// Canonicalizes user and asset ids of every line read.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// How long a client may take to send its request, [READ_TIMEOUT] by default.
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = read_timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections forever, one thread per connection.
+    pub fn serve(&self) -> io::Result<()> {
+        let files = Arc::clone(&self.files);
+        let policy = self.policy.clone();
+        accept_loop(&self.listener, self.read_timeout, move |request| {
+            route(&files, policy.as_ref(), request)
+        });
+        Ok(())
+    }
+}
+
+fn route(files: &[PathBuf], policy: Option<&Policy>, request: &Request) -> Response {
+    let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+    let result = match segments.as_slice() {
+        ["lines"] => lines(files, policy, request),
+        ["requests", id] => match id.parse::<NonZeroU32>() {
+            Ok(id) => read_all(files, policy, ReadMode::All, vec![id])
+                .map(|logs| Response::json(lines_json(&logs))),
+            Err(_) => Ok(Response::error(400, &format!("invalid request id '{id}'"))),
+        },
+        ["stats"] => read_all(files, policy, ReadMode::All, vec![]).map(|logs| {
+            let mut stats = Stats::default();
+            stats.extend(&logs);
+            Response::json(stats.to_json())
+        }),
+        ["ledger"] => read_all(files, policy, ReadMode::All, vec![]).map(|logs| {
+            let mut ledger = Ledger::default();
+            ledger.extend(&logs);
+            Response::json(ledger.to_json())
+        }),
+        _ => Ok(Response::error(404, "unknown endpoint")),
+    };
+    result.unwrap_or_else(|e| Response::error(500, &e.to_string()))
+}
+
+fn lines(files: &[PathBuf], policy: Option<&Policy>, request: &Request) -> io::Result<Response> {
+    let mode = match request.param("mode").unwrap_or("all") {
+        "all" => ReadMode::All,
+        "errors" => ReadMode::Errors,
+        "exchanges" => ReadMode::Exchanges,
+        other => return Ok(Response::error(400, &format!("unknown mode '{other}'"))),
+    };
+    let request_ids = match request.param("request_id") {
+        None | Some("") => vec![],
+        Some(ids) => match ids.split(',').map(|id| id.trim().parse()).collect() {
+            Ok(ids) => ids,
+            Err(_) => return Ok(Response::error(400, &format!("invalid request ids '{ids}'"))),
+        },
+    };
+    read_all(files, policy, mode, request_ids).map(|logs| Response::json(lines_json(&logs)))
+}
+
+/// [read_log](crate::read_log) over every file, concatenated in file order,
+/// with canonical ids under `policy`. Parse warnings go to stderr.
+fn read_all(
+    files: &[PathBuf],
+    policy: Option<&Policy>,
+    mode: ReadMode,
+    request_ids: Vec<NonZeroU32>,
+) -> io::Result<Vec<LogLine>> {
+    let mut logs = Vec::new();
+    for path in files {
+        let file = std::fs::File::open(path)?;
+        let report = read_log_with_policy(file, mode, request_ids.clone(), Utf8Policy::Lossy)?;
+        for (line, warning) in &report.warnings {
+            eprintln!("warning: {}: line {line}: {warning}", path.display());
+        }
+        logs.extend(report.logs);
+    }
+    if let Some(policy) = policy {
+        let mut canonicalizer = Canonicalizer::new(policy.clone());
+        logs = logs.iter().map(|log| canonicalizer.canonicalize(log)).collect();
+    }
+    Ok(logs)
+}
+
+fn lines_json(logs: &[LogLine]) -> String {
+    json::array(logs.iter().map(|log| {
+        json::object([
+            ("request_id", log.request_id.to_string()),
+            ("source", json::string(log.kind.source())),
+            ("category", json::string(log.kind.category())),
+            ("variant", json::string(log.kind.variant())),
+            ("text", json::string(&log.kind.to_string())),
+        ])
+    }))
+}
+
+/// Decodes `%XX` escapes and `+` (as space); malformed escapes are kept verbatim.
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => match input
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                None => decoded.push(b'%'),
+            },
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    const SOURCE: &str = r#"System::Error NetworkError "network interface is down" requestid=1
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Journal BuyAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+"#;
+
+    fn get(addr: SocketAddr, target: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_owned(), body.to_owned())
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("1%2C2"), "1,2");
+        assert_eq!(percent_decode("a+b%zz"), "a b%zz");
+    }
+
+    #[test]
+    fn test_endpoints_over_local_port() {
+        let path = std::env::temp_dir().join(format!("analysis-server-{}.log", std::process::id()));
+        std::fs::write(&path, SOURCE).unwrap();
+        let server = Server::bind("127.0.0.1:0", vec![path.clone()]).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        let (status, body) = get(addr, "/lines?mode=errors");
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert_eq!(
+            body,
+            r#"[{"request_id":1,"source":"System","category":"Error","variant":"NetworkError","text":"System::Error NetworkError: network interface is down"}]"#
+        );
+        let (_, body) = get(addr, "/lines?request_id=4%2C10");
+        assert_eq!(body.matches("request_id").count(), 2);
+        let (_, body) = get(addr, "/requests/10");
+        assert!(body.contains(r#""variant":"BuyAsset""#), "{body}");
+        let (_, body) = get(addr, "/stats");
+        assert!(body.starts_with(r#"{"lines":3,"#), "{body}");
+        let (_, body) = get(addr, "/ledger");
+        assert!(body.contains(r#""Bob":{"cash":{"unspecified":1000},"holdings":{"milk":5}"#), "{body}");
+
+        assert_eq!(get(addr, "/lines?mode=bogus").0, "HTTP/1.1 400 Bad Request");
+        assert_eq!(get(addr, "/requests/0").0, "HTTP/1.1 400 Bad Request");
+        assert_eq!(get(addr, "/nope").0, "HTTP/1.1 404 Not Found");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_canonical_ids() {
+        let path = std::env::temp_dir().join(format!("analysis-canonical-{}.log", std::process::id()));
+        std::fs::write(
+            &path,
+            format!(
+                "{SOURCE}{}\n",
+                r#"App::Journal SellAsset UserBacket{"user_id":" bob","backet":Backet{"asset_id":"MILK","count":2,},} requestid=11"#
+            ),
+        )
+        .unwrap();
+        let server = Server::bind("127.0.0.1:0", vec![path.clone()])
+            .unwrap()
+            .with_policy(Policy::default());
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        let (_, body) = get(addr, "/ledger");
+        assert!(body.contains(r#""bob":{"cash":{"unspecified":1000},"holdings":{"milk":3}"#), "{body}");
+        assert!(!body.contains("Bob"), "{body}");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_silent_client_times_out() {
+        let server = Server::bind("127.0.0.1:0", vec![])
+            .unwrap()
+            .with_read_timeout(Duration::from_millis(50));
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+
+        // never sends a request; the server gives up and answers 400
+        let mut silent = TcpStream::connect(addr).unwrap();
+        let mut response = String::new();
+        silent.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request"), "{response}");
+        assert_eq!(get(addr, "/nope").0, "HTTP/1.1 404 Not Found");
+    }
+}
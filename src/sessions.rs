@@ -0,0 +1,322 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::num::NonZeroU32;
+
+use sha2::{Digest, Sha256};
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// First 8 bytes of the SHA-256 of [AuthData]; identifies a session without
+/// keeping the credentials around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Fingerprint(pub [u8; 8]);
+
+impl Fingerprint {
+    pub fn of(auth: &AuthData) -> Self {
+        let digest = Sha256::digest(auth.as_bytes());
+        Fingerprint(digest[..8].try_into().expect("SHA-256 is 32 bytes"))
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// Everything attributed to one auth token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub fingerprint: Fingerprint,
+    /// Request id of the first `Connect` with this token.
+    pub first_request_id: NonZeroU32,
+    /// Number of `Connect` lines with this token.
+    pub connects: usize,
+    /// Users whose journal operations ran under this session.
+    pub users: BTreeSet<UserId>,
+    pub request_ids: BTreeSet<NonZeroU32>,
+    pub journal_ops: usize,
+    /// Lines of any kind with one of `request_ids`.
+    pub lines: usize,
+    /// Those of the lines at least as severe as [Severity::Error].
+    pub errors: usize,
+}
+
+/// A journal operation that could not be tied to any session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnauthenticatedOp {
+    pub request_id: NonZeroU32,
+    /// Journal variant, e.g. `"BuyAsset"`.
+    pub op: String,
+    pub user_id: UserId,
+}
+
+/// Sessions reconstructed from `App::Trace Connect` lines.
+///
+/// A `Connect` opens (or resumes) the session of its token for its request
+/// id. A journal operation belongs to the session of its request if there
+/// is one, which also binds the operation's user to that session; otherwise
+/// it falls back to the session its user was last bound to. Operations that
+/// match neither are reported as [unauthenticated](Sessions::unauthenticated).
+/// Every other line (traces, errors) is counted in the session of its
+/// request, including lines read before the request was attributed.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sessions {
+    sessions: BTreeMap<Fingerprint, Session>,
+    by_request: HashMap<NonZeroU32, Fingerprint>,
+    by_user: HashMap<UserId, Fingerprint>,
+    /// `(lines, errors)` of requests not attributed to a session yet.
+    pending: HashMap<NonZeroU32, (usize, usize)>,
+    unauthenticated: Vec<UnauthenticatedOp>,
+}
+
+impl Accumulator for Sessions {
+    /// Accounts a single log line; lines must be fed in log order.
+    fn add(&mut self, log: &LogLine) {
+        let request_id = log.request_id;
+        match &log.kind {
+            LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(auth))) => {
+                let fingerprint = Fingerprint::of(auth);
+                let session = self.sessions.entry(fingerprint).or_insert_with(|| Session {
+                    fingerprint,
+                    first_request_id: request_id,
+                    connects: 0,
+                    users: BTreeSet::new(),
+                    request_ids: BTreeSet::new(),
+                    journal_ops: 0,
+                    lines: 0,
+                    errors: 0,
+                });
+                session.connects += 1;
+                self.bind(request_id, fingerprint);
+            }
+            LogKind::App(AppLogKind::Journal(journal)) => {
+                let user_id = journal.user_id();
+                let fingerprint = match self.by_request.get(&request_id) {
+                    Some(&fingerprint) => {
+                        self.by_user.insert(user_id.clone(), fingerprint);
+                        Some(fingerprint)
+                    }
+                    None => self.by_user.get(user_id).copied(),
+                };
+                match fingerprint.and_then(|f| self.sessions.get_mut(&f)) {
+                    Some(session) => {
+                        session.users.insert(user_id.clone());
+                        session.journal_ops += 1;
+                        let fingerprint = session.fingerprint;
+                        self.bind(request_id, fingerprint);
+                    }
+                    None => self.unauthenticated.push(UnauthenticatedOp {
+                        request_id,
+                        op: log.kind.variant().to_owned(),
+                        user_id: user_id.clone(),
+                    }),
+                }
+            }
+            _ => {}
+        }
+        let error = usize::from(log.kind.severity() >= Severity::Error);
+        match self.session_mut(request_id) {
+            Some(session) => {
+                session.lines += 1;
+                session.errors += error;
+            }
+            None => {
+                let pending = self.pending.entry(request_id).or_default();
+                pending.0 += 1;
+                pending.1 += error;
+            }
+        }
+    }
+}
+
+impl Sessions {
+    /// Attributes `request_id` to the session of `fingerprint`, along with
+    /// the lines of that request seen so far.
+    fn bind(&mut self, request_id: NonZeroU32, fingerprint: Fingerprint) {
+        self.by_request.insert(request_id, fingerprint);
+        let (lines, errors) = self.pending.remove(&request_id).unwrap_or_default();
+        let session = self
+            .sessions
+            .get_mut(&fingerprint)
+            .expect("bound sessions exist");
+        session.request_ids.insert(request_id);
+        session.lines += lines;
+        session.errors += errors;
+    }
+
+    fn session_mut(&mut self, request_id: NonZeroU32) -> Option<&mut Session> {
+        let fingerprint = self.by_request.get(&request_id)?;
+        self.sessions.get_mut(fingerprint)
+    }
+
+    /// Sessions in order of their first `Connect`.
+    pub fn sessions(&self) -> Vec<&Session> {
+        let mut sessions: Vec<_> = self.sessions.values().collect();
+        sessions.sort_by_key(|session| session.first_request_id);
+        sessions
+    }
+
+    /// Session a request was attributed to, if any.
+    pub fn session_of(&self, request_id: NonZeroU32) -> Option<&Session> {
+        self.by_request
+            .get(&request_id)
+            .and_then(|fingerprint| self.sessions.get(fingerprint))
+    }
+
+    /// Journal operations with no preceding authenticated session, in log order.
+    pub fn unauthenticated(&self) -> &[UnauthenticatedOp] {
+        &self.unauthenticated
+    }
+
+    /// Renders sessions and unauthenticated operations as a JSON object.
+    pub fn to_json(&self) -> String {
+        let sessions = self.sessions().into_iter().map(|session| {
+            json::object([
+                (
+                    "fingerprint",
+                    json::string(&session.fingerprint.to_string()),
+                ),
+                ("first_request_id", session.first_request_id.to_string()),
+                ("connects", session.connects.to_string()),
+                (
+                    "users",
+                    json::array(session.users.iter().map(|u| json::string(&u.0))),
+                ),
+                (
+                    "request_ids",
+                    json::array(session.request_ids.iter().map(|id| id.to_string())),
+                ),
+                ("journal_ops", session.journal_ops.to_string()),
+                ("lines", session.lines.to_string()),
+                ("errors", session.errors.to_string()),
+            ])
+        });
+        let unauthenticated = self.unauthenticated.iter().map(|op| {
+            json::object([
+                ("request_id", op.request_id.to_string()),
+                ("op", json::string(&op.op)),
+                ("user_id", json::string(&op.user_id.0)),
+            ])
+        });
+        json::object([
+            ("sessions", json::array(sessions)),
+            ("unauthenticated", json::array(unauthenticated)),
+        ])
+    }
+}
+
+impl fmt::Display for Sessions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+            items
+                .into_iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        writeln!(f, "sessions: {}", self.sessions.len())?;
+        for session in self.sessions() {
+            writeln!(
+                f,
+                "  {} (first request {}, {} connects, {} journal ops, {} lines, {} errors)",
+                session.fingerprint,
+                session.first_request_id,
+                session.connects,
+                session.journal_ops,
+                session.lines,
+                session.errors
+            )?;
+            writeln!(f, "    users: {}", join(&session.users))?;
+            writeln!(f, "    requests: {}", join(&session.request_ids))?;
+        }
+        writeln!(
+            f,
+            "unauthenticated journal ops: {}",
+            self.unauthenticated.len()
+        )?;
+        for op in &self.unauthenticated {
+            writeln!(
+                f,
+                "  request {}: {} for {}",
+                op.request_id, op.op, op.user_id
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const SOURCE: &str = r#"
+System::Trace SendRequest "login me" requestid=3
+App::Trace Connect 0a0b0c requestid=3
+App::Trace Connect 0d0e0f requestid=4
+System::Error AccessDenied "not authorized" requestid=4
+App::Journal CreateUser {"user_id": "Alice", "authorized_capital": 5000,} requestid=3
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Trace SendRequest "SellAsset" requestid=9
+App::Journal SellAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":3,},} requestid=9
+App::Fatal Panic "sell failed" requestid=9
+App::Journal DepositCash UserCash{"user_id":"Mallory","count":500,} requestid=11
+App::Trace Connect 0a0b0c requestid=12
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=12
+"#;
+
+    fn sessions() -> Sessions {
+        accumulate(SOURCE)
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let fingerprint = Fingerprint::of(&AuthData(vec![0x0a, 0x0b, 0x0c]));
+        assert_eq!(fingerprint.to_string(), "9909ec831e2cf6d0");
+        assert_ne!(
+            fingerprint,
+            Fingerprint::of(&AuthData(vec![0x0d, 0x0e, 0x0f]))
+        );
+    }
+
+    #[test]
+    fn test_sessions_follow_users() {
+        let sessions = sessions();
+        let [alice, bob] = sessions.sessions()[..] else {
+            panic!("expected two sessions");
+        };
+        assert_eq!((alice.first_request_id, alice.connects), (nz(3), 2));
+        assert_eq!(alice.users, BTreeSet::from([UserId("Alice".into())]));
+        assert_eq!(alice.request_ids, BTreeSet::from([nz(3), nz(12)]));
+        assert_eq!(bob.request_ids, BTreeSet::from([nz(4), nz(9)]));
+        assert_eq!(bob.journal_ops, 2);
+        // the trace before request 9 was tied to Bob's session is counted too
+        assert_eq!((alice.lines, alice.errors), (5, 0));
+        assert_eq!((bob.lines, bob.errors), (6, 2));
+        assert_eq!(
+            sessions.session_of(nz(9)).map(|s| s.fingerprint),
+            Some(bob.fingerprint)
+        );
+        assert_eq!(sessions.session_of(nz(11)), None);
+    }
+
+    #[test]
+    fn test_unauthenticated_ops() {
+        let sessions = sessions();
+        assert_eq!(
+            sessions.unauthenticated(),
+            [UnauthenticatedOp {
+                request_id: nz(11),
+                op: "DepositCash".into(),
+                user_id: UserId("Mallory".into()),
+            }]
+        );
+        assert!(sessions.to_json().ends_with(
+            r#""unauthenticated":[{"request_id":11,"op":"DepositCash","user_id":"Mallory"}]}"#
+        ));
+    }
+}
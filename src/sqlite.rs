@@ -0,0 +1,396 @@
+use std::path::Path;
+
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use sha2::{Digest, Sha256};
+
+use crate::parse::*;
+
+/// Normalized schema: one row per line in `lines`, plus one row in the table
+/// matching its category. `Check` announcements are flattened into
+/// `announcements` (one per user) and `backets` (one per asset and count).
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sources (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    line_count INTEGER NOT NULL,
+    prefix_hash BLOB NOT NULL
+);
+CREATE TABLE IF NOT EXISTS lines (
+    id INTEGER PRIMARY KEY,
+    source_id INTEGER NOT NULL REFERENCES sources(id),
+    seq INTEGER NOT NULL,
+    request_id INTEGER NOT NULL,
+    source TEXT NOT NULL,
+    category TEXT NOT NULL,
+    variant TEXT NOT NULL,
+    text TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS attributes (
+    line_id INTEGER NOT NULL REFERENCES lines(id),
+    key TEXT NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS journal_ops (
+    line_id INTEGER PRIMARY KEY REFERENCES lines(id),
+    op TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    asset_id TEXT,
+    count INTEGER,
+    amount_minor INTEGER,
+    currency TEXT
+);
+CREATE TABLE IF NOT EXISTS errors (
+    line_id INTEGER PRIMARY KEY REFERENCES lines(id),
+    kind TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS traces (
+    line_id INTEGER PRIMARY KEY REFERENCES lines(id),
+    kind TEXT NOT NULL,
+    payload TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS announcements (
+    id INTEGER PRIMARY KEY,
+    line_id INTEGER NOT NULL REFERENCES lines(id),
+    user_id TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS backets (
+    announcement_id INTEGER NOT NULL REFERENCES announcements(id),
+    asset_id TEXT NOT NULL,
+    count INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS lines_request_id ON lines(request_id);
+CREATE INDEX IF NOT EXISTS journal_ops_user_id ON journal_ops(user_id);
+CREATE INDEX IF NOT EXISTS journal_ops_asset_id ON journal_ops(asset_id);
+CREATE INDEX IF NOT EXISTS announcements_user_id ON announcements(user_id);
+CREATE INDEX IF NOT EXISTS backets_asset_id ON backets(asset_id);
+";
+
+/// SQLite database that parsed logs are exported into, one source file at a time.
+pub struct SqliteExport {
+    conn: Connection,
+}
+
+impl SqliteExport {
+    /// Opens (or creates) the database at `path` and ensures the schema exists.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// In-memory database, mostly useful for tests.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(SqliteExport { conn })
+    }
+
+    /// Underlying connection, for ad-hoc queries.
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Returns `true` if a source with this path was already imported.
+    pub fn is_imported(&self, source: &str) -> rusqlite::Result<bool> {
+        self.conn
+            .prepare_cached("SELECT 1 FROM sources WHERE path = ?1")?
+            .exists([source])
+    }
+
+    /// Number of lines imported so far from `source`, `None` if it never was;
+    /// a multi-line entry counts once.
+    pub fn imported_lines(&self, source: &str) -> rusqlite::Result<Option<usize>> {
+        self.conn
+            .prepare_cached("SELECT line_count FROM sources WHERE path = ?1")?
+            .query_row([source], |row| row.get::<_, i64>(0))
+            .map(|count| Some(count as usize))
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })
+    }
+
+    /// Imports the lines of `logs` past those already imported under the
+    /// `source` label, so a log that grew since the last run only adds its
+    /// new lines; `logs` holds the whole source. Returns the number of lines
+    /// added, or `None` without importing anything if `logs` no longer
+    /// starts with the lines imported before, e.g. after a rotation.
+    pub fn append(&mut self, source: &str, logs: &[LogLine]) -> rusqlite::Result<Option<usize>> {
+        let stored = self
+            .conn
+            .prepare_cached("SELECT id, line_count, prefix_hash FROM sources WHERE path = ?1")?
+            .query_row([source], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)? as usize, row.get::<_, Vec<u8>>(2)?))
+            })
+            .optional()?;
+        let Some((source_id, imported, hash)) = stored else {
+            self.import(source, logs)?;
+            return Ok(Some(logs.len()));
+        };
+        match logs.get(..imported) {
+            Some(prefix) if prefix_hash(prefix) == hash => {}
+            _ => return Ok(None),
+        }
+        let new = &logs[imported..];
+        if new.is_empty() {
+            return Ok(Some(0));
+        }
+        let tx = self.conn.transaction()?;
+        for (seq, log) in new.iter().enumerate() {
+            insert_line(&tx, source_id, imported + seq, log)?;
+        }
+        tx.execute(
+            "UPDATE sources SET line_count = ?1, prefix_hash = ?2 WHERE id = ?3",
+            params![logs.len() as i64, prefix_hash(logs), source_id],
+        )?;
+        tx.commit()?;
+        Ok(Some(new.len()))
+    }
+
+    /// Imports `logs` under the `source` label in a single transaction.
+    /// Fails with a constraint error if `source` was already imported.
+    pub fn import(&mut self, source: &str, logs: &[LogLine]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO sources (path, line_count, prefix_hash) VALUES (?1, ?2, ?3)",
+            params![source, logs.len() as i64, prefix_hash(logs)],
+        )?;
+        let source_id = tx.last_insert_rowid();
+        for (seq, log) in logs.iter().enumerate() {
+            insert_line(&tx, source_id, seq, log)?;
+        }
+        tx.commit()
+    }
+}
+
+/// SHA-256 of the lines as written back, one per line, to tell whether a
+/// source still starts with what was imported from it.
+fn prefix_hash(logs: &[LogLine]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    for log in logs {
+        hasher.update(log.to_log_string());
+        hasher.update(b"\n");
+    }
+    hasher.finalize().to_vec()
+}
+
+fn insert_line(tx: &Transaction, source_id: i64, seq: usize, log: &LogLine) -> rusqlite::Result<()> {
+    let kind = &log.kind;
+    tx.prepare_cached(
+        "INSERT INTO lines (source_id, seq, request_id, source, category, variant, text)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+    )?
+    .execute(params![
+        source_id,
+        seq as i64,
+        log.request_id.get(),
+        kind.source(),
+        kind.category(),
+        kind.variant(),
+        log.to_string(),
+    ])?;
+    let line_id = tx.last_insert_rowid();
+
+    for attribute in &log.attributes {
+        tx.prepare_cached("INSERT INTO attributes (line_id, key, value) VALUES (?1, ?2, ?3)")?
+            .execute(params![line_id, attribute.key, attribute.text()])?;
+    }
+    if let Some(msg) = kind.error_message() {
+        tx.prepare_cached("INSERT INTO errors (line_id, kind, message) VALUES (?1, ?2, ?3)")?
+            .execute(params![line_id, kind.variant(), msg])?;
+    }
+    match kind {
+        LogKind::System(SystemLogKind::Error(_)) | LogKind::App(AppLogKind::Error(_)) => {}
+        LogKind::System(SystemLogKind::Trace(
+            SystemLogTraceKind::SendRequest(payload) | SystemLogTraceKind::GetResponse(payload),
+        ))
+        | LogKind::App(AppLogKind::Trace(
+            AppLogTraceKind::SendRequest(payload) | AppLogTraceKind::GetResponse(payload),
+        ))
+        | LogKind::Unknown {
+            raw_payload: payload,
+            ..
+        } => insert_trace(tx, line_id, kind.variant(), payload)?,
+        LogKind::Custom { payload, .. } => {
+            insert_trace(tx, line_id, kind.variant(), &payload.to_log_string())?;
+        }
+        LogKind::System(
+            SystemLogKind::Debug(_)
+            | SystemLogKind::Info(_)
+            | SystemLogKind::Warn(_)
+            | SystemLogKind::Fatal(_),
+        )
+        | LogKind::App(
+            AppLogKind::Debug(_) | AppLogKind::Info(_) | AppLogKind::Warn(_) | AppLogKind::Fatal(_),
+        ) => insert_trace(tx, line_id, kind.variant(), kind.message().unwrap_or_default())?,
+        LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(auth))) => {
+            insert_trace(tx, line_id, kind.variant(), &auth.to_string())?;
+        }
+        LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) => {
+            insert_trace(tx, line_id, kind.variant(), &announcements.to_string())?;
+            for offer in announcements.user_backets() {
+                tx.prepare_cached("INSERT INTO announcements (line_id, user_id) VALUES (?1, ?2)")?
+                    .execute(params![line_id, offer.user_id.0])?;
+                let announcement_id = tx.last_insert_rowid();
+                for backet in &offer.backets {
+                    tx.prepare_cached(
+                        "INSERT INTO backets (announcement_id, asset_id, count) VALUES (?1, ?2, ?3)",
+                    )?
+                    .execute(params![announcement_id, backet.asset_id.0, backet.count.get()])?;
+                }
+            }
+        }
+        LogKind::App(AppLogKind::Journal(journal)) => {
+            let (user_id, asset_id, count, money) = journal_columns(journal);
+            tx.prepare_cached(
+                "INSERT INTO journal_ops (line_id, op, user_id, asset_id, count, amount_minor, currency)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?
+            .execute(params![
+                line_id,
+                kind.variant(),
+                user_id,
+                asset_id,
+                count,
+                money.map(|money| money.minor()),
+                money
+                    .map(|money| money.currency())
+                    .filter(|&currency| currency != Currency::UNSPECIFIED)
+                    .map(|currency| currency.to_string())
+            ])?;
+        }
+    }
+    Ok(())
+}
+
+fn insert_trace(tx: &Transaction, line_id: i64, kind: &str, payload: &str) -> rusqlite::Result<()> {
+    tx.prepare_cached("INSERT INTO traces (line_id, kind, payload) VALUES (?1, ?2, ?3)")?
+        .execute(params![line_id, kind, payload])?;
+    Ok(())
+}
+
+/// `(user_id, asset_id, count, money)` of a journal operation; `count` is
+/// the asset count of a trade, `money` the capital, liquidity or cash, stored
+/// exactly as minor units and currency, NULL when the log wrote none.
+fn journal_columns(
+    journal: &AppLogJournalKind,
+) -> (&str, Option<&str>, Option<u32>, Option<Money>) {
+    match journal {
+        AppLogJournalKind::CreateUser {
+            user_id,
+            authorized_capital,
+        } => (&user_id.0, None, None, Some(*authorized_capital)),
+        AppLogJournalKind::DeleteUser { user_id } => (&user_id.0, None, None, None),
+        AppLogJournalKind::RegisterAsset {
+            asset_id,
+            user_id,
+            liquidity,
+        } => (&user_id.0, Some(&asset_id.0), None, Some(*liquidity)),
+        AppLogJournalKind::UnregisterAsset { asset_id, user_id } => {
+            (&user_id.0, Some(&asset_id.0), None, None)
+        }
+        AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+            (&cash.user_id.0, None, None, Some(cash.count))
+        }
+        AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => (
+            &trade.user_id.0,
+            Some(&trade.backet.asset_id.0),
+            Some(trade.backet.count.get()),
+            None,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ReadMode, read_log};
+
+    const SOURCE: &str = r#"
+System::Error NetworkError "network interface is down" requestid=1
+System::Trace GetResponse "HTTP 200" requestid=3 latency_ms=12
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=10
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+"#;
+
+    fn count(export: &SqliteExport, sql: &str) -> i64 {
+        export.connection().query_row(sql, [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_import_normalized_tables() {
+        let logs = read_log(SOURCE.as_bytes(), ReadMode::All, vec![]).unwrap();
+        let mut export = SqliteExport::open_in_memory().unwrap();
+        export.import("a.log", &logs).unwrap();
+
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM lines"), 5);
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM errors"), 1);
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM traces"), 2);
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM backets"), 1);
+        assert_eq!(
+            count(
+                &export,
+                "SELECT COUNT(*) FROM attributes WHERE key = 'latency_ms' AND value = '12'"
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &export,
+                "SELECT count FROM journal_ops WHERE op = 'BuyAsset' AND asset_id = 'milk'"
+            ),
+            5
+        );
+        assert_eq!(
+            count(
+                &export,
+                "SELECT amount_minor FROM journal_ops WHERE op = 'CreateUser' AND currency IS NULL"
+            ),
+            100_000
+        );
+        assert_eq!(
+            count(&export, "SELECT COUNT(*) FROM lines WHERE request_id = 10"),
+            2
+        );
+    }
+
+    #[test]
+    fn test_incremental_import() {
+        let logs = read_log(SOURCE.as_bytes(), ReadMode::All, vec![]).unwrap();
+        let mut export = SqliteExport::open_in_memory().unwrap();
+        export.import("a.log", &logs).unwrap();
+        assert!(export.is_imported("a.log").unwrap());
+        assert!(!export.is_imported("b.log").unwrap());
+        assert!(export.import("a.log", &logs).is_err());
+
+        export.import("b.log", &logs[..2]).unwrap();
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM lines"), 7);
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM sources"), 2);
+    }
+
+    #[test]
+    fn test_append_only_new_lines() {
+        let logs = read_log(SOURCE.as_bytes(), ReadMode::All, vec![]).unwrap();
+        let mut export = SqliteExport::open_in_memory().unwrap();
+        assert_eq!(export.imported_lines("a.log").unwrap(), None);
+        assert_eq!(export.append("a.log", &logs[..3]).unwrap(), Some(3));
+        assert_eq!(export.append("a.log", &logs[..3]).unwrap(), Some(0));
+        assert_eq!(export.append("a.log", &logs).unwrap(), Some(2));
+        assert_eq!(export.imported_lines("a.log").unwrap(), Some(5));
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM lines"), 5);
+        assert_eq!(count(&export, "SELECT MAX(seq) FROM lines"), 4);
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM journal_ops"), 2);
+        // a source that shrank, or was rewritten with as many lines or
+        // more, adds nothing
+        assert_eq!(export.append("a.log", &logs[..1]).unwrap(), None);
+        let mut rotated = logs.clone();
+        rotated.reverse();
+        rotated.extend(logs.iter().cloned());
+        assert_eq!(export.append("a.log", &rotated).unwrap(), None);
+        assert_eq!(export.imported_lines("a.log").unwrap(), Some(5));
+        assert_eq!(count(&export, "SELECT COUNT(*) FROM lines"), 5);
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::Accumulator;
+use crate::json;
+use crate::parse::*;
+
+/// Summary counters over a stream of [LogLine]s.
+///
+/// Feed lines one at a time with [Stats::add]; the accumulator keeps only
+/// counters and distinct-value sets, never the lines themselves.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    /// Total number of lines seen.
+    pub lines: usize,
+    /// Lines per source (`System` / `App`).
+    pub by_source: BTreeMap<String, usize>,
+    /// Lines per variant, keyed as in the log grammar, e.g. `System::Error NetworkError`.
+    pub by_variant: BTreeMap<String, usize>,
+    /// Lines of severity `Error` or worse per kind, e.g. `App::LackOf`.
+    pub errors: BTreeMap<String, usize>,
+    /// Lines the grammar only knows by their skeleton ([LogKind::Unknown]),
+    /// keyed like `by_variant`.
+    pub unknown: BTreeMap<String, usize>,
+    pub request_ids: BTreeSet<NonZeroU32>,
+    pub users: BTreeSet<UserId>,
+    pub assets: BTreeSet<AssetId>,
+    /// Units bought plus units sold per asset, from `BuyAsset`/`SellAsset` journal entries.
+    pub traded_units: BTreeMap<AssetId, u64>,
+}
+
+impl Accumulator for Stats {
+    /// Accounts a single log line.
+    fn add(&mut self, log: &LogLine) {
+        let kind = &log.kind;
+        self.lines += 1;
+        *self.by_source.entry(kind.source().to_owned()).or_default() += 1;
+        let variant = format!("{}::{} {}", kind.source(), kind.category(), kind.variant());
+        if let LogKind::Unknown { .. } = kind {
+            *self.unknown.entry(variant.clone()).or_default() += 1;
+        }
+        *self.by_variant.entry(variant).or_default() += 1;
+        if kind.severity() >= Severity::Error {
+            *self
+                .errors
+                .entry(format!("{}::{}", kind.source(), kind.variant()))
+                .or_default() += 1;
+        }
+        self.request_ids.insert(log.request_id);
+
+        match kind {
+            LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) => {
+                for offer in announcements.user_backets() {
+                    self.users.insert(offer.user_id.clone());
+                    self.assets
+                        .extend(offer.backets.iter().map(|backet| backet.asset_id.clone()));
+                }
+            }
+            LogKind::App(AppLogKind::Journal(journal)) => self.add_journal(journal),
+            _ => {}
+        }
+    }
+}
+
+impl Stats {
+    fn add_journal(&mut self, journal: &AppLogJournalKind) {
+        match journal {
+            AppLogJournalKind::CreateUser { user_id, .. }
+            | AppLogJournalKind::DeleteUser { user_id } => {
+                self.users.insert(user_id.clone());
+            }
+            AppLogJournalKind::RegisterAsset {
+                asset_id, user_id, ..
+            }
+            | AppLogJournalKind::UnregisterAsset { asset_id, user_id } => {
+                self.users.insert(user_id.clone());
+                self.assets.insert(asset_id.clone());
+            }
+            AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+                self.users.insert(cash.user_id.clone());
+            }
+            AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => {
+                self.users.insert(trade.user_id.clone());
+                self.assets.insert(trade.backet.asset_id.clone());
+                *self
+                    .traded_units
+                    .entry(trade.backet.asset_id.clone())
+                    .or_default() += u64::from(trade.backet.count.get());
+            }
+        }
+    }
+
+    /// Renders the summary as a single JSON object.
+    pub fn to_json(&self) -> String {
+        let counts = |map: &BTreeMap<String, usize>| {
+            json::object(map.iter().map(|(key, n)| (key, n.to_string())))
+        };
+        json::object([
+            ("lines", self.lines.to_string()),
+            ("by_source", counts(&self.by_source)),
+            ("by_variant", counts(&self.by_variant)),
+            ("errors", counts(&self.errors)),
+            ("unknown", counts(&self.unknown)),
+            ("request_ids", self.request_ids.len().to_string()),
+            (
+                "users",
+                json::array(self.users.iter().map(|user| json::string(&user.0))),
+            ),
+            (
+                "assets",
+                json::array(self.assets.iter().map(|asset| json::string(&asset.0))),
+            ),
+            (
+                "traded_units",
+                json::object(
+                    self.traded_units
+                        .iter()
+                        .map(|(asset, units)| (&asset.0, units.to_string())),
+                ),
+            ),
+        ])
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn join<T: fmt::Display>(items: impl IntoIterator<Item = T>) -> String {
+            items
+                .into_iter()
+                .map(|item| item.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+
+        writeln!(f, "lines: {}", self.lines)?;
+        for (source, n) in &self.by_source {
+            writeln!(f, "  {source}: {n}")?;
+        }
+        writeln!(f, "by variant:")?;
+        for (variant, n) in &self.by_variant {
+            writeln!(f, "  {variant}: {n}")?;
+        }
+        writeln!(f, "errors:")?;
+        for (kind, n) in &self.errors {
+            writeln!(f, "  {kind}: {n}")?;
+        }
+        if !self.unknown.is_empty() {
+            writeln!(f, "unknown variants:")?;
+            for (variant, n) in &self.unknown {
+                writeln!(f, "  {variant}: {n}")?;
+            }
+        }
+        writeln!(f, "request ids: {}", self.request_ids.len())?;
+        writeln!(f, "users ({}): {}", self.users.len(), join(&self.users))?;
+        writeln!(f, "assets ({}): {}", self.assets.len(), join(&self.assets))?;
+        writeln!(f, "traded units:")?;
+        for (asset, units) in &self.traded_units {
+            writeln!(f, "  {asset}: {units}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::*;
+
+    const SOURCE: &str = r#"
+System::Error NetworkError "network interface is down" requestid=1
+App::Error SystemError "network" requestid=1
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Journal RegisterAsset {"asset_id": "milk", "user_id": "Bob", "liquidity": 10000,} requestid=5
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=10
+App::Journal SellAsset UserBacket{"user_id":"Bob","backet":Backet{"asset_id":"milk","count":3,},} requestid=9
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+App::Trace Heartbeat requestid=11
+"#;
+
+    fn stats() -> Stats {
+        accumulate(SOURCE)
+    }
+
+    #[test]
+    fn test_stats_counters() {
+        let stats = stats();
+        assert_eq!(stats.lines, 8);
+        assert_eq!(stats.by_source["System"], 1);
+        assert_eq!(stats.by_source["App"], 7);
+        assert_eq!(stats.by_variant["App::Journal BuyAsset"], 1);
+        assert_eq!(stats.errors["System::NetworkError"], 1);
+        assert_eq!(stats.errors["App::SystemError"], 1);
+        let fatal: Stats = accumulate("App::Fatal Panic \"boom\" requestid=1\n");
+        assert_eq!(fatal.errors["App::Panic"], 1);
+        assert_eq!(stats.unknown["App::Trace Heartbeat"], 1);
+        assert_eq!(stats.by_variant["App::Trace Heartbeat"], 1);
+        assert_eq!(stats.request_ids.len(), 6);
+        assert_eq!(
+            stats.users,
+            BTreeSet::from([UserId("Alice".into()), UserId("Bob".into())])
+        );
+        assert_eq!(stats.assets, BTreeSet::from([AssetId("milk".into())]));
+        assert_eq!(stats.traded_units[&AssetId("milk".into())], 8);
+    }
+
+    #[test]
+    fn test_stats_json() {
+        let json = stats().to_json();
+        assert!(json.starts_with(r#"{"lines":8,"by_source":{"App":7,"System":1},"#), "{json}");
+        assert!(json.ends_with(r#""traded_units":{"milk":8}}"#), "{json}");
+    }
+}
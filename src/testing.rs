@@ -0,0 +1,18 @@
+//! Fixtures shared by the unit tests of the report modules.
+
+use std::num::NonZeroU32;
+
+use crate::{Accumulator, ReadMode, read_log};
+
+/// Feeds every line of `source` into a fresh accumulator.
+pub fn accumulate<A: Accumulator + Default>(source: &str) -> A {
+    let mut accumulator = A::default();
+    for log in &read_log(source.as_bytes(), ReadMode::All, vec![]).unwrap() {
+        accumulator.add(log);
+    }
+    accumulator
+}
+
+pub fn nz(n: u32) -> NonZeroU32 {
+    NonZeroU32::new(n).unwrap()
+}
@@ -0,0 +1,415 @@
+use std::io::{self, Read};
+use std::num::NonZeroU32;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::parse::*;
+use crate::{LogFilter, LogIterator, ReadMode};
+
+/// Loads every line from `logs` and runs the interactive browser until the
+/// user quits. The terminal is restored even if drawing fails.
+///
+/// Keys: `j`/`k`/arrows, `PgUp`/`PgDn`, `g`/`G` move the selection; `m`
+/// cycles the [ReadMode]; `/` edits the text filter; `r`/`Enter` shows only
+/// the selected request; `n`/`p` jump to the next/previous request;
+/// `J`/`K` scroll the detail pane; `q` quits.
+pub fn run<R: Read>(logs: LogIterator<R>, mode: ReadMode) -> io::Result<()> {
+    let mut app = App::new(logs.collect::<io::Result<_>>()?, mode);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Normal,
+    Filter,
+}
+
+struct App {
+    logs: Vec<LogLine>,
+    /// Rendered `logs`, used for the list and the text filter.
+    texts: Vec<String>,
+    mode: ReadMode,
+    filter: String,
+    /// Set while the list is narrowed to a single request.
+    request: Option<NonZeroU32>,
+    /// Indices into `logs` that pass all filters.
+    visible: Vec<usize>,
+    list: ListState,
+    input: Input,
+    detail_scroll: u16,
+    /// Height of the list viewport, for paging.
+    page: usize,
+    quit: bool,
+}
+
+impl App {
+    fn new(logs: Vec<LogLine>, mode: ReadMode) -> Self {
+        let texts = logs.iter().map(ToString::to_string).collect();
+        let mut app = App {
+            logs,
+            texts,
+            mode,
+            filter: String::new(),
+            request: None,
+            visible: Vec::new(),
+            list: ListState::default(),
+            input: Input::Normal,
+            detail_scroll: 0,
+            page: 10,
+            quit: false,
+        };
+        app.refilter();
+        app
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key.code);
+            }
+        }
+        Ok(())
+    }
+
+    /// Index into `logs` of the selected line.
+    fn selected(&self) -> Option<usize> {
+        self.list
+            .selected()
+            .and_then(|pos| self.visible.get(pos).copied())
+    }
+
+    /// Recomputes `visible`, keeping the selection on the same line when it
+    /// still passes, or on the next one that does.
+    fn refilter(&mut self) {
+        let selected = self.selected();
+        self.visible = (0..self.logs.len())
+            .filter(|&i| {
+                let log = &self.logs[i];
+                self.mode.accepts(log)
+                    && self.request.is_none_or(|id| log.request_id == id)
+                    && self.texts[i].contains(&self.filter)
+            })
+            .collect();
+        let pos = selected.map_or(0, |i| self.visible.partition_point(|&v| v < i));
+        self.select(pos);
+    }
+
+    fn select(&mut self, pos: usize) {
+        self.list
+            .select((!self.visible.is_empty()).then(|| pos.min(self.visible.len() - 1)));
+        self.detail_scroll = 0;
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let pos = self.list.selected().unwrap_or(0);
+        self.select(pos.saturating_add_signed(delta));
+    }
+
+    fn request_at(&self, pos: usize) -> NonZeroU32 {
+        self.logs[self.visible[pos]].request_id
+    }
+
+    /// Moves to the first line of the next (or previous) request in the list.
+    fn jump_request(&mut self, forward: bool) {
+        let Some(mut pos) = self.list.selected() else {
+            return;
+        };
+        let current = self.request_at(pos);
+        if forward {
+            while pos + 1 < self.visible.len() && self.request_at(pos) == current {
+                pos += 1;
+            }
+        } else {
+            while pos > 0 && self.request_at(pos) == current {
+                pos -= 1;
+            }
+            let previous = self.request_at(pos);
+            while pos > 0 && self.request_at(pos - 1) == previous {
+                pos -= 1;
+            }
+        }
+        self.select(pos);
+    }
+
+    fn handle_key(&mut self, code: KeyCode) {
+        if self.input == Input::Filter {
+            match code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter => self.input = Input::Normal,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.input = Input::Normal;
+                }
+                _ => return,
+            }
+            return self.refilter();
+        }
+        match code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if self.request.is_some() => {
+                self.request = None;
+                self.refilter();
+            }
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(self.page as isize),
+            KeyCode::PageUp => self.move_by(-(self.page as isize)),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Char('J') => self.detail_scroll = self.detail_scroll.saturating_add(1),
+            KeyCode::Char('K') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            KeyCode::Char('n') => self.jump_request(true),
+            KeyCode::Char('p') => self.jump_request(false),
+            KeyCode::Char('/') => self.input = Input::Filter,
+            KeyCode::Char('m') => {
+                self.mode = match self.mode {
+                    ReadMode::All => ReadMode::Errors,
+                    ReadMode::Errors => ReadMode::Exchanges,
+                    ReadMode::Exchanges => ReadMode::All,
+                };
+                self.refilter();
+            }
+            KeyCode::Char('r') | KeyCode::Enter => {
+                self.request = match self.request {
+                    Some(_) => None,
+                    None => self.selected().map(|i| self.logs[i].request_id),
+                };
+                self.refilter();
+            }
+            _ => {}
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main);
+        self.page = usize::from(list_area.height.saturating_sub(2)).max(1);
+
+        let items = self.visible.iter().map(|&i| {
+            let style = match self.logs[i].kind.error_message() {
+                Some(_) => Style::new().fg(Color::Red),
+                None => Style::new(),
+            };
+            ListItem::new(self.texts[i].as_str()).style(style)
+        });
+        let title = format!(" {} / {} lines ", self.visible.len(), self.logs.len());
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let detail = self
+            .selected()
+            .map(|i| detail_lines(&self.logs[i]).join("\n"))
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(detail)
+                .block(Block::bordered().title(" detail "))
+                .wrap(Wrap { trim: false })
+                .scroll((self.detail_scroll, 0)),
+            detail_area,
+        );
+
+        let mode = match self.mode {
+            ReadMode::All => "all",
+            ReadMode::Errors => "errors",
+            ReadMode::Exchanges => "exchanges",
+        };
+        let request = self.request.map_or("-".to_owned(), |id| id.to_string());
+        let line = match self.input {
+            Input::Normal => format!(
+                "[m]ode: {mode}  [/]filter: {}  [r]equest: {request}  [n]ext/[p]rev request  [q]uit",
+                self.filter
+            ),
+            Input::Filter => format!("/{}", self.filter),
+        };
+        frame.render_widget(Paragraph::new(line), status);
+        if self.input == Input::Filter {
+            let x = status
+                .x
+                .saturating_add(1 + self.filter.chars().count() as u16);
+            frame.set_cursor_position(Position::new(x, status.y));
+        }
+    }
+}
+
+/// Indented field-per-line view of a log line, with nested domain values
+/// (announcements, backets) expanded.
+fn detail_lines(log: &LogLine) -> Vec<String> {
+    let kind = &log.kind;
+    let mut out = vec![
+        format!("request_id: {}", log.request_id),
+        format!("{}::{} {}", kind.source(), kind.category(), kind.variant()),
+    ];
+    for attribute in &log.attributes {
+        out.insert(out.len() - 1, format!("{}: {}", attribute.key, attribute.text()));
+    }
+    let mut field = |depth: usize, text: String| out.push(format!("{}{text}", "  ".repeat(depth)));
+    match kind {
+        LogKind::System(SystemLogKind::Error(_)) | LogKind::App(AppLogKind::Error(_)) => {
+            field(
+                1,
+                format!("message: {:?}", kind.error_message().unwrap_or_default()),
+            );
+        }
+        LogKind::System(SystemLogKind::Trace(
+            SystemLogTraceKind::SendRequest(payload) | SystemLogTraceKind::GetResponse(payload),
+        ))
+        | LogKind::App(AppLogKind::Trace(
+            AppLogTraceKind::SendRequest(payload) | AppLogTraceKind::GetResponse(payload),
+        )) => field(1, format!("payload: {payload:?}")),
+        LogKind::Unknown { raw_payload, .. } => field(1, format!("raw_payload: {raw_payload}")),
+        LogKind::Custom { payload, .. } => field(1, format!("payload: {payload:?}")),
+        LogKind::System(
+            SystemLogKind::Debug(_)
+            | SystemLogKind::Info(_)
+            | SystemLogKind::Warn(_)
+            | SystemLogKind::Fatal(_),
+        )
+        | LogKind::App(
+            AppLogKind::Debug(_) | AppLogKind::Info(_) | AppLogKind::Warn(_) | AppLogKind::Fatal(_),
+        ) => field(1, format!("message: {:?}", kind.message().unwrap_or_default())),
+        LogKind::App(AppLogKind::Trace(AppLogTraceKind::Connect(auth))) => {
+            field(1, format!("auth_data: {auth}"));
+        }
+        LogKind::App(AppLogKind::Trace(AppLogTraceKind::Check(announcements))) => {
+            field(1, "announcements:".to_owned());
+            for offer in announcements.user_backets() {
+                field(2, format!("- user_id: {:?}", offer.user_id.0));
+                field(3, "backets:".to_owned());
+                for backet in &offer.backets {
+                    field(4, format!("- asset_id: {:?}", backet.asset_id.0));
+                    field(5, format!("count: {}", backet.count));
+                }
+            }
+        }
+        LogKind::App(AppLogKind::Journal(journal)) => match journal {
+            AppLogJournalKind::CreateUser {
+                user_id,
+                authorized_capital,
+            } => {
+                field(1, format!("user_id: {:?}", user_id.0));
+                field(1, format!("authorized_capital: {authorized_capital}"));
+            }
+            AppLogJournalKind::DeleteUser { user_id } => {
+                field(1, format!("user_id: {:?}", user_id.0));
+            }
+            AppLogJournalKind::RegisterAsset {
+                asset_id,
+                user_id,
+                liquidity,
+            } => {
+                field(1, format!("asset_id: {:?}", asset_id.0));
+                field(1, format!("user_id: {:?}", user_id.0));
+                field(1, format!("liquidity: {liquidity}"));
+            }
+            AppLogJournalKind::UnregisterAsset { asset_id, user_id } => {
+                field(1, format!("asset_id: {:?}", asset_id.0));
+                field(1, format!("user_id: {:?}", user_id.0));
+            }
+            AppLogJournalKind::DepositCash(cash) | AppLogJournalKind::WithdrawCash(cash) => {
+                field(1, format!("user_id: {:?}", cash.user_id.0));
+                field(1, format!("count: {}", cash.count));
+            }
+            AppLogJournalKind::BuyAsset(trade) | AppLogJournalKind::SellAsset(trade) => {
+                field(1, format!("user_id: {:?}", trade.user_id.0));
+                field(1, "backet:".to_owned());
+                field(2, format!("asset_id: {:?}", trade.backet.asset_id.0));
+                field(2, format!("count: {}", trade.backet.count));
+            }
+        },
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Utf8Policy;
+
+    const SOURCE: &str = r#"
+System::Error NetworkError "network interface is down" requestid=1
+System::Trace GetResponse "HTTP 200" requestid=1
+App::Journal CreateUser {"user_id": "Bob", "authorized_capital": 1000,} requestid=4
+App::Trace Check [UserBackets{"user_id":"Bob","backets":[Backet{"asset_id":"milk","count":3,},],},] requestid=10
+App::Journal BuyAsset UserBacket{"user_id":"Alice","backet":Backet{"asset_id":"milk","count":5,},} requestid=10
+"#;
+
+    fn app() -> App {
+        let logs = LogIterator::new(SOURCE.as_bytes(), Utf8Policy::Lossy);
+        App::new(logs.collect::<io::Result<_>>().unwrap(), ReadMode::All)
+    }
+
+    #[test]
+    fn test_mode_and_text_filter() {
+        let mut app = app();
+        assert_eq!(app.visible, [0, 1, 2, 3, 4]);
+        app.handle_key(KeyCode::Char('m'));
+        assert_eq!(app.visible, [0]);
+        app.handle_key(KeyCode::Char('m'));
+        assert_eq!(app.visible, [2, 4]);
+        app.handle_key(KeyCode::Char('/'));
+        "Alice"
+            .chars()
+            .for_each(|c| app.handle_key(KeyCode::Char(c)));
+        assert_eq!(app.visible, [4]);
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(
+            (app.input, app.visible.as_slice()),
+            (Input::Normal, &[2, 4][..])
+        );
+    }
+
+    #[test]
+    fn test_request_navigation() {
+        let mut app = app();
+        app.handle_key(KeyCode::Char('n'));
+        assert_eq!(app.selected(), Some(2));
+        app.handle_key(KeyCode::Char('n'));
+        assert_eq!(app.selected(), Some(3));
+        app.handle_key(KeyCode::Char('r'));
+        assert_eq!(app.visible, [3, 4]);
+        assert_eq!(app.selected(), Some(3));
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.visible.len(), 5);
+        app.handle_key(KeyCode::Char('G'));
+        app.handle_key(KeyCode::Char('p'));
+        assert_eq!(app.selected(), Some(2));
+        app.handle_key(KeyCode::Char('p'));
+        assert_eq!(app.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_detail_expands_announcements() {
+        let app = app();
+        assert_eq!(
+            detail_lines(&app.logs[3]),
+            [
+                "request_id: 10",
+                "App::Trace Check",
+                "  announcements:",
+                "    - user_id: \"Bob\"",
+                "      backets:",
+                "        - asset_id: \"milk\"",
+                "          count: 3",
+            ]
+        );
+    }
+}